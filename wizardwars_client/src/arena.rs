@@ -1,25 +1,42 @@
-use crate::camera::{CameraTarget, FollowCamera};
+use crate::{
+    camera::{CameraTarget, FollowCamera},
+    interpolation::SnapshotBuffer,
+    lobby::LobbyEvent,
+    prediction::Prediction,
+};
 use bevy::prelude::*;
 use bevy_mod_picking::{PickableBundle, PickingCameraBundle};
 use std::collections::HashMap;
 use wizardwars_shared::{
-    components::Uuid,
+    components::{Mana, Position, Uuid},
     events::{InsertPlayerEvent, SpawnEvent},
     resources::CharacterDimensions,
+    spells::{SpellDefinition, Spells},
 };
 
 pub struct LocalPlayer;
 
+pub struct SafeZoneChanged {
+    pub radius: f32,
+}
+
+struct SafeZoneRing {
+    mesh: Handle<Mesh>,
+}
+
 pub struct ArenaPlugin;
 
 impl Plugin for ArenaPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.add_event::<InsertPlayerEvent>()
             .add_event::<SpawnEvent>()
+            .add_event::<SafeZoneChanged>()
             .add_startup_system(setup_world_system.system())
+            .add_system(update_safe_zone_system.system())
             .add_system(apply_pickable.system())
             .add_system(spawn_player_system.system())
-            .add_system(handle_spawn_events.system());
+            .add_system(handle_spawn_events.system())
+            .add_system(teardown_match_system.system());
     }
 }
 
@@ -97,6 +114,9 @@ fn spawn_player_system(
                 .insert(id);
             if is_local {
                 cmd.entity(entity).insert(LocalPlayer).insert(CameraTarget);
+            } else {
+                // Other players can be right-clicked to attack them.
+                cmd.entity(entity).insert_bundle(PickableBundle::default());
             }
         }
     }
@@ -107,12 +127,14 @@ fn handle_spawn_events(
     mut cmd: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    spells: Res<Spells>,
 ) {
     for event in events.iter() {
         match event {
-            SpawnEvent::Projectile(id) => {
-                spawn_projectile(&mut cmd, &mut meshes, &mut materials, *id)
-            }
+            SpawnEvent::Projectile { id, spell } => match spells.get(spell) {
+                Some(spell) => spawn_projectile(&mut cmd, &mut meshes, &mut materials, *id, spell),
+                None => warn!("Unknown spell: {:?}", spell),
+            },
         }
     }
 }
@@ -122,10 +144,11 @@ fn spawn_projectile(
     meshes: &mut ResMut<Assets<Mesh>>,
     materials: &mut ResMut<Assets<StandardMaterial>>,
     id: Uuid,
+    spell: &SpellDefinition,
 ) {
     cmd.spawn_bundle(PbrBundle {
         mesh: meshes.add(Mesh::from(shape::Icosphere {
-            radius: 0.1,
+            radius: spell.radius,
             subdivisions: 2,
         })),
         transform: Transform::identity(),
@@ -134,3 +157,80 @@ fn spawn_projectile(
     })
     .insert(id);
 }
+
+fn safe_zone_mesh(radius: f32) -> Mesh {
+    Mesh::from(shape::Torus {
+        radius,
+        ring_radius: 0.05,
+        subdivisions_segments: 64,
+        subdivisions_sides: 8,
+    })
+}
+
+fn update_safe_zone_system(
+    mut cmd: Commands,
+    mut events: EventReader<SafeZoneChanged>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    rings: Query<&SafeZoneRing>,
+) {
+    let radius = match events.iter().last() {
+        Some(event) => event.radius,
+        None => return,
+    };
+
+    if let Ok(ring) = rings.single() {
+        if let Some(mesh) = meshes.get_mut(&ring.mesh) {
+            *mesh = safe_zone_mesh(radius);
+        }
+        return;
+    }
+
+    let mesh = meshes.add(safe_zone_mesh(radius));
+    cmd.spawn_bundle(PbrBundle {
+        mesh: mesh.clone(),
+        transform: Transform::from_xyz(0.0, 0.02, 0.0),
+        material: materials.add(Color::rgb(1.0, 0.35, 0.0).into()),
+        ..Default::default()
+    })
+    .insert(SafeZoneRing { mesh });
+}
+
+/// Back in the lobby the players stay, everything they got for the match goes away.
+fn teardown_match_system(
+    mut cmd: Commands,
+    mut events: EventReader<LobbyEvent>,
+    players: Query<(Entity, Option<&Children>), (With<Uuid>, With<Name>)>,
+    others: Query<Entity, (With<Uuid>, Without<Name>)>,
+    rings: Query<Entity, With<SafeZoneRing>>,
+) {
+    if !events
+        .iter()
+        .any(|event| matches!(event, LobbyEvent::ReturnedToLobby))
+    {
+        return;
+    }
+
+    for (entity, children) in players.iter() {
+        for &child in children.iter().flat_map(|children| children.iter()) {
+            cmd.entity(child).despawn_recursive();
+        }
+        cmd.entity(entity)
+            .remove_bundle::<PbrBundle>()
+            .remove_bundle::<PickableBundle>()
+            .remove::<Children>()
+            .remove::<LocalPlayer>()
+            .remove::<CameraTarget>()
+            .remove::<SnapshotBuffer>()
+            .remove::<Prediction>()
+            .remove::<Position>()
+            .remove::<Mana>();
+    }
+    // Projectiles still in flight when the match ended.
+    for entity in others.iter() {
+        cmd.entity(entity).despawn_recursive();
+    }
+    for ring in rings.iter() {
+        cmd.entity(ring).despawn_recursive();
+    }
+}
@@ -1,7 +1,38 @@
 use bevy::prelude::*;
+use structopt::StructOpt;
 
-use wizardwars_client::ClientPlugin;
+use wizardwars_client::{ClientPlugin, ClientSettings};
+use wizardwars_shared::{items::Items, replay::Replay, spells::Spells};
 
 fn main() {
-    App::build().add_plugin(ClientPlugin).run();
+    let settings = ClientSettings::from_args();
+    let spells = match Spells::from_file(&settings.spells) {
+        Ok(spells) => spells,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let items = match Items::from_file(&settings.items) {
+        Ok(items) => items,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let replay = match settings.replay.as_deref().map(Replay::from_file) {
+        Some(Ok(replay)) => Some(replay),
+        Some(Err(err)) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        None => None,
+    };
+
+    let mut plugin = ClientPlugin::new(settings, spells, items);
+    if let Some(replay) = replay {
+        plugin = plugin.with_replay(replay);
+    }
+    App::build().add_plugin(plugin).run();
 }
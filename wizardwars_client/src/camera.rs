@@ -1,4 +1,8 @@
 use bevy::prelude::*;
+
+/// Units per second the camera pans when it has no target.
+const FREE_CAMERA_SPEED: f32 = 10.0;
+
 pub struct CameraTarget;
 pub struct FollowCamera {
     pub target: Vec3,
@@ -10,12 +14,13 @@ pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_system_to_stage(
-            CoreStage::PostUpdate,
-            update_camera_target
-                .system()
-                .chain(camera_follow_system.system()),
-        );
+        app.add_system(free_camera_system.system())
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                update_camera_target
+                    .system()
+                    .chain(camera_follow_system.system()),
+            );
     }
 }
 
@@ -38,3 +43,35 @@ pub fn update_camera_target(
         camera.target = target.translation;
     }
 }
+
+/// Arrow keys move the camera while there is nobody to follow, e.g. when spectating.
+pub fn free_camera_system(
+    input: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    mut cameras: Query<&mut FollowCamera>,
+    targets: Query<(), With<CameraTarget>>,
+) {
+    if targets.iter().next().is_some() {
+        return;
+    }
+
+    let mut direction = Vec3::ZERO;
+    for (key, step) in [
+        (KeyCode::Up, -Vec3::Z),
+        (KeyCode::Down, Vec3::Z),
+        (KeyCode::Left, -Vec3::X),
+        (KeyCode::Right, Vec3::X),
+    ] {
+        if input.pressed(key) {
+            direction += step;
+        }
+    }
+    if direction == Vec3::ZERO {
+        return;
+    }
+
+    let step = direction.normalize() * FREE_CAMERA_SPEED * time.delta_seconds();
+    for mut camera in cameras.iter_mut() {
+        camera.target += step;
+    }
+}
@@ -0,0 +1,119 @@
+use crate::{reconnect::SwitchServer, settings::ClientSettings};
+use bevy::prelude::*;
+use std::net::SocketAddr;
+use wizardwars_shared::{
+    network::{DiscoveryClient, ServerAnnouncement},
+    version::GameVersion,
+};
+
+/// Pressed together with the left control key to join the server with that number.
+const SERVER_KEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+/// Servers that answered on the LAN, in the order they answered.
+#[derive(Default)]
+pub struct DiscoveredServers(pub Vec<(SocketAddr, ServerAnnouncement)>);
+
+pub struct DiscoveryPlugin;
+
+impl Plugin for DiscoveryPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let client = match DiscoveryClient::bind() {
+            Ok(client) => client,
+            Err(err) => {
+                warn!("LAN discovery is not available: {}", err);
+                return;
+            }
+        };
+
+        app.insert_resource(client)
+            .insert_resource(DiscoveredServers::default())
+            .add_startup_system(discover_on_startup_system.system())
+            .add_system(discovery_input_system.system())
+            .add_system(receive_announcements_system.system());
+    }
+}
+
+fn query(client: &DiscoveryClient, servers: &mut DiscoveredServers, port: u16) {
+    info!("Looking for servers on the LAN...");
+    servers.0.clear();
+    if let Err(err) = client.broadcast(port) {
+        warn!("Cannot send discovery query: {}", err);
+    }
+}
+
+fn discover_on_startup_system(
+    client: Res<DiscoveryClient>,
+    mut servers: ResMut<DiscoveredServers>,
+    settings: Res<ClientSettings>,
+) {
+    if settings.discover {
+        query(&client, &mut servers, settings.discovery_port());
+    }
+}
+
+fn discovery_input_system(
+    input: Res<Input<KeyCode>>,
+    client: Res<DiscoveryClient>,
+    settings: Res<ClientSettings>,
+    mut servers: ResMut<DiscoveredServers>,
+    mut switches: EventWriter<SwitchServer>,
+) {
+    if input.just_pressed(KeyCode::D) {
+        query(&client, &mut servers, settings.discovery_port());
+    }
+
+    if !input.pressed(KeyCode::LControl) {
+        return;
+    }
+    for (key, (address, _)) in SERVER_KEYS.iter().zip(servers.0.iter()) {
+        if input.just_pressed(*key) {
+            switches.send(SwitchServer(*address));
+        }
+    }
+}
+
+fn receive_announcements_system(
+    client: Res<DiscoveryClient>,
+    version: Res<GameVersion>,
+    mut servers: ResMut<DiscoveredServers>,
+) {
+    let announcements = match client.receive() {
+        Ok(announcements) => announcements,
+        Err(err) => {
+            warn!("Cannot receive discovery answers: {}", err);
+            return;
+        }
+    };
+
+    for (address, announcement) in announcements {
+        if servers.0.iter().any(|(known, _)| *known == address) {
+            continue;
+        }
+        let compatibility = if announcement.version == *version {
+            ""
+        } else {
+            ", incompatible"
+        };
+        info!(
+            "{}. {} at {} ({}/{} players, {}{})",
+            servers.0.len() + 1,
+            announcement.name,
+            address,
+            announcement.players,
+            announcement.max_players,
+            announcement.state,
+            compatibility
+        );
+        servers.0.push((address, announcement));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::prediction::Prediction;
+use bevy::prelude::*;
+use std::collections::VecDeque;
+use wizardwars_shared::{components::Uuid, events::InsertPlayerEvent, network::Tick};
+
+/// Snapshots older than this are dropped from the buffer.
+const BUFFER_SIZE: usize = 32;
+
+#[derive(Debug, Clone, Copy)]
+pub struct InterpolationSettings {
+    /// How far in the past remote entities are rendered, in seconds.
+    pub delay: f32,
+    /// How long to keep moving an entity when no new snapshots arrive, in seconds.
+    pub max_extrapolation: f32,
+}
+
+/// Estimates the current server tick from the ticks stamped on received updates.
+#[derive(Debug, Default)]
+pub struct ServerClock {
+    tick_rate: u32,
+    /// Server tick at local time zero.
+    offset: Option<f64>,
+}
+
+impl ServerClock {
+    pub fn set_tick_rate(&mut self, tick_rate: u32) {
+        self.tick_rate = tick_rate;
+        self.offset = None;
+    }
+
+    pub fn tick_rate(&self) -> u32 {
+        self.tick_rate
+    }
+
+    pub fn observe(&mut self, tick: Tick, now: f64) {
+        let sample = tick.0 as f64 - now * self.tick_rate as f64;
+        self.offset = Some(match self.offset {
+            // Updates arriving late only nudge the estimate, early ones mean it was too far behind.
+            Some(offset) if sample < offset => offset + (sample - offset) * 0.05,
+            _ => sample,
+        });
+    }
+
+    /// Fractional server tick for the given local time, `None` until the first update arrives.
+    pub fn tick_at(&self, now: f64) -> Option<f64> {
+        self.offset
+            .map(|offset| offset + now * self.tick_rate as f64)
+    }
+}
+
+/// Last positions received from the server for one entity, ordered by tick.
+#[derive(Debug, Default)]
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<(Tick, Vec3)>,
+}
+
+impl SnapshotBuffer {
+    pub fn push(&mut self, tick: Tick, position: Vec3) {
+        let index = self
+            .snapshots
+            .iter()
+            .rposition(|&(t, _)| t <= tick)
+            .map_or(0, |index| index + 1);
+        if index > 0 && self.snapshots[index - 1].0 == tick {
+            return;
+        }
+
+        self.snapshots.insert(index, (tick, position));
+        if self.snapshots.len() > BUFFER_SIZE {
+            self.snapshots.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+
+    /// Position at the given fractional tick. Positions past the newest snapshot are extrapolated
+    /// for at most `max_extrapolation` ticks.
+    pub fn sample(&self, tick: f64, max_extrapolation: f64) -> Option<Vec3> {
+        let &(first_tick, first) = self.snapshots.front()?;
+        if tick <= first_tick.0 as f64 {
+            return Some(first);
+        }
+
+        let next = self.snapshots.iter().position(|&(t, _)| t.0 as f64 >= tick);
+        let (from, to, tick) = match next {
+            Some(index) => (self.snapshots[index - 1], self.snapshots[index], tick),
+            None if self.snapshots.len() >= 2 => {
+                let last = self.snapshots.len() - 1;
+                let newest = self.snapshots[last].0 .0 as f64;
+                let tick = tick.min(newest + max_extrapolation);
+                (self.snapshots[last - 1], self.snapshots[last], tick)
+            }
+            None => return Some(first),
+        };
+
+        let ((from_tick, from), (to_tick, to)) = (from, to);
+        let span = (to_tick.0 - from_tick.0) as f64;
+        let t = (tick - from_tick.0 as f64) / span;
+        Some(from.lerp(to, t as f32))
+    }
+}
+
+pub struct InterpolationPlugin {
+    pub settings: InterpolationSettings,
+}
+
+impl Plugin for InterpolationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(self.settings)
+            .insert_resource(ServerClock::default())
+            .add_system(reset_buffers_system.system())
+            .add_system(interpolate_positions_system.system());
+    }
+}
+
+/// Players are teleported to their spawn points at the start of every round.
+fn reset_buffers_system(
+    mut events: EventReader<InsertPlayerEvent>,
+    mut query: Query<(&Uuid, &mut SnapshotBuffer)>,
+) {
+    for event in events.iter() {
+        for (id, mut buffer) in query.iter_mut() {
+            if *id == event.id {
+                buffer.clear();
+            }
+        }
+    }
+}
+
+fn interpolate_positions_system(
+    settings: Res<InterpolationSettings>,
+    clock: Res<ServerClock>,
+    time: Res<Time>,
+    mut query: Query<(&SnapshotBuffer, &mut Transform), Without<Prediction>>,
+) {
+    let tick_rate = clock.tick_rate() as f64;
+    let render_tick = match clock.tick_at(time.seconds_since_startup()) {
+        Some(tick) => tick - settings.delay as f64 * tick_rate,
+        None => return,
+    };
+    let max_extrapolation = settings.max_extrapolation as f64 * tick_rate;
+
+    for (buffer, mut transform) in query.iter_mut() {
+        if let Some(position) = buffer.sample(render_tick, max_extrapolation) {
+            transform.translation = position;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer() -> SnapshotBuffer {
+        let mut buffer = SnapshotBuffer::default();
+        buffer.push(Tick(10), Vec3::new(0.0, 0.0, 0.0));
+        buffer.push(Tick(14), Vec3::new(4.0, 0.0, 0.0));
+        buffer
+    }
+
+    #[test]
+    fn interpolate_between_snapshots() {
+        let buffer = buffer();
+
+        assert_eq!(buffer.sample(5.0, 0.0), Some(Vec3::ZERO));
+        assert_eq!(buffer.sample(12.0, 0.0), Some(Vec3::new(2.0, 0.0, 0.0)));
+        assert_eq!(buffer.sample(14.0, 0.0), Some(Vec3::new(4.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn extrapolation_is_limited() {
+        let buffer = buffer();
+
+        assert_eq!(buffer.sample(16.0, 4.0), Some(Vec3::new(6.0, 0.0, 0.0)));
+        assert_eq!(buffer.sample(30.0, 4.0), Some(Vec3::new(8.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn out_of_order_snapshots_are_sorted() {
+        let mut buffer = SnapshotBuffer::default();
+        buffer.push(Tick(14), Vec3::new(4.0, 0.0, 0.0));
+        buffer.push(Tick(10), Vec3::ZERO);
+        buffer.push(Tick(14), Vec3::new(100.0, 0.0, 0.0));
+
+        assert_eq!(buffer.sample(12.0, 0.0), Some(Vec3::new(2.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn clock_follows_newest_tick() {
+        let mut clock = ServerClock::default();
+        clock.set_tick_rate(10);
+        assert_eq!(clock.tick_at(0.0), None);
+
+        clock.observe(Tick(100), 1.0);
+        assert!((clock.tick_at(2.0).unwrap() - 110.0).abs() < 0.0001);
+
+        clock.observe(Tick(120), 2.0);
+        assert!((clock.tick_at(2.0).unwrap() - 120.0).abs() < 0.0001);
+    }
+}
@@ -0,0 +1,64 @@
+use bevy::prelude::*;
+use wizardwars_shared::{
+    components::{damage::DamageKind, Uuid},
+    messages::server_messages::KillFeedEntry,
+};
+
+pub struct KillFeedPlugin;
+
+impl Plugin for KillFeedPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<KillFeedEntry>()
+            .add_system(kill_feed_system.system());
+    }
+}
+
+fn describe(entry: &KillFeedEntry, name_of: impl Fn(Uuid) -> String) -> String {
+    let victim = name_of(entry.victim);
+    match (entry.killer, entry.kind) {
+        (Some(killer), DamageKind::Burn) => format!("{} burned {}", name_of(killer), victim),
+        (Some(killer), _) => format!("{} knocked out {}", name_of(killer), victim),
+        (None, DamageKind::Environment) => format!("{} was swallowed by the arena", victim),
+        (None, _) => format!("{} was knocked out", victim),
+    }
+}
+
+fn kill_feed_system(mut events: EventReader<KillFeedEntry>, players: Query<(&Uuid, &Name)>) {
+    let name_of = |id: Uuid| {
+        players
+            .iter()
+            .find(|(&player, _)| player == id)
+            .map_or_else(
+                || format!("Player {}", id.0),
+                |(_, name)| name.as_str().to_owned(),
+            )
+    };
+
+    for entry in events.iter() {
+        info!("{}", describe(entry, &name_of));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_name_both_players() {
+        let name_of = |id: Uuid| format!("P{}", id.0);
+        let entry = |killer, kind| KillFeedEntry {
+            victim: Uuid(1),
+            killer,
+            kind,
+        };
+
+        assert_eq!(
+            describe(&entry(Some(Uuid(2)), DamageKind::Spell), name_of),
+            "P2 knocked out P1"
+        );
+        assert_eq!(
+            describe(&entry(None, DamageKind::Environment), name_of),
+            "P1 was swallowed by the arena"
+        );
+    }
+}
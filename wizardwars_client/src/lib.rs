@@ -1,4 +1,4 @@
-use arena::ArenaPlugin;
+use arena::{ArenaPlugin, LocalPlayer};
 use bevy::prelude::*;
 use bevy_inspector_egui::WorldInspectorPlugin;
 use bevy_mod_picking::{
@@ -7,28 +7,86 @@ use bevy_mod_picking::{
 };
 use bevy_networking_turbulence::NetworkResource;
 use camera::CameraPlugin;
+use discovery::DiscoveryPlugin;
+use interpolation::{InterpolationPlugin, InterpolationSettings, SnapshotBuffer};
+use kill_feed::KillFeedPlugin;
 use lobby::LobbyPlugin;
-use network::{read_component_channel_system, NetworkPlugin};
+use network::NetworkPlugin;
+use prediction::{Prediction, PredictionPlugin};
+use reconnect::ReconnectPlugin;
+use replay::ReplayPlugin;
+use rooms::RoomsPlugin;
+use shop::ShopPlugin;
+use spectator::SpectatorPlugin;
+use status_effects::StatusEffectsPlugin;
 use wizardwars_shared::{
-    components::{Position, ReadyState},
+    components::{BotDifficulty, Position, ReadyState, Uuid},
+    items::Items,
     messages::client_messages::{ActionMessage, ClientMessage, LobbyClientMessage},
     resources::{ArenaDimensions, CharacterDimensions},
+    spells::{SpellId, Spells},
+    version::GameVersion,
 };
 
 mod arena;
 mod camera;
+mod discovery;
+mod interpolation;
+mod kill_feed;
 mod lobby;
 mod network;
+mod prediction;
+mod reconnect;
+mod replay;
+mod rooms;
+mod settings;
+mod shop;
+mod spectator;
+mod status_effects;
 
-pub struct ClientPlugin;
+pub use settings::ClientSettings;
+
+pub struct SelectedSpell(pub Option<SpellId>);
+
+pub struct ClientPlugin {
+    settings: ClientSettings,
+    spells: Spells,
+    items: Items,
+    replay: Option<Replay>,
+}
+
+impl ClientPlugin {
+    pub fn new(settings: ClientSettings, spells: Spells, items: Items) -> Self {
+        Self {
+            settings,
+            spells,
+            items,
+            replay: None,
+        }
+    }
+
+    /// Plays the replay instead of connecting to a server.
+    pub fn with_replay(mut self, replay: Replay) -> Self {
+        self.replay = Some(replay);
+        self
+    }
+}
 
 impl Plugin for ClientPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.insert_resource(WindowDescriptor {
-            width: 800.0,
-            height: 600.0,
+            width: self.settings.width,
+            height: self.settings.height,
+            title: format!("Wizard Wars - {}", self.settings.name),
             ..Default::default()
         })
+        .insert_resource(self.settings.clone())
+        .insert_resource(SelectedSpell(
+            self.spells.iter().next().map(|spell| spell.id.clone()),
+        ))
+        .insert_resource(self.spells.clone())
+        .insert_resource(self.items.clone())
+        .insert_resource(GameVersion::new(&self.spells, &self.items))
         .insert_resource(CharacterDimensions::default())
         .insert_resource(ArenaDimensions::default())
         .add_plugins(DefaultPlugins)
@@ -38,39 +96,97 @@ impl Plugin for ClientPlugin {
         .add_plugin(DebugEventsPickingPlugin)
         .add_plugin(CameraPlugin)
         .add_plugin(NetworkPlugin)
+        .add_plugin(ReconnectPlugin)
+        .add_plugin(RoomsPlugin)
+        .add_plugin(DiscoveryPlugin)
         .add_plugin(ArenaPlugin)
         .add_plugin(LobbyPlugin)
+        .add_plugin(ShopPlugin)
+        .add_plugin(SpectatorPlugin)
+        .add_plugin(StatusEffectsPlugin)
+        .add_plugin(KillFeedPlugin)
+        .add_plugin(PredictionPlugin)
+        .add_plugin(InterpolationPlugin {
+            settings: InterpolationSettings {
+                delay: self.settings.interpolation_delay as f32 / 1000.0,
+                max_extrapolation: self.settings.max_extrapolation as f32 / 1000.0,
+            },
+        })
         .add_plugin(WorldInspectorPlugin::new())
+        .add_system_to_stage(CoreStage::PreUpdate, select_spell_system.system())
         .add_system_to_stage(CoreStage::PreUpdate, input_system.system())
         .add_system_to_stage(CoreStage::PreUpdate, network_mock_input_system.system())
-        .add_system(update_translation_system.system())
-        .add_system_to_stage(
-            CoreStage::PreUpdate,
-            read_component_channel_system::<Position>.system(),
-        );
+        .add_system(update_translation_system.system());
+        if let Some(replay) = &self.replay {
+            app.add_plugin(ReplayPlugin {
+                replay: replay.clone(),
+            });
+        }
     }
 }
 
-fn update_translation_system(mut players: Query<(&Position, &mut Transform), Changed<Position>>) {
+/// Entities without a snapshot buffer are placed directly at their last known position.
+fn update_translation_system(
+    mut players: Query<(&Position, &mut Transform), (Changed<Position>, Without<SnapshotBuffer>)>,
+) {
     for (position, mut transform) in players.iter_mut() {
         transform.translation = position.0;
     }
 }
 
+const SPELL_KEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+fn select_spell_system(
+    input: Res<Input<KeyCode>>,
+    spells: Res<Spells>,
+    mut selected: ResMut<SelectedSpell>,
+) {
+    for (key, spell) in SPELL_KEYS.iter().zip(spells.iter()) {
+        if input.just_pressed(*key) {
+            info!("Selected spell: {}", spell.name);
+            selected.0 = Some(spell.id.clone());
+        }
+    }
+}
+
 fn input_system(
     mouse_input: Res<Input<MouseButton>>,
     mut net: ResMut<NetworkResource>,
+    selected_spell: Res<SelectedSpell>,
     camera_query: Query<&PickingCamera>,
+    mut prediction: Query<&mut Prediction>,
+    enemies: Query<&Uuid, Without<LocalPlayer>>,
 ) {
     if mouse_input.just_pressed(MouseButton::Right) {
-        let target = camera_query
+        let picked = camera_query
             .single()
             .ok()
-            .and_then(|camera| camera.intersect_top())
-            .map(|(_, intersect)| intersect.position());
+            .and_then(|camera| camera.intersect_top());
+        let enemy = picked.and_then(|(entity, _)| enemies.get(entity).ok());
 
-        if let Some(target) = target {
-            net.broadcast_message(ClientMessage::Action(ActionMessage::Move { target }));
+        if let Some(&target) = enemy {
+            if let Ok(mut prediction) = prediction.single_mut() {
+                prediction.stop();
+            }
+            net.broadcast_message(ClientMessage::Action(ActionMessage::Attack { target }));
+        } else if let Some(target) = picked.map(|(_, intersect)| intersect.position()) {
+            let sequence = prediction
+                .single_mut()
+                .map_or(0, |mut prediction| prediction.move_to(target));
+            net.broadcast_message(ClientMessage::Action(ActionMessage::Move {
+                target,
+                sequence,
+            }));
         }
     }
 
@@ -81,8 +197,14 @@ fn input_system(
             .and_then(|camera| camera.intersect_top())
             .map(|(_, intersect)| intersect.position());
 
-        if let Some(target) = target {
-            net.broadcast_message(ClientMessage::Action(ActionMessage::FireBall(target)));
+        if let (Some(target), Some(spell_id)) = (target, selected_spell.0.clone()) {
+            if let Ok(mut prediction) = prediction.single_mut() {
+                prediction.stop();
+            }
+            net.broadcast_message(ClientMessage::Action(ActionMessage::Cast {
+                spell_id,
+                target,
+            }));
         }
     }
 }
@@ -99,6 +221,15 @@ fn network_mock_input_system(input: Res<Input<KeyCode>>, mut net: ResMut<Network
     }
 
     if input.just_pressed(KeyCode::B) {
-        net.broadcast_message(ClientMessage::LobbyMessage(LobbyClientMessage::AddBot));
+        let difficulty = if input.pressed(KeyCode::LShift) {
+            BotDifficulty::Hard
+        } else if input.pressed(KeyCode::LControl) {
+            BotDifficulty::Easy
+        } else {
+            BotDifficulty::Normal
+        };
+        net.broadcast_message(ClientMessage::LobbyMessage(LobbyClientMessage::AddBot(
+            difficulty,
+        )));
     }
 }
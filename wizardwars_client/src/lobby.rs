@@ -1,8 +1,14 @@
+use crate::settings::ClientSettings;
 use bevy::prelude::*;
-use wizardwars_shared::messages::client_messages::ClientMessage;
+use wizardwars_shared::{
+    components::ReadyState,
+    messages::client_messages::{ClientMessage, LobbyClientMessage},
+};
 
 pub enum LobbyEvent {
+    Joined,
     StartLoading,
+    ReturnedToLobby,
 }
 
 pub struct LobbyPlugin;
@@ -17,9 +23,17 @@ impl Plugin for LobbyPlugin {
 fn handle_events_system(
     mut lobby_events: EventReader<LobbyEvent>,
     mut packets: EventWriter<ClientMessage>,
+    settings: Res<ClientSettings>,
 ) {
     for event in lobby_events.iter() {
         match &event {
+            LobbyEvent::Joined | LobbyEvent::ReturnedToLobby => {
+                if settings.auto_ready {
+                    packets.send(ClientMessage::LobbyMessage(
+                        LobbyClientMessage::ChangeReadyState(ReadyState::Ready),
+                    ));
+                }
+            }
             LobbyEvent::StartLoading => {
                 packets.send(ClientMessage::Loaded);
             }
@@ -1,19 +1,29 @@
-use crate::lobby::LobbyEvent;
+use crate::{
+    arena::SafeZoneChanged,
+    interpolation::{ServerClock, SnapshotBuffer},
+    lobby::LobbyEvent,
+    reconnect::Session,
+    replay::ReplayInbox,
+    settings::ClientSettings,
+};
 use bevy::{app::AppExit, prelude::*};
 use bevy_networking_turbulence::{NetworkEvent, NetworkResource, NetworkingPlugin};
-use std::{
-    collections::HashMap,
-    net::{IpAddr, Ipv4Addr, SocketAddr},
-};
-use turbulence::message_channels::ChannelMessage;
+use std::collections::HashMap;
 use wizardwars_shared::{
     components::Uuid,
     events::{DespawnEntityEvent, InsertPlayerEvent, SpawnEvent},
     messages::{
-        client_messages::{ClientMessage, LobbyClientMessage},
+        client_messages::ClientMessage,
+        handshake::Handshake,
         network_channels_setup,
-        server_messages::{LobbyServerMessage, ServerMessage},
+        server_messages::{
+            KillFeedEntry, LobbyServerMessage, RejectReason, RoomServerMessage, ServerMessage,
+            ShoppingServerMessage, StatusEffectMessage,
+        },
+        ReplicationPlugin,
     },
+    network::{NetworkRole, SnapshotAck, SnapshotAssembler, SnapshotDelta, SnapshotHistory},
+    version::GameVersion,
 };
 
 pub struct NetworkPlugin;
@@ -22,65 +32,183 @@ impl Plugin for NetworkPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.add_event::<ClientMessage>()
             .add_event::<DespawnEntityEvent>()
+            .insert_resource(SnapshotHistory::default())
+            .insert_resource(NetworkRole::Client)
             .add_plugin(NetworkingPlugin {
                 idle_timeout_ms: Some(3000),
                 auto_heartbeat_ms: Some(1000),
                 ..Default::default()
             })
+            .add_plugin(ReplicationPlugin)
             .add_startup_system(network_channels_setup.system())
             .add_startup_system(client_setup_system.system())
             .add_system(handle_network_events_system.system())
+            .add_system(read_handshake_system.system())
             .add_system(send_packets_system.system())
-            .add_system(read_server_message_channel_system.system())
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                read_server_message_channel_system.system(),
+            )
+            .add_system_to_stage(CoreStage::PreUpdate, read_snapshots_system.system())
+            .add_system(seed_spawned_entities_system.system())
             .add_system(despawn_entities_system.system())
             .add_system_to_stage(CoreStage::Last, handle_app_exit_event.system());
     }
 }
 
-pub fn read_component_channel_system<C: ChannelMessage>(
+/// Rebuilds world snapshots from the received deltas, acknowledges them and applies them to
+/// the local entities.
+#[allow(clippy::too_many_arguments)]
+pub fn read_snapshots_system(
     mut cmd: Commands,
     mut net: ResMut<NetworkResource>,
-    players_query: Query<(&Uuid, Entity)>,
+    mut inbox: Option<ResMut<ReplayInbox>>,
+    mut history: ResMut<SnapshotHistory>,
+    mut assembler: Local<SnapshotAssembler>,
+    mut clock: ResMut<ServerClock>,
+    time: Res<Time>,
+    mut query: Query<(Entity, &Uuid, Option<&mut SnapshotBuffer>)>,
 ) {
-    let players: HashMap<&Uuid, Entity> = players_query.iter().map(|(id, e)| (id, e)).collect();
+    let entities = query
+        .iter_mut()
+        .map(|(entity, &id, _)| (id, entity))
+        .collect::<HashMap<_, _>>();
 
-    for (_, connection) in net.connections.iter_mut() {
+    let mut received = Vec::new();
+    for (&handle, connection) in net.connections.iter_mut() {
         let channels = connection.channels().unwrap();
-        while let Some((network_id, component)) = channels.recv::<(Uuid, C)>() {
-            match players.get(&network_id) {
-                Some(entity) => {
-                    cmd.entity(*entity).insert(component);
+        while let Some(part) = channels.recv::<SnapshotDelta>() {
+            if let Some(delta) = assembler.push(part) {
+                received.push((Some(handle), delta));
+            }
+        }
+    }
+    if let Some(inbox) = inbox.as_mut() {
+        received.extend(inbox.snapshots.drain(..).map(|delta| (None, delta)));
+    }
+    received.sort_by_key(|(_, delta)| delta.tick);
+
+    for (handle, delta) in received {
+        let latest = history.latest();
+        if latest.map_or(false, |latest| latest.tick >= delta.tick) {
+            continue;
+        }
+        let baseline = delta.baseline.and_then(|tick| history.get(tick));
+        let snapshot = match delta.apply(baseline) {
+            Some(snapshot) => snapshot,
+            None => continue,
+        };
+        if let Some(handle) = handle {
+            let _ = net.send_message(handle, SnapshotAck(snapshot.tick));
+        }
+        clock.observe(snapshot.tick, time.seconds_since_startup());
+
+        let previous = history.latest();
+        for (id, entity_snapshot) in snapshot.entities.iter() {
+            let entity = match entities.get(id) {
+                Some(&entity) => entity,
+                None => continue,
+            };
+            let old = previous
+                .and_then(|previous| previous.entities.get(id))
+                .cloned()
+                .unwrap_or_default();
+
+            if let Some(position) = entity_snapshot.position {
+                match query.get_mut(entity) {
+                    Ok((_, _, Some(mut buffer))) => buffer.push(snapshot.tick, position.0),
+                    _ => {
+                        let mut buffer = SnapshotBuffer::default();
+                        buffer.push(snapshot.tick, position.0);
+                        cmd.entity(entity).insert(buffer);
+                    }
                 }
-                None => {
-                    warn!("No player found with id: {:?}", network_id);
+                if old.position != Some(position) {
+                    cmd.entity(entity).insert(position);
                 }
             }
+            if let Some(mana) = entity_snapshot.mana.filter(|mana| old.mana != Some(*mana)) {
+                cmd.entity(entity).insert(mana);
+            }
+            if let Some(cooldowns) = entity_snapshot
+                .cooldowns
+                .as_ref()
+                .filter(|cooldowns| old.cooldowns.as_ref() != Some(cooldowns))
+            {
+                cmd.entity(entity).insert(cooldowns.clone());
+            }
         }
+
+        history.push(snapshot);
     }
 }
 
-fn client_setup_system(mut net: ResMut<NetworkResource>) {
-    let ip_address = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
-    let socket_address = SocketAddr::new(ip_address, 9001);
-    info!("Connecting to {}...", socket_address);
-    net.connect(socket_address);
+/// Spawn messages travel on the reliable channel and can arrive after the snapshots that
+/// already contain the entity. Later deltas only carry changes, so newly spawned entities
+/// start from the latest snapshot instead of waiting for their next update.
+fn seed_spawned_entities_system(
+    mut cmd: Commands,
+    history: Res<SnapshotHistory>,
+    spawned: Query<(Entity, &Uuid), (Added<Uuid>, Without<SnapshotBuffer>)>,
+) {
+    let latest = match history.latest() {
+        Some(latest) => latest,
+        None => return,
+    };
+    for (entity, id) in spawned.iter() {
+        let snapshot = match latest.entities.get(id) {
+            Some(snapshot) => snapshot,
+            None => continue,
+        };
+        if let Some(position) = snapshot.position {
+            let mut buffer = SnapshotBuffer::default();
+            buffer.push(latest.tick, position.0);
+            cmd.entity(entity).insert(position).insert(buffer);
+        }
+        if let Some(mana) = snapshot.mana {
+            cmd.entity(entity).insert(mana);
+        }
+        if let Some(cooldowns) = &snapshot.cooldowns {
+            cmd.entity(entity).insert(cooldowns.clone());
+        }
+    }
+}
+
+fn client_setup_system(
+    mut net: ResMut<NetworkResource>,
+    mut session: ResMut<Session>,
+    settings: Res<ClientSettings>,
+) {
+    session.set_spectator(settings.spectate);
+    if settings.discover || settings.replay.is_some() {
+        return;
+    }
+    info!("Connecting to {}...", settings.server);
+    net.connect(settings.server);
 }
 
 fn handle_network_events_system(
     mut net: ResMut<NetworkResource>,
     mut network_event_reader: EventReader<NetworkEvent>,
+    mut session: ResMut<Session>,
+    settings: Res<ClientSettings>,
+    version: Res<GameVersion>,
 ) {
     for event in network_event_reader.iter() {
         match event {
             NetworkEvent::Connected(handle) => match net.connections.get_mut(handle) {
                 Some(_connection) => {
+                    if !session.connected(*handle) {
+                        net.disconnect(*handle);
+                        continue;
+                    }
                     info!("Connection successful");
 
+                    net.send_message(*handle, Handshake::Hello(*version))
+                        .expect("Could not send handshake");
                     net.send_message(
                         *handle,
-                        ClientMessage::LobbyMessage(LobbyClientMessage::Join(
-                            "John Doe".to_owned(),
-                        )),
+                        ClientMessage::LobbyMessage(session.hello(*version, &settings.name)),
                     )
                     .expect("Could not send hello");
                 }
@@ -94,51 +222,158 @@ fn handle_network_events_system(
     }
 }
 
+fn log_version_mismatch(server_version: GameVersion, version: GameVersion) {
+    error!(
+        "Cannot join, the server runs {} but this client {}. \
+         Both need the same game version and spell and item files.",
+        server_version, version
+    );
+}
+
+fn read_handshake_system(
+    mut net: ResMut<NetworkResource>,
+    mut session: ResMut<Session>,
+    version: Res<GameVersion>,
+) {
+    let mut mismatched = Vec::new();
+    for (&handle, connection) in net.connections.iter_mut() {
+        let channels = connection.channels().unwrap();
+        while let Some(message) = channels.recv::<Handshake>() {
+            if let Handshake::VersionMismatch { server_version } = message {
+                log_version_mismatch(server_version, *version);
+                mismatched.push(handle);
+            }
+        }
+    }
+
+    for handle in mismatched {
+        session.close();
+        net.disconnect(handle);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn read_server_message_channel_system(
     mut cmd: Commands,
     mut net: ResMut<NetworkResource>,
     mut insert_player_events: EventWriter<InsertPlayerEvent>,
     mut remove_player_events: EventWriter<DespawnEntityEvent>,
     mut lobby_events: EventWriter<LobbyEvent>,
+    mut room_events: EventWriter<RoomServerMessage>,
     mut spawn_events: EventWriter<SpawnEvent>,
+    mut safe_zone_events: EventWriter<SafeZoneChanged>,
+    mut shopping_events: EventWriter<ShoppingServerMessage>,
+    mut status_effect_events: EventWriter<StatusEffectMessage>,
+    mut kill_feed_events: EventWriter<KillFeedEntry>,
+    mut clock: ResMut<ServerClock>,
+    mut session: ResMut<Session>,
+    mut inbox: Option<ResMut<ReplayInbox>>,
+    version: Res<GameVersion>,
+    settings: Res<ClientSettings>,
 ) {
-    let mut disconnected = Vec::new();
-    for (handle, connection) in net.connections.iter_mut() {
+    let mut received = Vec::new();
+    for (&handle, connection) in net.connections.iter_mut() {
         let channels = connection.channels().unwrap();
-
         while let Some(message) = channels.recv::<ServerMessage>() {
-            info!("Received message: {:?}", message);
-            match message {
-                ServerMessage::Lobby(msg) => match msg {
-                    LobbyServerMessage::Welcome(id) => {
-                        cmd.spawn().insert(id);
-                    }
-                    LobbyServerMessage::Reject { reason, disconnect } => {
-                        error!("Cannot perform action: {:?}", reason);
-                        if disconnect {
-                            disconnected.push(*handle);
-                        }
-                    }
-                    LobbyServerMessage::SetHost(_) => {}
-                    LobbyServerMessage::PlayerJoined(id, _) => {
-                        cmd.spawn().insert(id);
-                    }
-                    LobbyServerMessage::ReadyState(_) => {}
-                    LobbyServerMessage::StartLoading => {
-                        lobby_events.send(LobbyEvent::StartLoading);
+            received.push((Some(handle), message));
+        }
+    }
+    // Replayed messages have no connection to answer on.
+    if let Some(inbox) = inbox.as_mut() {
+        received.extend(inbox.messages.drain(..).map(|message| (None, message)));
+    }
+
+    let mut disconnected = Vec::new();
+    let mut rejoin = Vec::new();
+    for (handle, message) in received {
+        info!("Received message: {:?}", message);
+        match message {
+            ServerMessage::Lobby(msg) => match msg {
+                LobbyServerMessage::Welcome {
+                    id,
+                    tick_rate,
+                    reconnect_token,
+                } => {
+                    session.set_token(reconnect_token);
+                    clock.set_tick_rate(tick_rate);
+                    cmd.spawn()
+                        .insert(id)
+                        .insert(Name::new(settings.name.clone()));
+                    lobby_events.send(LobbyEvent::Joined);
+                }
+                LobbyServerMessage::Spectating { tick_rate } => {
+                    info!("Spectating");
+                    clock.set_tick_rate(tick_rate);
+                }
+                LobbyServerMessage::Reject {
+                    reason: RejectReason::VersionMismatch { server_version },
+                    ..
+                } => {
+                    log_version_mismatch(server_version, *version);
+                    session.close();
+                    disconnected.extend(handle);
+                }
+                LobbyServerMessage::Reject {
+                    reason: RejectReason::InvalidReconnectToken,
+                    ..
+                } => {
+                    warn!("The previous player is gone, joining as a new one");
+                    session.clear_token();
+                    rejoin.extend(handle);
+                }
+                LobbyServerMessage::Reject { reason, disconnect } => {
+                    error!("Cannot perform action: {:?}", reason);
+                    if disconnect {
+                        disconnected.extend(handle);
                     }
-                    LobbyServerMessage::PlayersList(_) => todo!(),
-                },
-                ServerMessage::Loading(_) => {}
-                ServerMessage::Shopping(_) => {}
-                ServerMessage::InsertPlayer(event) => {
-                    insert_player_events.send(event);
                 }
-                ServerMessage::Spawn(spawn) => {
-                    spawn_events.send(spawn);
+                LobbyServerMessage::SetHost(_) => {}
+                LobbyServerMessage::PlayerJoined(id, name) => {
+                    cmd.spawn().insert(id).insert(Name::new(name));
                 }
-                ServerMessage::Despawn(id) => {
-                    remove_player_events.send(DespawnEntityEvent { id });
+                LobbyServerMessage::ReadyState(_) => {}
+                LobbyServerMessage::StartLoading => {
+                    lobby_events.send(LobbyEvent::StartLoading);
+                }
+                LobbyServerMessage::ReturnToLobby => {
+                    lobby_events.send(LobbyEvent::ReturnedToLobby);
+                }
+                LobbyServerMessage::PlayersList(_) => todo!(),
+            },
+            ServerMessage::Room(msg) => {
+                room_events.send(msg);
+            }
+            ServerMessage::Loading(_) => {}
+            ServerMessage::Shopping(msg) => {
+                shopping_events.send(msg);
+            }
+            ServerMessage::InsertPlayer(event) => {
+                insert_player_events.send(event);
+            }
+            ServerMessage::Spawn(spawn) => {
+                spawn_events.send(spawn);
+            }
+            ServerMessage::Despawn(id) => {
+                remove_player_events.send(DespawnEntityEvent { id });
+            }
+            ServerMessage::ActionRejected(reason) => {
+                warn!("Action rejected: {:?}", reason);
+            }
+            ServerMessage::SafeZoneRadius(radius) => {
+                safe_zone_events.send(SafeZoneChanged { radius });
+            }
+            ServerMessage::Round { current, total } => {
+                info!("Round {}/{}", current, total);
+            }
+            ServerMessage::StatusEffect(msg) => {
+                status_effect_events.send(msg);
+            }
+            ServerMessage::KillFeed(entry) => {
+                kill_feed_events.send(entry);
+            }
+            ServerMessage::Results(results) => {
+                for (place, player) in results.scoreboard.iter().enumerate() {
+                    info!("{}. {} {:?}", place + 1, player.name, player.statistics);
                 }
             }
         }
@@ -147,6 +382,10 @@ fn read_server_message_channel_system(
     for handle in disconnected {
         net.disconnect(handle);
     }
+    for handle in rejoin {
+        let hello = session.hello(*version, &settings.name);
+        let _ = net.send_message(handle, ClientMessage::LobbyMessage(hello));
+    }
 }
 
 fn send_packets_system(mut net: ResMut<NetworkResource>, mut events: EventReader<ClientMessage>) {
@@ -184,7 +423,7 @@ fn despawn_entities_system(
         .collect::<HashMap<_, _>>();
     for event in events.iter() {
         if let Some(&entity) = map.get(&event.id) {
-            cmd.entity(entity).despawn();
+            cmd.entity(entity).despawn_recursive();
         } else {
             warn!("Trying to remove non existing entity: {:?}", event.id);
         }
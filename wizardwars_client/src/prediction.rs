@@ -0,0 +1,254 @@
+use bevy::prelude::*;
+use bevy_networking_turbulence::NetworkResource;
+use std::collections::VecDeque;
+use wizardwars_shared::{
+    components::{Stats, Uuid, BASE_MOVE_SPEED},
+    events::InsertPlayerEvent,
+    messages::server_messages::InputAck,
+    movement::{has_arrived, step_towards},
+};
+
+/// Errors below this distance are left alone.
+const TOLERANCE: f32 = 0.05;
+/// Errors above this distance are snapped instead of smoothed out.
+const SNAP_DISTANCE: f32 = 2.0;
+/// How fast the rendered position catches up with a correction, per second.
+const SMOOTHING: f32 = 10.0;
+const HISTORY_SIZE: usize = 256;
+
+/// Locally simulated movement of the player's own character.
+#[derive(Debug)]
+pub struct Prediction {
+    position: Vec3,
+    waypoint: Option<Vec3>,
+    sequence: u32,
+    /// Seconds since the last move was sent.
+    elapsed: f32,
+    /// Predicted positions since the last move, by seconds since it was sent.
+    history: VecDeque<(f32, Vec3)>,
+    /// Offset between the rendered and the predicted position, decays to zero.
+    visual_offset: Vec3,
+}
+
+impl Prediction {
+    pub fn new(position: Vec3) -> Self {
+        Self {
+            position,
+            waypoint: None,
+            sequence: 0,
+            elapsed: 0.0,
+            history: VecDeque::new(),
+            visual_offset: Vec3::ZERO,
+        }
+    }
+
+    /// Teleports to `position`, e.g. the spawn point of a new round. Sequence numbers keep
+    /// counting so acknowledgements from the previous round are ignored.
+    pub fn reset(&mut self, position: Vec3) {
+        *self = Self {
+            sequence: self.sequence,
+            ..Self::new(position)
+        };
+    }
+
+    /// Starts moving towards `target` and returns the sequence number to send with the move.
+    pub fn move_to(&mut self, target: Vec3) -> u32 {
+        self.sequence += 1;
+        self.waypoint = Some(target);
+        self.elapsed = 0.0;
+        self.history.clear();
+        self.history.push_back((0.0, self.position));
+
+        self.sequence
+    }
+
+    pub fn stop(&mut self) {
+        self.waypoint = None;
+    }
+
+    pub fn step(&mut self, speed: f32, dt: f32) {
+        if let Some(target) = self.waypoint {
+            self.position = step_towards(self.position, target, speed, dt);
+            if has_arrived(self.position, target) {
+                self.waypoint = None;
+            }
+        }
+
+        self.elapsed += dt;
+        self.history.push_back((self.elapsed, self.position));
+        if self.history.len() > HISTORY_SIZE {
+            self.history.pop_front();
+        }
+
+        self.visual_offset *= (1.0 - SMOOTHING * dt).max(0.0);
+    }
+
+    /// Compares the server position with what was predicted for the same moment and moves the
+    /// prediction by the difference.
+    pub fn reconcile(&mut self, ack: &InputAck) {
+        if ack.sequence != self.sequence {
+            return;
+        }
+        let predicted = match self.predicted_at(ack.elapsed) {
+            Some(predicted) => predicted,
+            None => return,
+        };
+
+        let error = ack.position - predicted;
+        if error.length() < TOLERANCE {
+            return;
+        }
+
+        self.position += error;
+        for (_, position) in self.history.iter_mut() {
+            *position += error;
+        }
+        if error.length() < SNAP_DISTANCE {
+            self.visual_offset -= error;
+        } else {
+            self.visual_offset = Vec3::ZERO;
+        }
+    }
+
+    pub fn rendered_position(&self) -> Vec3 {
+        self.position + self.visual_offset
+    }
+
+    fn predicted_at(&self, elapsed: f32) -> Option<Vec3> {
+        let next = self.history.iter().position(|&(time, _)| time >= elapsed);
+        match next {
+            Some(0) => self.history.front().map(|&(_, position)| position),
+            Some(index) => {
+                let (from_time, from) = self.history[index - 1];
+                let (to_time, to) = self.history[index];
+                let t = (elapsed - from_time) / (to_time - from_time);
+                Some(from.lerp(to, t))
+            }
+            None => self.history.back().map(|&(_, position)| position),
+        }
+    }
+}
+
+pub struct PredictionPlugin;
+
+impl Plugin for PredictionPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_to_stage(CoreStage::PreUpdate, read_input_acks_system.system())
+            .add_system(setup_prediction_system.system())
+            .add_system(predict_movement_system.system());
+    }
+}
+
+fn setup_prediction_system(
+    mut cmd: Commands,
+    mut events: EventReader<InsertPlayerEvent>,
+    mut query: Query<(Entity, &Uuid, Option<&mut Prediction>)>,
+) {
+    for event in events.iter().filter(|event| event.is_local) {
+        for (entity, id, prediction) in query.iter_mut() {
+            if *id != event.id {
+                continue;
+            }
+            match prediction {
+                Some(mut prediction) => prediction.reset(event.position),
+                None => {
+                    cmd.entity(entity).insert(Prediction::new(event.position));
+                }
+            }
+        }
+    }
+}
+
+fn read_input_acks_system(mut net: ResMut<NetworkResource>, mut query: Query<&mut Prediction>) {
+    for (_, connection) in net.connections.iter_mut() {
+        let channels = connection.channels().unwrap();
+        while let Some(ack) = channels.recv::<InputAck>() {
+            if let Ok(mut prediction) = query.single_mut() {
+                prediction.reconcile(&ack);
+            }
+        }
+    }
+}
+
+fn predict_movement_system(
+    time: Res<Time>,
+    mut query: Query<(&mut Prediction, &mut Transform, Option<&Stats>)>,
+) {
+    for (mut prediction, mut transform, stats) in query.iter_mut() {
+        let speed = stats.map_or(BASE_MOVE_SPEED, |stats| stats.move_speed);
+        prediction.step(speed, time.delta_seconds());
+        transform.translation = prediction.rendered_position();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ack(sequence: u32, elapsed: f32, position: Vec3) -> InputAck {
+        InputAck {
+            sequence,
+            elapsed,
+            position,
+        }
+    }
+
+    #[test]
+    fn predict_waypoint_movement() {
+        let mut prediction = Prediction::new(Vec3::ZERO);
+        assert_eq!(prediction.move_to(Vec3::new(10.0, 0.0, 0.0)), 1);
+        prediction.step(2.0, 0.5);
+        prediction.step(2.0, 0.5);
+
+        assert_eq!(prediction.rendered_position(), Vec3::new(2.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn matching_ack_changes_nothing() {
+        let mut prediction = Prediction::new(Vec3::ZERO);
+        let sequence = prediction.move_to(Vec3::new(10.0, 0.0, 0.0));
+        prediction.step(2.0, 0.5);
+        prediction.step(2.0, 0.5);
+        prediction.reconcile(&ack(sequence, 0.25, Vec3::new(0.5, 0.0, 0.0)));
+
+        assert_eq!(prediction.position, Vec3::new(2.0, 0.0, 0.0));
+        assert_eq!(prediction.visual_offset, Vec3::ZERO);
+    }
+
+    #[test]
+    fn mismatch_is_corrected_smoothly() {
+        let mut prediction = Prediction::new(Vec3::ZERO);
+        let sequence = prediction.move_to(Vec3::new(10.0, 0.0, 0.0));
+        prediction.step(2.0, 0.5);
+        prediction.reconcile(&ack(sequence, 0.5, Vec3::new(1.0, 0.0, 0.5)));
+
+        assert_eq!(prediction.position, Vec3::new(1.0, 0.0, 0.5));
+        assert_eq!(prediction.rendered_position(), Vec3::new(1.0, 0.0, 0.0));
+
+        prediction.stop();
+        for _ in 0..20 {
+            prediction.step(2.0, 0.05);
+        }
+        assert!(prediction.visual_offset.length() < 0.01);
+    }
+
+    #[test]
+    fn reset_keeps_sequence() {
+        let mut prediction = Prediction::new(Vec3::ZERO);
+        prediction.move_to(Vec3::new(10.0, 0.0, 0.0));
+        prediction.reset(Vec3::ONE);
+
+        assert_eq!(prediction.rendered_position(), Vec3::ONE);
+        assert_eq!(prediction.move_to(Vec3::ZERO), 2);
+    }
+
+    #[test]
+    fn stale_acks_are_ignored() {
+        let mut prediction = Prediction::new(Vec3::ZERO);
+        let sequence = prediction.move_to(Vec3::new(10.0, 0.0, 0.0));
+        prediction.move_to(Vec3::new(-10.0, 0.0, 0.0));
+        prediction.reconcile(&ack(sequence, 0.0, Vec3::new(5.0, 0.0, 0.0)));
+
+        assert_eq!(prediction.position, Vec3::ZERO);
+    }
+}
@@ -0,0 +1,299 @@
+use crate::settings::ClientSettings;
+use bevy::prelude::*;
+use bevy_networking_turbulence::{NetworkEvent, NetworkResource};
+use std::{net::SocketAddr, time::Duration};
+use wizardwars_shared::{
+    components::Uuid,
+    messages::{client_messages::LobbyClientMessage, server_messages::ReconnectToken},
+    network::SnapshotHistory,
+    version::GameVersion,
+};
+
+/// Seconds between two connection attempts after the connection dropped.
+const RETRY_INTERVAL: f32 = 2.0;
+const MAX_ATTEMPTS: u32 = 15;
+
+/// The connection to the server and what is needed to get it back after it drops.
+#[derive(Debug, Default)]
+pub struct Session {
+    /// Server of the joined room, the one from the settings until another room was joined.
+    address: Option<SocketAddr>,
+    handle: Option<u32>,
+    token: Option<ReconnectToken>,
+    retry: Option<Timer>,
+    attempts: u32,
+    /// The server refused this client, reconnecting would not help.
+    closed: bool,
+    /// Watch instead of joining as a player.
+    spectator: bool,
+}
+
+impl Session {
+    /// Returns `false` if another connection is in use already.
+    pub fn connected(&mut self, handle: u32) -> bool {
+        if self.handle.is_some() {
+            return false;
+        }
+        self.handle = Some(handle);
+        self.retry = None;
+        self.attempts = 0;
+
+        true
+    }
+
+    pub fn set_spectator(&mut self, spectator: bool) {
+        self.spectator = spectator;
+    }
+
+    /// The first message on a new connection, takes over the old player if there was one.
+    pub fn hello(&self, version: GameVersion, name: &str) -> LobbyClientMessage {
+        match self.token {
+            Some(token) => LobbyClientMessage::Reconnect { version, token },
+            None if self.spectator => LobbyClientMessage::Spectate {
+                version,
+                name: name.to_owned(),
+            },
+            None => LobbyClientMessage::Join {
+                version,
+                name: name.to_owned(),
+            },
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.handle.is_some()
+    }
+
+    pub fn address(&self, default: SocketAddr) -> SocketAddr {
+        self.address.unwrap_or(default)
+    }
+
+    /// Leaves the current server for another one, returns the connection to close.
+    pub fn switch_to(&mut self, address: SocketAddr) -> Option<u32> {
+        self.address = Some(address);
+        self.token = None;
+        self.retry = None;
+        self.attempts = 0;
+        self.closed = false;
+
+        self.handle.take()
+    }
+
+    pub fn set_token(&mut self, token: ReconnectToken) {
+        self.token = Some(token);
+    }
+
+    pub fn clear_token(&mut self) {
+        self.token = None;
+    }
+
+    pub fn close(&mut self) {
+        self.closed = true;
+        self.token = None;
+        self.retry = None;
+    }
+
+    /// Starts reconnecting if `handle` was the connection in use.
+    pub fn connection_lost(&mut self, handle: u32) -> bool {
+        if self.handle != Some(handle) {
+            return false;
+        }
+        self.handle = None;
+        if self.closed {
+            return false;
+        }
+        self.retry = Some(Timer::from_seconds(RETRY_INTERVAL, true));
+        self.attempts = 0;
+
+        true
+    }
+
+    /// Whether the next connection attempt is due.
+    pub fn should_retry(&mut self, delta: Duration) -> bool {
+        let timer = match &mut self.retry {
+            Some(timer) => timer,
+            None => return false,
+        };
+        if !timer.tick(delta).just_finished() {
+            return false;
+        }
+
+        self.attempts += 1;
+        if self.attempts > MAX_ATTEMPTS {
+            error!("Giving up reconnecting after {} attempts", MAX_ATTEMPTS);
+            self.retry = None;
+            return false;
+        }
+
+        true
+    }
+}
+
+/// Leaves the current server, if any, and joins the one at the given address.
+pub struct SwitchServer(pub SocketAddr);
+
+pub struct ReconnectPlugin;
+
+impl Plugin for ReconnectPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(Session::default())
+            .add_event::<SwitchServer>()
+            .add_system(handle_connection_lost_system.system())
+            .add_system(retry_connection_system.system())
+            .add_system(switch_server_system.system());
+    }
+}
+
+/// Forgets the world of the lost connection, the server sends it again after reconnecting.
+fn handle_connection_lost_system(
+    mut cmd: Commands,
+    mut session: ResMut<Session>,
+    mut history: ResMut<SnapshotHistory>,
+    mut network_events: EventReader<NetworkEvent>,
+    entities: Query<Entity, With<Uuid>>,
+) {
+    for event in network_events.iter() {
+        if let NetworkEvent::Disconnected(handle) = event {
+            if !session.connection_lost(*handle) {
+                continue;
+            }
+            warn!("Connection lost, trying to reconnect");
+            for entity in entities.iter() {
+                cmd.entity(entity).despawn_recursive();
+            }
+            history.clear();
+        }
+    }
+}
+
+fn retry_connection_system(
+    mut net: ResMut<NetworkResource>,
+    mut session: ResMut<Session>,
+    settings: Res<ClientSettings>,
+    time: Res<Time>,
+) {
+    if session.should_retry(time.delta()) {
+        let address = session.address(settings.server);
+        info!("Reconnecting to {}...", address);
+        net.connect(address);
+    }
+}
+
+fn switch_server_system(
+    mut cmd: Commands,
+    mut events: EventReader<SwitchServer>,
+    mut net: ResMut<NetworkResource>,
+    mut session: ResMut<Session>,
+    mut history: ResMut<SnapshotHistory>,
+    settings: Res<ClientSettings>,
+    entities: Query<Entity, With<Uuid>>,
+) {
+    for SwitchServer(address) in events.iter() {
+        if session.is_connected() && session.address(settings.server) == *address {
+            continue;
+        }
+
+        info!("Connecting to {}...", address);
+        if let Some(handle) = session.switch_to(*address) {
+            net.disconnect(handle);
+        }
+        for entity in entities.iter() {
+            cmd.entity(entity).despawn_recursive();
+        }
+        history.clear();
+        net.connect(*address);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version() -> GameVersion {
+        GameVersion {
+            protocol: 1,
+            content_hash: 0,
+        }
+    }
+
+    #[test]
+    fn reconnect_with_token() {
+        let mut session = Session::default();
+        assert!(matches!(
+            session.hello(version(), "Merlin"),
+            LobbyClientMessage::Join { .. }
+        ));
+
+        session.set_token(ReconnectToken(42));
+        assert!(matches!(
+            session.hello(version(), "Merlin"),
+            LobbyClientMessage::Reconnect {
+                token: ReconnectToken(42),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn spectators_do_not_join() {
+        let mut session = Session::default();
+        session.set_spectator(true);
+
+        assert!(matches!(
+            session.hello(version(), "Merlin"),
+            LobbyClientMessage::Spectate { .. }
+        ));
+    }
+
+    #[test]
+    fn only_the_current_connection_counts() {
+        let mut session = Session::default();
+        assert!(session.connected(1));
+        assert!(!session.connected(2));
+
+        assert!(!session.connection_lost(2));
+        assert!(session.connection_lost(1));
+        assert!(session.connected(3));
+    }
+
+    #[test]
+    fn retries_are_limited() {
+        let mut session = Session::default();
+        session.connected(1);
+        session.connection_lost(1);
+
+        let interval = Duration::from_secs_f32(RETRY_INTERVAL);
+        assert!(!session.should_retry(Duration::from_millis(10)));
+        let retries = (0..MAX_ATTEMPTS * 2)
+            .filter(|_| session.should_retry(interval))
+            .count();
+        assert_eq!(retries, MAX_ATTEMPTS as usize);
+    }
+
+    #[test]
+    fn switching_rooms_starts_a_new_session() {
+        let mut session = Session::default();
+        let default = "127.0.0.1:9001".parse().unwrap();
+        let room = "127.0.0.1:9002".parse().unwrap();
+        session.connected(1);
+        session.set_token(ReconnectToken(42));
+
+        assert_eq!(session.switch_to(room), Some(1));
+        assert_eq!(session.address(default), room);
+        assert!(!session.connection_lost(1));
+        assert!(matches!(
+            session.hello(version(), "Merlin"),
+            LobbyClientMessage::Join { .. }
+        ));
+    }
+
+    #[test]
+    fn closed_session_does_not_reconnect() {
+        let mut session = Session::default();
+        session.connected(1);
+        session.close();
+
+        assert!(!session.connection_lost(1));
+        assert!(!session.should_retry(Duration::from_secs(10)));
+    }
+}
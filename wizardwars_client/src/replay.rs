@@ -0,0 +1,356 @@
+use crate::interpolation::ServerClock;
+use bevy::prelude::*;
+use std::collections::HashSet;
+use wizardwars_shared::{
+    components::Uuid,
+    events::SpawnEvent,
+    messages::server_messages::{LobbyServerMessage, ServerMessage},
+    network::{Dest, ReplicationLog, SnapshotDelta, SnapshotHistory},
+    replay::{Replay, ReplayRecord},
+    version::GameVersion,
+};
+
+/// Seconds skipped forward or back by one key press.
+const SEEK_SECONDS: f64 = 5.0;
+const SPEEDS: [f32; 5] = [0.25, 0.5, 1.0, 2.0, 4.0];
+const NORMAL_SPEED: usize = 2;
+
+/// Recorded messages that are due, the network systems handle them like received ones.
+#[derive(Default)]
+pub struct ReplayInbox {
+    pub messages: Vec<ServerMessage>,
+    pub snapshots: Vec<SnapshotDelta>,
+}
+
+/// Plays a recorded match instead of connecting to a server. The replay shows what a
+/// spectator would have seen.
+pub struct ReplayPlugin {
+    pub replay: Replay,
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(Playback::new(self.replay.clone()))
+            .insert_resource(ReplayInbox::default())
+            .insert_resource(ReplicationLog::default())
+            .add_startup_system(setup_playback_system.system())
+            .add_system(playback_input_system.system())
+            .add_system_to_stage(CoreStage::First, playback_system.system());
+    }
+}
+
+struct Playback {
+    replay: Replay,
+    /// Index of the next frame to play.
+    next_frame: usize,
+    /// Ticks since the first frame.
+    position: f64,
+    speed: usize,
+    paused: bool,
+    seek_to: Option<f64>,
+}
+
+impl Playback {
+    fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            next_frame: 0,
+            position: 0.0,
+            speed: NORMAL_SPEED,
+            paused: false,
+            seek_to: None,
+        }
+    }
+
+    fn tick_rate(&self) -> u32 {
+        self.replay.header.tick_rate
+    }
+
+    /// Tick rate of the playback, the client clock runs at it.
+    fn playback_rate(&self) -> u32 {
+        ((self.tick_rate() as f32 * SPEEDS[self.speed]).round() as u32).max(1)
+    }
+
+    fn seek_by(&mut self, seconds: f64) {
+        let target = self.seek_to.unwrap_or(self.position) + seconds * self.tick_rate() as f64;
+        self.seek_to = Some(target.max(0.0).min(self.replay.duration() as f64));
+    }
+
+    fn rewind(&mut self) {
+        self.next_frame = 0;
+        self.position = 0.0;
+    }
+
+    /// Records of all frames up to the current position that were not played yet.
+    fn due(&mut self) -> Vec<ReplayRecord> {
+        let first_tick = self.replay.first_tick();
+        let mut records = Vec::new();
+        while let Some(frame) = self.replay.frames.get(self.next_frame) {
+            if frame.tick.0.wrapping_sub(first_tick.0) as f64 > self.position {
+                break;
+            }
+            records.extend(frame.records.iter().cloned());
+            self.next_frame += 1;
+        }
+
+        records
+    }
+}
+
+/// Packets sent to a single client are left out, the replay is watched as a spectator.
+fn is_public(record: &ReplayRecord) -> bool {
+    match record {
+        ReplayRecord::Packet(pack) => !matches!(pack.dest, Dest::Single(_)),
+        ReplayRecord::Snapshot(_) => true,
+        ReplayRecord::Component(update) => !matches!(update.dest, Dest::Single(_)),
+    }
+}
+
+/// The entity a message is about, `true` if the message creates it.
+fn subject(message: &ServerMessage) -> Option<(Uuid, bool)> {
+    match message {
+        ServerMessage::Lobby(LobbyServerMessage::PlayerJoined(id, _)) => Some((*id, true)),
+        ServerMessage::Spawn(SpawnEvent::Projectile { id, .. }) => Some((*id, true)),
+        ServerMessage::InsertPlayer(event) => Some((event.id, false)),
+        ServerMessage::Despawn(id) => Some((*id, false)),
+        _ => None,
+    }
+}
+
+/// Leaves out entities that are created and despawned again within `records`. When skipping
+/// through the replay they would be spawned and despawned in the same frame, which the
+/// despawn does not see yet.
+fn skip_short_lived(records: Vec<ReplayRecord>) -> Vec<ReplayRecord> {
+    let messages = records.iter().filter_map(|record| match record {
+        ReplayRecord::Packet(pack) => Some(&pack.msg),
+        _ => None,
+    });
+    let mut created = HashSet::new();
+    let mut despawned = HashSet::new();
+    for message in messages {
+        match (message, subject(message)) {
+            (_, Some((id, true))) => {
+                created.insert(id);
+            }
+            (ServerMessage::Despawn(id), _) => {
+                despawned.insert(*id);
+            }
+            _ => {}
+        }
+    }
+
+    records
+        .into_iter()
+        .filter(|record| match record {
+            ReplayRecord::Packet(pack) => subject(&pack.msg).map_or(true, |(id, _)| {
+                !created.contains(&id) || !despawned.contains(&id)
+            }),
+            _ => true,
+        })
+        .collect()
+}
+
+fn setup_playback_system(
+    mut clock: ResMut<ServerClock>,
+    playback: Res<Playback>,
+    version: Res<GameVersion>,
+) {
+    let header = &playback.replay.header;
+    if header.version != *version {
+        warn!(
+            "The replay was recorded with {} but this client runs {}, it may not play correctly",
+            header.version, *version
+        );
+    }
+    info!(
+        "Playing a replay of {:.0} seconds. P pauses, [ and ] change the speed, \
+         , and . skip 5 seconds, Home restarts",
+        playback.replay.duration() as f32 / header.tick_rate as f32
+    );
+    clock.set_tick_rate(playback.playback_rate());
+}
+
+fn playback_input_system(
+    input: Res<Input<KeyCode>>,
+    mut playback: ResMut<Playback>,
+    mut clock: ResMut<ServerClock>,
+) {
+    if input.just_pressed(KeyCode::P) {
+        playback.paused = !playback.paused;
+        info!("{}", if playback.paused { "Paused" } else { "Playing" });
+    }
+
+    let speed = if input.just_pressed(KeyCode::LBracket) {
+        playback.speed.saturating_sub(1)
+    } else if input.just_pressed(KeyCode::RBracket) {
+        (playback.speed + 1).min(SPEEDS.len() - 1)
+    } else {
+        playback.speed
+    };
+    if speed != playback.speed {
+        playback.speed = speed;
+        clock.set_tick_rate(playback.playback_rate());
+        info!("Playback speed {}x", SPEEDS[speed]);
+    }
+
+    if input.just_pressed(KeyCode::Comma) {
+        playback.seek_by(-SEEK_SECONDS);
+    }
+    if input.just_pressed(KeyCode::Period) {
+        playback.seek_by(SEEK_SECONDS);
+    }
+    if input.just_pressed(KeyCode::Home) {
+        playback.seek_to = Some(0.0);
+    }
+}
+
+/// Hands the records that are due to the network systems. Seeking back starts over from
+/// the beginning, everything the replay spawned so far is removed first.
+#[allow(clippy::too_many_arguments)]
+fn playback_system(
+    mut cmd: Commands,
+    mut playback: ResMut<Playback>,
+    mut inbox: ResMut<ReplayInbox>,
+    mut log: ResMut<ReplicationLog>,
+    mut history: ResMut<SnapshotHistory>,
+    mut clock: ResMut<ServerClock>,
+    time: Res<Time>,
+    entities: Query<Entity, With<Uuid>>,
+) {
+    let records = match playback.seek_to.take() {
+        Some(target) => {
+            if target < playback.position {
+                for entity in entities.iter() {
+                    cmd.entity(entity).despawn_recursive();
+                }
+                history.clear();
+                playback.rewind();
+            }
+            playback.position = target;
+            clock.set_tick_rate(playback.playback_rate());
+            info!("Skipped to {:.0}s", target / playback.tick_rate() as f64);
+
+            let records = playback.due().into_iter().filter(is_public).collect();
+            skip_short_lived(records)
+        }
+        None if playback.paused => return,
+        None => {
+            let duration = playback.replay.duration() as f64;
+            let step = time.delta_seconds_f64() * playback.playback_rate() as f64;
+            playback.position = (playback.position + step).min(duration);
+
+            playback.due().into_iter().filter(is_public).collect()
+        }
+    };
+
+    for record in records {
+        match record {
+            ReplayRecord::Packet(pack) => inbox.messages.push(pack.msg),
+            ReplayRecord::Snapshot(delta) => inbox.snapshots.push(delta),
+            ReplayRecord::Component(update) => log.push(update),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wizardwars_shared::{
+        components::Client,
+        network::{Pack, Tick},
+        replay::{ReplayFrame, ReplayHeader},
+        spells::SpellId,
+    };
+
+    fn despawn(id: u32) -> ReplayRecord {
+        ReplayRecord::Packet(Pack::all(ServerMessage::Despawn(Uuid(id))))
+    }
+
+    fn spawn(id: u32) -> ReplayRecord {
+        ReplayRecord::Packet(Pack::all(ServerMessage::Spawn(SpawnEvent::Projectile {
+            id: Uuid(id),
+            spell: SpellId::new("fireball"),
+        })))
+    }
+
+    fn despawned_ids(records: &[ReplayRecord]) -> Vec<Uuid> {
+        records
+            .iter()
+            .filter_map(|record| match record {
+                ReplayRecord::Packet(Pack {
+                    msg: ServerMessage::Despawn(id),
+                    ..
+                }) => Some(*id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn playback() -> Playback {
+        let frame = |tick, records| ReplayFrame {
+            tick: Tick(tick),
+            records,
+        };
+        Playback::new(Replay {
+            header: ReplayHeader {
+                version: GameVersion {
+                    protocol: 1,
+                    content_hash: 0,
+                },
+                tick_rate: 10,
+            },
+            frames: vec![
+                frame(100, vec![despawn(1)]),
+                frame(105, vec![despawn(2)]),
+                frame(120, vec![despawn(3)]),
+            ],
+        })
+    }
+
+    #[test]
+    fn frames_are_played_once() {
+        let mut playback = playback();
+
+        assert_eq!(despawned_ids(&playback.due()), vec![Uuid(1)]);
+        playback.position = 10.0;
+        assert_eq!(despawned_ids(&playback.due()), vec![Uuid(2)]);
+        assert!(playback.due().is_empty());
+
+        playback.rewind();
+        playback.position = 20.0;
+        assert_eq!(despawned_ids(&playback.due()).len(), 3);
+    }
+
+    #[test]
+    fn seeking_stays_inside_the_replay() {
+        let mut playback = playback();
+
+        playback.seek_by(-5.0);
+        assert!(playback
+            .seek_to
+            .map_or(false, |target| target.abs() < f64::EPSILON));
+        playback.seek_by(5.0);
+        playback.seek_by(5.0);
+        assert!(playback
+            .seek_to
+            .map_or(false, |target| (target - 20.0).abs() < f64::EPSILON));
+    }
+
+    #[test]
+    fn short_lived_entities_are_skipped() {
+        let records = vec![spawn(1), despawn(0), spawn(2), despawn(1)];
+
+        let kept = skip_short_lived(records);
+        assert_eq!(kept.len(), 2);
+        assert_eq!(despawned_ids(&kept), vec![Uuid(0)]);
+    }
+
+    #[test]
+    fn private_packets_are_not_played() {
+        let private =
+            ReplayRecord::Packet(Pack::single(ServerMessage::Despawn(Uuid(0)), Client(1)));
+
+        assert!(!is_public(&private));
+        assert!(is_public(&despawn(0)));
+    }
+}
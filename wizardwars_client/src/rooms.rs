@@ -0,0 +1,109 @@
+use crate::{
+    reconnect::{Session, SwitchServer},
+    settings::ClientSettings,
+};
+use bevy::prelude::*;
+use std::net::SocketAddr;
+use wizardwars_shared::messages::{
+    client_messages::{ClientMessage, RoomClientMessage},
+    server_messages::{RoomInfo, RoomServerMessage},
+};
+
+/// Pressed together with the left alt key to join the room with that number.
+const ROOM_KEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+/// Rooms of the server as of the last time they were listed.
+#[derive(Default)]
+pub struct RoomList(pub Vec<RoomInfo>);
+
+/// Leaves the current room for the one on the given port of the same server.
+pub struct JoinRoom(pub u16);
+
+pub struct RoomsPlugin;
+
+impl Plugin for RoomsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<RoomServerMessage>()
+            .add_event::<JoinRoom>()
+            .insert_resource(RoomList::default())
+            .add_system(room_input_system.system())
+            .add_system(handle_room_messages_system.system())
+            .add_system(join_room_system.system());
+    }
+}
+
+fn join_room_system(
+    mut events: EventReader<JoinRoom>,
+    mut switches: EventWriter<SwitchServer>,
+    session: Res<Session>,
+    settings: Res<ClientSettings>,
+) {
+    for JoinRoom(port) in events.iter() {
+        let current = session.address(settings.server);
+        switches.send(SwitchServer(SocketAddr::new(current.ip(), *port)));
+    }
+}
+
+fn room_input_system(
+    input: Res<Input<KeyCode>>,
+    settings: Res<ClientSettings>,
+    rooms: Res<RoomList>,
+    mut packets: EventWriter<ClientMessage>,
+    mut joins: EventWriter<JoinRoom>,
+) {
+    if input.just_pressed(KeyCode::R) {
+        packets.send(ClientMessage::Room(RoomClientMessage::List));
+    }
+    if input.just_pressed(KeyCode::N) {
+        packets.send(ClientMessage::Room(RoomClientMessage::Create {
+            name: format!("{}'s room", settings.name),
+        }));
+    }
+
+    if !input.pressed(KeyCode::LAlt) {
+        return;
+    }
+    for (key, room) in ROOM_KEYS.iter().zip(rooms.0.iter()) {
+        if input.just_pressed(*key) {
+            joins.send(JoinRoom(room.port));
+        }
+    }
+}
+
+fn handle_room_messages_system(
+    mut events: EventReader<RoomServerMessage>,
+    mut rooms: ResMut<RoomList>,
+    mut joins: EventWriter<JoinRoom>,
+) {
+    for event in events.iter() {
+        match event {
+            RoomServerMessage::List(list) => {
+                for (number, room) in list.iter().enumerate() {
+                    info!(
+                        "{}. {} ({}/{} players, {})",
+                        number + 1,
+                        room.name,
+                        room.players,
+                        room.max_players,
+                        room.state
+                    );
+                }
+                rooms.0 = list.clone();
+            }
+            RoomServerMessage::Created(room) => {
+                info!("Created room {}", room.name);
+                joins.send(JoinRoom(room.port));
+            }
+        }
+    }
+}
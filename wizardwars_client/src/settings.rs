@@ -0,0 +1,133 @@
+use std::{
+    net::{SocketAddr, ToSocketAddrs},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+use wizardwars_shared::{
+    items::DEFAULT_ITEMS_PATH, network::DISCOVERY_PORT, spells::DEFAULT_SPELLS_PATH,
+};
+
+#[derive(StructOpt, Debug, Clone)]
+#[structopt(name = "wizardwars_client")]
+pub struct ClientSettings {
+    /// Server address as `host:port`
+    #[structopt(short, long, default_value = "127.0.0.1:9001", parse(try_from_str = parse_server_address))]
+    pub server: SocketAddr,
+    /// Look for servers on the LAN instead of connecting to `--server` right away
+    #[structopt(long)]
+    pub discover: bool,
+    /// UDP port servers answer LAN discovery queries on, the standard discovery port if not set
+    #[structopt(long)]
+    pub discovery_port: Option<u16>,
+    /// Player name shown to other players
+    #[structopt(short, long, default_value = "John Doe")]
+    pub name: String,
+    /// Window width in pixels
+    #[structopt(long, default_value = "800")]
+    pub width: f32,
+    /// Window height in pixels
+    #[structopt(long, default_value = "600")]
+    pub height: f32,
+    /// Mark the player as ready right after joining the lobby
+    #[structopt(long)]
+    pub auto_ready: bool,
+    /// Watch the matches without playing
+    #[structopt(long)]
+    pub spectate: bool,
+    /// Play a recorded match instead of connecting to a server
+    #[structopt(long, parse(from_os_str))]
+    pub replay: Option<PathBuf>,
+    /// Path to the spell definitions file
+    #[structopt(long, default_value = DEFAULT_SPELLS_PATH, parse(from_os_str))]
+    pub spells: PathBuf,
+    /// Path to the shop items file
+    #[structopt(long, default_value = DEFAULT_ITEMS_PATH, parse(from_os_str))]
+    pub items: PathBuf,
+    /// How far in the past other entities are rendered, in milliseconds
+    #[structopt(long, default_value = "100")]
+    pub interpolation_delay: u32,
+    /// How long entities keep moving without updates from the server, in milliseconds
+    #[structopt(long, default_value = "250")]
+    pub max_extrapolation: u32,
+}
+
+impl Default for ClientSettings {
+    fn default() -> Self {
+        Self {
+            server: SocketAddr::from(([127, 0, 0, 1], 9001)),
+            discover: false,
+            discovery_port: None,
+            name: "John Doe".to_owned(),
+            width: 800.0,
+            height: 600.0,
+            auto_ready: false,
+            spectate: false,
+            replay: None,
+            spells: PathBuf::from(DEFAULT_SPELLS_PATH),
+            items: PathBuf::from(DEFAULT_ITEMS_PATH),
+            interpolation_delay: 100,
+            max_extrapolation: 250,
+        }
+    }
+}
+
+impl ClientSettings {
+    pub fn discovery_port(&self) -> u16 {
+        self.discovery_port.unwrap_or(DISCOVERY_PORT)
+    }
+}
+
+fn parse_server_address(address: &str) -> Result<SocketAddr, String> {
+    address
+        .to_socket_addrs()
+        .map_err(|err| format!("Cannot resolve {}: {}", address, err))?
+        .next()
+        .ok_or_else(|| format!("No address found for {}", address))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_settings() {
+        let settings = ClientSettings::from_iter(&["wizardwars_client"]);
+        let default = ClientSettings::default();
+
+        assert_eq!(settings.server, default.server);
+        assert_eq!(settings.name, default.name);
+        assert!(!settings.auto_ready);
+        assert!(!settings.discover);
+        assert!(!settings.spectate);
+        assert!(settings.replay.is_none());
+        assert_eq!(settings.discovery_port(), DISCOVERY_PORT);
+        assert_eq!(settings.interpolation_delay, default.interpolation_delay);
+        assert_eq!(settings.max_extrapolation, default.max_extrapolation);
+    }
+
+    #[test]
+    fn parse_settings() {
+        let settings = ClientSettings::from_iter(&[
+            "wizardwars_client",
+            "--server",
+            "10.0.0.2:9100",
+            "--name",
+            "Merlin",
+            "--width",
+            "1280",
+            "--auto-ready",
+            "--discover",
+        ]);
+
+        assert_eq!(settings.server, "10.0.0.2:9100".parse().unwrap());
+        assert_eq!(settings.name, "Merlin");
+        assert!((settings.width - 1280.0).abs() < f32::EPSILON);
+        assert!(settings.auto_ready);
+        assert!(settings.discover);
+    }
+
+    #[test]
+    fn reject_invalid_address() {
+        assert!(parse_server_address("not an address").is_err());
+    }
+}
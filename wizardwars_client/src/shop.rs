@@ -0,0 +1,90 @@
+use bevy::prelude::*;
+use bevy_networking_turbulence::NetworkResource;
+use wizardwars_shared::{
+    components::{Gold, Inventory},
+    items::{Items, ShopItem},
+    messages::{
+        client_messages::{ClientMessage, ShopClientMessage},
+        server_messages::ShoppingServerMessage,
+    },
+    spells::Spells,
+};
+
+const SHOP_KEYS: [KeyCode; 9] = [
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+];
+
+/// Gold and inventory of the local player as last reported by the server.
+#[derive(Debug, Default)]
+pub struct Wallet {
+    pub gold: Gold,
+    pub inventory: Inventory,
+}
+
+pub struct ShopPlugin;
+
+impl Plugin for ShopPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<ShoppingServerMessage>()
+            .insert_resource(Wallet::default())
+            .add_system(update_wallet_system.system())
+            .add_system_to_stage(CoreStage::PreUpdate, shop_input_system.system());
+    }
+}
+
+/// Everything that can be bought, free spells excluded, in the order of the
+/// `F1`..`F9` keys.
+fn catalog(spells: &Spells, items: &Items) -> Vec<ShopItem> {
+    spells
+        .iter()
+        .filter(|spell| spell.price > 0)
+        .map(|spell| ShopItem::Spell(spell.id.clone()))
+        .chain(items.iter().map(|item| ShopItem::Item(item.id.clone())))
+        .collect()
+}
+
+fn update_wallet_system(
+    mut events: EventReader<ShoppingServerMessage>,
+    mut wallet: ResMut<Wallet>,
+) {
+    for event in events.iter() {
+        match event {
+            ShoppingServerMessage::Timer(_) => {}
+            ShoppingServerMessage::Gold(gold) => {
+                info!("Gold: {}", gold);
+                wallet.gold = Gold(*gold);
+            }
+            ShoppingServerMessage::Inventory(inventory) => {
+                wallet.inventory = inventory.clone();
+            }
+        }
+    }
+}
+
+/// `F1`..`F9` buys the matching catalog entry, holding `Shift` sells it.
+fn shop_input_system(
+    input: Res<Input<KeyCode>>,
+    spells: Res<Spells>,
+    items: Res<Items>,
+    mut net: ResMut<NetworkResource>,
+) {
+    let selling = input.pressed(KeyCode::LShift) || input.pressed(KeyCode::RShift);
+    for (key, shop_item) in SHOP_KEYS.iter().zip(catalog(&spells, &items)) {
+        if input.just_pressed(*key) {
+            let message = if selling {
+                ShopClientMessage::Sell(shop_item)
+            } else {
+                ShopClientMessage::Buy(shop_item)
+            };
+            net.broadcast_message(ClientMessage::Shop(message));
+        }
+    }
+}
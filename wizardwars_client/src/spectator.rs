@@ -0,0 +1,87 @@
+use crate::{arena::LocalPlayer, camera::CameraTarget};
+use bevy::prelude::*;
+use wizardwars_shared::components::{Dead, Health, Uuid};
+
+pub struct SpectatorPlugin;
+
+impl Plugin for SpectatorPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(spectate_system.system());
+    }
+}
+
+/// The player after `current` in the list, wrapping around.
+fn next_target(current: Option<Uuid>, alive: &[Uuid]) -> Option<Uuid> {
+    let next = current
+        .and_then(|current| alive.iter().position(|&id| id == current))
+        .map_or(0, |index| index + 1);
+
+    alive.get(next).or_else(|| alive.first()).copied()
+}
+
+/// The camera follows the local player while it is alive. Otherwise tab cycles through the
+/// remaining players, without a target the camera moves freely.
+#[allow(clippy::type_complexity)]
+fn spectate_system(
+    mut cmd: Commands,
+    input: Res<Input<KeyCode>>,
+    local: Query<(Entity, Option<&Dead>), With<LocalPlayer>>,
+    players: Query<(Entity, &Uuid, Option<&Dead>, Option<&CameraTarget>), With<Health>>,
+) {
+    let local = local.iter().next();
+    let watched = players.iter().find(|(.., target)| target.is_some());
+
+    let target = match local {
+        Some((entity, None)) => Some(entity),
+        _ => {
+            // Knocked out players keep looking at themselves until they press tab.
+            let local = local.map(|(entity, _)| entity);
+            let watched_died = watched.map_or(false, |(entity, _, dead, _)| {
+                dead.is_some() && Some(entity) != local
+            });
+            if !input.just_pressed(KeyCode::Tab) && !watched_died {
+                return;
+            }
+
+            let mut alive = players
+                .iter()
+                .filter(|(_, _, dead, _)| dead.is_none())
+                .map(|(_, &id, ..)| id)
+                .collect::<Vec<_>>();
+            alive.sort();
+            next_target(watched.map(|(_, &id, ..)| id), &alive).and_then(|next| {
+                players
+                    .iter()
+                    .find(|(_, &id, ..)| id == next)
+                    .map(|(entity, ..)| entity)
+            })
+        }
+    };
+    if target == watched.map(|(entity, ..)| entity) {
+        return;
+    }
+
+    for (entity, ..) in players.iter() {
+        if Some(entity) == target {
+            cmd.entity(entity).insert(CameraTarget);
+        } else {
+            cmd.entity(entity).remove::<CameraTarget>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tab_cycles_through_living_players() {
+        let alive = [Uuid(1), Uuid(4), Uuid(7)];
+
+        assert_eq!(next_target(None, &alive), Some(Uuid(1)));
+        assert_eq!(next_target(Some(Uuid(4)), &alive), Some(Uuid(7)));
+        assert_eq!(next_target(Some(Uuid(7)), &alive), Some(Uuid(1)));
+        assert_eq!(next_target(Some(Uuid(5)), &alive), Some(Uuid(1)));
+        assert_eq!(next_target(Some(Uuid(1)), &[]), None);
+    }
+}
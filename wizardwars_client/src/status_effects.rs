@@ -0,0 +1,90 @@
+use bevy::prelude::*;
+use wizardwars_shared::{
+    components::{StatusEffectKind, Uuid},
+    messages::server_messages::StatusEffectMessage,
+    resources::CharacterDimensions,
+};
+
+const ICON_RADIUS: f32 = 0.06;
+const ICON_SPACING: f32 = 0.15;
+
+/// Floats above a player for as long as the effect lasts.
+struct EffectIcon(StatusEffectKind);
+
+pub struct StatusEffectsPlugin;
+
+impl Plugin for StatusEffectsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<StatusEffectMessage>()
+            .add_system(show_status_effects_system.system());
+    }
+}
+
+fn icon_color(kind: StatusEffectKind) -> Color {
+    match kind {
+        StatusEffectKind::Burn => Color::rgb(1.0, 0.3, 0.0),
+        StatusEffectKind::Slow => Color::rgb(0.3, 0.6, 1.0),
+        StatusEffectKind::Stun => Color::rgb(1.0, 0.9, 0.1),
+        StatusEffectKind::Silence => Color::rgb(0.6, 0.2, 0.8),
+        StatusEffectKind::Shield => Color::rgb(0.9, 0.9, 0.9),
+    }
+}
+
+/// Every kind has its own place in the row above the head, so icons do not move around as
+/// other effects come and go.
+fn icon_offset(kind: StatusEffectKind, height: f32) -> Vec3 {
+    let slot = kind as usize as f32 - 2.0;
+    Vec3::new(slot * ICON_SPACING, height + 0.2, 0.0)
+}
+
+fn show_status_effects_system(
+    mut cmd: Commands,
+    mut events: EventReader<StatusEffectMessage>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    dimensions: Res<CharacterDimensions>,
+    players: Query<(Entity, &Uuid, Option<&Children>)>,
+    icons: Query<&EffectIcon>,
+) {
+    for event in events.iter() {
+        let (id, kind) = match *event {
+            StatusEffectMessage::Started { id, kind, .. } => (id, kind),
+            StatusEffectMessage::Ended { id, kind } => (id, kind),
+        };
+        let (player, children) = match players.iter().find(|(_, &player, _)| player == id) {
+            Some((player, _, children)) => (player, children),
+            None => continue,
+        };
+        let icon = children.and_then(|children| {
+            children
+                .iter()
+                .copied()
+                .find(|&child| icons.get(child).map_or(false, |icon| icon.0 == kind))
+        });
+
+        match (event, icon) {
+            (StatusEffectMessage::Started { .. }, None) => {
+                cmd.entity(player).with_children(|parent| {
+                    parent
+                        .spawn_bundle(PbrBundle {
+                            mesh: meshes.add(Mesh::from(shape::Icosphere {
+                                radius: ICON_RADIUS,
+                                subdivisions: 2,
+                            })),
+                            material: materials.add(icon_color(kind).into()),
+                            transform: Transform::from_translation(icon_offset(
+                                kind,
+                                dimensions.height(),
+                            )),
+                            ..Default::default()
+                        })
+                        .insert(EffectIcon(kind));
+                });
+            }
+            (StatusEffectMessage::Ended { .. }, Some(icon)) => {
+                cmd.entity(icon).despawn_recursive();
+            }
+            _ => {}
+        }
+    }
+}
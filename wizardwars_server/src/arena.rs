@@ -2,6 +2,10 @@
 use bevy::prelude::*;
 use std::f32::consts::PI;
 
+pub struct ArenaConfig {
+    pub total_rounds: u32,
+}
+
 pub struct Arena {
     spawn_points: Vec<Vec3>,
     current_round: u32,
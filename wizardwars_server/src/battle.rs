@@ -1,34 +1,59 @@
 use crate::{
     arena::Arena,
-    network::{IdFactory, ServerPacket},
+    melee::{AttackCooldown, Attacking, MeleeConfig},
+    network::{LastInput, ServerPacket},
+    spells::Casting,
     states::ServerState,
 };
 use bevy::prelude::*;
 use bevy_rapier3d::{
-    physics::{ColliderBundle, IntoEntity, IntoHandle, RigidBodyBundle, RigidBodyPositionSync},
+    physics::{ColliderBundle, RigidBodyBundle, RigidBodyPositionSync},
     prelude::{
-        ActiveEvents, ColliderShape, ColliderType, NarrowPhase, RigidBodyForces,
-        RigidBodyMassProps, RigidBodyMassPropsFlags, RigidBodyPosition, RigidBodyVelocity,
+        ActiveEvents, ColliderShape, ColliderType, RigidBodyMassProps, RigidBodyMassPropsFlags,
+        RigidBodyPosition,
     },
 };
-use rand::Rng;
 use std::collections::HashMap;
 use wizardwars_shared::{
     components::{
-        damage::{Attack, FireBall},
-        Bot, Client, Dead, Health, LifeTime, Owner, Player, Position, Uuid, Waypoint, Winner,
+        damage::{Attack, DamageKind, Resistances},
+        Client, Cooldowns, Dead, Health, Inventory, LifeTime, Mana, Player, Position, Stats,
+        StatusEffects, Uuid, Waypoint, Winner, BASE_MOVE_SPEED,
     },
-    events::{ClientEvent, InsertPlayerEvent, SpawnEvent},
-    messages::{client_messages::ActionMessage, server_messages::ServerMessage},
-    network::Pack,
+    events::{ClientEvent, DamageApplied, DamageEvent, InsertPlayerEvent, Killed},
+    items::Items,
+    messages::{
+        client_messages::ActionMessage,
+        server_messages::{KillFeedEntry, ServerMessage},
+    },
+    movement::{has_arrived, step_towards},
+    network::{Pack, Tick},
     resources::{CharacterDimensions, PlayerColors},
     systems::apply_damage_system,
 };
 
 pub struct BattlePlugin;
 
+pub struct BattleConfig {
+    pub preparation_time: f32,
+    pub starting_health: u32,
+    pub mana: f32,
+    pub mana_regeneration: f32,
+}
+
 pub struct PreparationTimer(Timer);
 
+/// Color the player was given for the current match, resent to reconnecting clients.
+pub struct PlayerColor(pub Color);
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BattleSystem {
+    /// Turns the damage events of the frame into `DamageApplied` and `Killed` events.
+    ApplyDamage,
+    /// Despawns entities whose `LifeTime` ran out.
+    TrackLifetime,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum BattleState {
     None,
@@ -39,23 +64,32 @@ pub enum BattleState {
 impl Plugin for BattlePlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.add_state(BattleState::None)
+            .add_event::<DamageEvent>()
+            .add_event::<DamageApplied>()
+            .add_event::<Killed>()
             .add_system_set(
                 SystemSet::on_enter(ServerState::Battle).with_system(setup_players.system()),
             )
             .add_system_set(
                 SystemSet::on_update(BattleState::Battle)
-                    .with_system(handle_attack_events_system.system())
                     .with_system(handle_health_system.system())
                     .with_system(check_winer_system.system())
-                    .with_system(apply_damage_system.system())
+                    .with_system(
+                        apply_damage_system
+                            .system()
+                            .label(BattleSystem::ApplyDamage),
+                    )
+                    .with_system(kill_feed_system.system().after(BattleSystem::ApplyDamage))
                     .with_system(check_switch_state_system.system())
                     .with_system(debug_health_change_system.system())
                     .with_system(debug_winner_change_system.system())
                     .with_system(debug_dead_message_system.system())
-                    .with_system(bot_waypoint_system.system())
                     .with_system(move_to_waypoint_system.system())
-                    .with_system(track_lifetime_system.system())
-                    .with_system(fireball_collision_system.system())
+                    .with_system(
+                        track_lifetime_system
+                            .system()
+                            .label(BattleSystem::TrackLifetime),
+                    )
                     .with_system(position_sync_system.system()),
             )
             .add_system_set(
@@ -76,14 +110,18 @@ impl Plugin for BattlePlugin {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn setup_players(
     mut cmd: Commands,
     arena: Res<Arena>,
     player_colors: Res<PlayerColors>,
     character_dimensions: Res<CharacterDimensions>,
+    config: Res<BattleConfig>,
+    melee: Res<MeleeConfig>,
     mut battle_state: ResMut<State<BattleState>>,
     mut packets: EventWriter<ServerPacket>,
-    clients: Query<(Entity, &Uuid, Option<&Client>), With<Player>>,
+    items: Res<Items>,
+    clients: Query<(Entity, &Uuid, Option<&Client>, Option<&Inventory>), With<Player>>,
 ) {
     battle_state
         .overwrite_set(BattleState::Prepare)
@@ -99,7 +137,12 @@ fn setup_players(
         spawn_points.iter(),
         player_colors.colors.iter()
     )
-    .for_each(|((entity, id, client), point, color)| {
+    .for_each(|((entity, id, client, inventory), point, color)| {
+        let modifiers = inventory
+            .map(|inventory| items.modifiers(inventory))
+            .unwrap_or_default();
+        let damage_multiplier = 1.0 + modifiers.damage;
+        let melee_damage = (melee.damage as f32 * damage_multiplier).round() as u32;
         let collider = ColliderBundle {
             collider_type: ColliderType::Solid,
             shape: ColliderShape::capsule(
@@ -123,8 +166,29 @@ fn setup_players(
         };
 
         cmd.entity(entity)
-            .insert(Health::new(20))
+            .insert(Health::new(config.starting_health + modifiers.health))
+            .insert(Mana::new(
+                config.mana + modifiers.mana,
+                config.mana_regeneration + modifiers.mana_regeneration,
+            ))
+            .insert(Stats {
+                move_speed: BASE_MOVE_SPEED * (1.0 + modifiers.move_speed),
+                damage_multiplier,
+            })
+            .insert(Attack::new(
+                melee_damage,
+                melee.knockback,
+                DamageKind::Melee,
+            ))
+            .insert(Resistances {
+                armor: modifiers.armor,
+                magic: modifiers.magic_resistance,
+                knockback: modifiers.knockback_resistance,
+            })
+            .insert(Cooldowns::default())
+            .insert(StatusEffects::default())
             .insert(Position(*point))
+            .insert(PlayerColor(*color))
             .insert(Transform::default())
             .insert_bundle(collider)
             .insert_bundle(rigidbody)
@@ -157,66 +221,6 @@ fn setup_players(
     });
 }
 
-#[allow(clippy::type_complexity)]
-fn handle_attack_events_system(
-    mut cmd: Commands,
-    mut id_factory: ResMut<IdFactory>,
-    mut events: EventReader<ClientEvent<ActionMessage>>,
-    query: Query<(Entity, &Position, &Client), (With<Health>, Without<Dead>)>,
-    mut packets: EventWriter<ServerPacket>,
-) {
-    let map = query
-        .iter()
-        .map(|(e, p, c)| (c, (e, p)))
-        .collect::<HashMap<_, _>>();
-    for event in events.iter() {
-        if let ActionMessage::FireBall(target) = &event.event() {
-            let offset = 0.5;
-            let (attacker_entity, attacker_position) = map.get(event.client()).unwrap();
-            let origin = attacker_position.0 + Vec3::Y * offset;
-            let target = Vec3::new(target.x, offset, target.z);
-            let dir = (origin - target).normalize();
-
-            let id = id_factory.generate();
-            let collier = ColliderBundle {
-                collider_type: ColliderType::Sensor,
-                shape: ColliderShape::ball(0.1),
-                flags: (ActiveEvents::INTERSECTION_EVENTS).into(),
-                ..Default::default()
-            };
-            let rigidbody = RigidBodyBundle {
-                position: origin.into(),
-                velocity: RigidBodyVelocity {
-                    linvel: (-dir * 5.0).into(),
-                    ..Default::default()
-                },
-                forces: RigidBodyForces {
-                    gravity_scale: 0.0,
-                    ..Default::default()
-                },
-                ..Default::default()
-            };
-
-            cmd.spawn()
-                .insert(Position(origin))
-                .insert(FireBall {
-                    attack: Attack::new(10, 100.0),
-                })
-                .insert(Owner::new(*attacker_entity))
-                .insert(LifeTime::from_seconds(5.0))
-                .insert(Transform::default())
-                .insert_bundle(collier)
-                .insert_bundle(rigidbody)
-                .insert(RigidBodyPositionSync::Discrete)
-                .insert(id);
-
-            cmd.entity(*attacker_entity).remove::<Waypoint>();
-
-            packets.send(Pack::all(ServerMessage::Spawn(SpawnEvent::Projectile(id))));
-        }
-    }
-}
-
 // TODO: Should be called right after some rigidbody_positions_sync system
 fn position_sync_system(mut query: Query<(&Transform, &mut Position), Changed<Transform>>) {
     for (transform, mut position) in query.iter_mut() {
@@ -224,79 +228,11 @@ fn position_sync_system(mut query: Query<(&Transform, &mut Position), Changed<Tr
     }
 }
 
-fn fireball_collision_system(
-    mut cmd: Commands,
-    mut packets: EventWriter<ServerPacket>,
-    fireballs: Query<(Entity, &FireBall, &Owner, &Uuid)>,
-    healths: Query<&Health>,
-    mut rigidbodies: Query<&mut RigidBodyForces>,
-    rigidbody_props: Query<(&RigidBodyPosition, &RigidBodyVelocity)>,
-    narrow_phase: Res<NarrowPhase>,
-) {
-    for (fireball_entity, fireball, fireball_owner, fireball_id) in fireballs.iter() {
-        for (collider1, collider2, _) in narrow_phase.intersections_with(fireball_entity.handle()) {
-            let target_entity = {
-                let e1 = collider1.entity();
-                let e2 = collider2.entity();
-
-                if e1 == fireball_entity {
-                    e2
-                } else {
-                    e1
-                }
-            };
-
-            if fireball_owner.entity() == target_entity {
-                continue;
-            }
-
-            let rigidbody = rigidbodies.get_mut(target_entity).ok();
-
-            if let Some(mut rigidbody_force) = rigidbody {
-                let (fireball_position, fireball_velocity) =
-                    rigidbody_props.get(fireball_entity).unwrap();
-                let (target_position, target_velocity) =
-                    rigidbody_props.get(target_entity).unwrap();
-
-                let mut diff_position: Vec3 = (fireball_position.position.translation.vector
-                    - target_position.position.translation.vector)
-                    .into();
-                diff_position.y = 0.0;
-
-                let mut diff_velocity: Vec3 =
-                    (fireball_velocity.linvel - target_velocity.linvel).into();
-                diff_velocity.y = 0.0;
-
-                let dot = diff_position
-                    .normalize()
-                    .dot(diff_velocity.normalize_or_zero());
-
-                let normal = diff_position.normalize() * dot * fireball.attack.knockback_force();
-                let force = [normal.x, 0.0, normal.z].into();
-
-                rigidbody_force.apply_force_at_point(
-                    &Default::default(),
-                    force,
-                    [0.0, 0.0, 0.0].into(),
-                );
-            }
-
-            let health = healths.get(target_entity).ok();
-
-            if health.is_some() {
-                cmd.entity(target_entity).insert(fireball.attack.damage());
-            }
-
-            cmd.entity(fireball_entity).despawn();
-            packets.send(Pack::all(ServerMessage::Despawn(*fireball_id)));
-        }
-    }
-}
-
 fn handle_move_events_system(
     mut cmd: Commands,
     mut events: EventReader<ClientEvent<ActionMessage>>,
-    query: Query<(Entity, &Client)>,
+    tick: Res<Tick>,
+    query: Query<(Entity, &Client), With<Player>>,
 ) {
     let clients = query
         .iter()
@@ -304,14 +240,44 @@ fn handle_move_events_system(
         .collect::<HashMap<_, _>>();
 
     for event in events.iter() {
-        if let ActionMessage::Move { target } = &event.event() {
+        if let ActionMessage::Move { target, sequence } = *event.event() {
             if let Some(&entity) = clients.get(event.client()) {
-                cmd.entity(entity).insert(Waypoint(*target));
+                cmd.entity(entity)
+                    .remove::<Casting>()
+                    .remove::<Attacking>()
+                    .insert(Waypoint(target))
+                    .insert(LastInput {
+                        sequence,
+                        tick: *tick,
+                    });
             }
         }
     }
 }
 
+fn kill_feed_system(
+    mut killed: EventReader<Killed>,
+    mut packets: EventWriter<ServerPacket>,
+    ids: Query<&Uuid>,
+) {
+    for event in killed.iter() {
+        let victim = match ids.get(event.target) {
+            Ok(&victim) => victim,
+            Err(_) => continue,
+        };
+        let killer = event
+            .source
+            .and_then(|source| ids.get(source).ok())
+            .copied()
+            .filter(|&killer| killer != victim);
+        packets.send(ServerPacket::all(ServerMessage::KillFeed(KillFeedEntry {
+            victim,
+            killer,
+            kind: event.kind,
+        })));
+    }
+}
+
 fn handle_health_system(mut cmd: Commands, query: Query<(Entity, &Health), Changed<Health>>) {
     for (entity, health) in query.iter() {
         if health.should_die() {
@@ -377,14 +343,17 @@ fn cleanup_system(
         cmd.entity(entity)
             .remove::<Position>()
             .remove::<Dead>()
-            .remove::<Winner>();
+            .remove::<Winner>()
+            .remove::<Casting>()
+            .remove::<Attacking>()
+            .remove::<AttackCooldown>()
+            .remove::<LastInput>();
     }
 }
 
-fn start_preparation_timer(mut cmd: Commands) {
-    let prepatation_timeout = 0.0;
+fn start_preparation_timer(mut cmd: Commands, config: Res<BattleConfig>) {
     cmd.insert_resource(PreparationTimer(Timer::from_seconds(
-        prepatation_timeout,
+        config.preparation_time,
         false,
     )));
 }
@@ -402,28 +371,28 @@ fn check_preparation_timer(
     }
 }
 
-fn bot_waypoint_system(mut cmd: Commands, query: Query<Entity, (With<Bot>, Without<Waypoint>)>) {
-    let mut rng = rand::thread_rng();
-    for entity in query.iter() {
-        let target_position = Vec3::new(rng.gen_range(-5.0..5.0), 0.0, rng.gen_range(-5.0..5.0));
-        cmd.entity(entity).insert(Waypoint(target_position));
-    }
-}
-
+#[allow(clippy::type_complexity)]
 fn move_to_waypoint_system(
     mut cmd: Commands,
-    mut query: Query<(Entity, &mut RigidBodyPosition, &Waypoint)>,
+    mut query: Query<(
+        Entity,
+        &mut RigidBodyPosition,
+        &Waypoint,
+        Option<&Stats>,
+        Option<&StatusEffects>,
+    )>,
     time: Res<Time>,
 ) {
-    let speed = 2.0;
-    for (entity, mut position, waypoint) in query.iter_mut() {
-        let target = waypoint.0;
-        let dir = (target - position.position.translation.into()).normalize();
-        let translation = dir * time.delta_seconds() * speed;
+    for (entity, mut position, waypoint, stats, effects) in query.iter_mut() {
+        let speed = stats.map_or(BASE_MOVE_SPEED, |stats| stats.move_speed)
+            * effects.map_or(1.0, |effects| effects.speed_multiplier());
+        let current = Vec3::from(position.position.translation);
+        let next = step_towards(current, waypoint.0, speed, time.delta_seconds());
+        let translation = next - current;
         position
             .position
             .append_translation_mut(&[translation.x, translation.y, translation.z].into());
-        if (Vec3::from(position.position.translation) - target).length() < 0.02 {
+        if has_arrived(next, waypoint.0) {
             cmd.entity(entity).remove::<Waypoint>();
         }
     }
@@ -1,7 +1,34 @@
 use bevy::prelude::*;
+use structopt::StructOpt;
 
-use wizardwars_server::ServerPlugin;
+use wizardwars_server::{ServerConfig, ServerOptions, ServerPlugin};
+use wizardwars_shared::{items::Items, spells::Spells};
 
 fn main() {
-    App::build().add_plugin(ServerPlugin).run();
+    let options = ServerOptions::from_args();
+    let config = match ServerConfig::from_options(&options) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let spells = match Spells::from_file(&config.spells) {
+        Ok(spells) => spells,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let items = match Items::from_file(&config.items) {
+        Ok(items) => items,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    App::build()
+        .add_plugin(ServerPlugin::new(config, spells, items))
+        .run();
 }
@@ -0,0 +1,446 @@
+use crate::{
+    battle::BattleState,
+    reconnect::Disconnected,
+    safe_zone::SafeZone,
+    shopping::{buy, price},
+    spells::{try_cast, Casting},
+    states::ServerState,
+};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::RigidBodyVelocity;
+use rand::Rng;
+use std::collections::HashSet;
+use wizardwars_shared::{
+    components::{
+        damage::Projectile, Bot, BotDifficulty, Cooldowns, Dead, Gold, Health, Inventory, Mana,
+        Owner, Player, Position, Stats, StatusEffects, Waypoint, BASE_MOVE_SPEED,
+    },
+    items::{Items, ShopItem},
+    spells::Spells,
+};
+
+/// Distance bots try to keep to their target.
+const PREFERRED_DISTANCE: f32 = 5.0;
+/// Fraction of the safe radius bots stay within.
+const SAFE_MARGIN: f32 = 0.8;
+/// Projectiles that pass further away than this are ignored.
+const DODGE_RADIUS: f32 = 1.0;
+const DODGE_DISTANCE: f32 = 2.0;
+/// Seconds ahead bots look for incoming projectiles.
+const DODGE_HORIZON: f32 = 1.5;
+
+struct Profile {
+    /// Seconds between two attempts to cast.
+    reaction_time: f32,
+    /// Shots land up to this far away from the aimed point.
+    aim_error: f32,
+    leads_shots: bool,
+    dodge_chance: f64,
+    /// Items bought per shopping phase.
+    purchases: usize,
+}
+
+fn profile(difficulty: BotDifficulty) -> Profile {
+    match difficulty {
+        BotDifficulty::Easy => Profile {
+            reaction_time: 1.5,
+            aim_error: 1.5,
+            leads_shots: false,
+            dodge_chance: 0.2,
+            purchases: 1,
+        },
+        BotDifficulty::Normal => Profile {
+            reaction_time: 0.8,
+            aim_error: 0.6,
+            leads_shots: true,
+            dodge_chance: 0.5,
+            purchases: 2,
+        },
+        BotDifficulty::Hard => Profile {
+            reaction_time: 0.4,
+            aim_error: 0.1,
+            leads_shots: true,
+            dodge_chance: 0.9,
+            purchases: usize::MAX,
+        },
+    }
+}
+
+/// Decides what a bot does during a match.
+pub struct BotBrain {
+    difficulty: BotDifficulty,
+    target: Option<Entity>,
+    cast_timer: Timer,
+    /// Projectiles that were seen already, each one is dodged or ignored only once.
+    noticed: HashSet<Entity>,
+}
+
+impl BotBrain {
+    pub fn new(difficulty: BotDifficulty) -> Self {
+        Self {
+            difficulty,
+            target: None,
+            cast_timer: Timer::from_seconds(profile(difficulty).reaction_time, true),
+            noticed: HashSet::new(),
+        }
+    }
+}
+
+pub struct BotsPlugin;
+
+impl Plugin for BotsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(attach_brain_system.system())
+            .add_system_set(
+                SystemSet::on_enter(ServerState::Shopping).with_system(shopping_system.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(BattleState::Battle)
+                    .with_system(choose_target_system.system())
+                    .with_system(dodge_system.system())
+                    .with_system(movement_system.system())
+                    .with_system(cast_system.system()),
+            );
+    }
+}
+
+/// Bots taking over for a disconnected player play on the default difficulty.
+fn attach_brain_system(mut cmd: Commands, query: Query<Entity, (With<Bot>, Without<BotBrain>)>) {
+    for entity in query.iter() {
+        cmd.entity(entity)
+            .insert(BotBrain::new(BotDifficulty::default()));
+    }
+}
+
+/// Buys the most expensive things the bot can afford. Bots playing for a disconnected player
+/// leave its gold alone.
+fn shopping_system(
+    spells: Res<Spells>,
+    items: Res<Items>,
+    mut bots: Query<(&BotBrain, &mut Gold, &mut Inventory), Without<Disconnected>>,
+) {
+    let mut catalog = spells
+        .iter()
+        .map(|spell| ShopItem::Spell(spell.id.clone()))
+        .chain(items.iter().map(|item| ShopItem::Item(item.id.clone())))
+        .filter_map(|shop_item| Some((price(&spells, &items, &shop_item)?, shop_item)))
+        .collect::<Vec<_>>();
+    catalog.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+    for (brain, mut gold, mut inventory) in bots.iter_mut() {
+        let mut purchases = profile(brain.difficulty).purchases;
+        for (price, shop_item) in catalog.iter() {
+            if purchases == 0 {
+                break;
+            }
+            if buy(&mut inventory, &mut gold, shop_item, *price).is_ok() {
+                purchases -= 1;
+            }
+        }
+    }
+}
+
+/// Sticks to the closest enemy until it dies.
+fn choose_target_system(
+    mut bots: Query<(Entity, &Position, &mut BotBrain), Without<Dead>>,
+    players: Query<(Entity, &Position), (With<Player>, With<Health>, Without<Dead>)>,
+) {
+    for (bot, position, mut brain) in bots.iter_mut() {
+        if brain
+            .target
+            .map_or(false, |target| players.get(target).is_ok())
+        {
+            continue;
+        }
+
+        brain.target = players
+            .iter()
+            .filter(|(entity, _)| *entity != bot)
+            .map(|(entity, other)| (entity, other.0.distance_squared(position.0)))
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(entity, _)| entity);
+    }
+}
+
+fn dodge_system(
+    mut cmd: Commands,
+    safe_zone: Option<Res<SafeZone>>,
+    mut bots: Query<(Entity, &Position, &mut BotBrain), Without<Dead>>,
+    projectiles: Query<(Entity, &Position, &Owner, &RigidBodyVelocity), With<Projectile>>,
+) {
+    let mut rng = rand::thread_rng();
+    for (bot, position, mut brain) in bots.iter_mut() {
+        brain
+            .noticed
+            .retain(|&projectile| projectiles.get(projectile).is_ok());
+
+        for (projectile, projectile_position, owner, velocity) in projectiles.iter() {
+            if owner.entity() == bot || brain.noticed.contains(&projectile) {
+                continue;
+            }
+            let velocity = Vec3::from(velocity.linvel);
+            let impact = match time_to_impact(position.0, projectile_position.0, velocity) {
+                Some(time) if time <= DODGE_HORIZON => time,
+                _ => continue,
+            };
+            brain.noticed.insert(projectile);
+            if !rng.gen_bool(profile(brain.difficulty).dodge_chance) {
+                continue;
+            }
+
+            let direction = dodge_direction(position.0, projectile_position.0, velocity);
+            let destination = position.0 + direction * DODGE_DISTANCE;
+            let destination = keep_inside(destination, safe_zone.as_deref());
+            debug!("Bot dodging a projectile {:.2}s before impact", impact);
+            cmd.entity(bot)
+                .remove::<Casting>()
+                .insert(Waypoint(destination));
+        }
+    }
+}
+
+/// Circles around the target at a distance, staying inside the safe zone.
+#[allow(clippy::type_complexity)]
+fn movement_system(
+    mut cmd: Commands,
+    safe_zone: Option<Res<SafeZone>>,
+    bots: Query<
+        (Entity, &Position, &BotBrain),
+        (Without<Waypoint>, Without<Casting>, Without<Dead>),
+    >,
+    positions: Query<&Position>,
+) {
+    let mut rng = rand::thread_rng();
+    for (bot, position, brain) in bots.iter() {
+        let target = brain
+            .target
+            .and_then(|target| positions.get(target).ok())
+            .map(|target| target.0);
+
+        let destination = match target {
+            Some(target) => {
+                let away = flat(position.0 - target).normalize_or_zero();
+                let angle = rng.gen_range(-0.6..0.6f32);
+                target + Quat::from_rotation_y(angle) * away * PREFERRED_DISTANCE
+            }
+            None => position.0 + Vec3::new(rng.gen_range(-5.0..5.0), 0.0, rng.gen_range(-5.0..5.0)),
+        };
+        let destination = keep_inside(flat(destination), safe_zone.as_deref());
+        cmd.entity(bot).insert(Waypoint(destination));
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn cast_system(
+    mut cmd: Commands,
+    spells: Res<Spells>,
+    time: Res<Time>,
+    mut bots: Query<
+        (
+            Entity,
+            &Position,
+            &mut BotBrain,
+            &Mana,
+            &Cooldowns,
+            Option<&Inventory>,
+            Option<&StatusEffects>,
+        ),
+        (Without<Casting>, Without<Dead>),
+    >,
+    targets: Query<(
+        &Position,
+        Option<&Waypoint>,
+        Option<&Stats>,
+        Option<&RigidBodyVelocity>,
+    )>,
+) {
+    let mut rng = rand::thread_rng();
+    for (bot, position, mut brain, mana, cooldowns, inventory, effects) in bots.iter_mut() {
+        if !brain.cast_timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+        let (target, waypoint, stats, velocity) =
+            match brain.target.and_then(|target| targets.get(target).ok()) {
+                Some(target) => target,
+                None => continue,
+            };
+
+        let distance = flat(target.0 - position.0).length();
+        let spell = spells
+            .iter()
+            .filter(|spell| spell.speed * spell.lifetime >= distance)
+            .filter(|spell| {
+                spell.price == 0 || inventory.map_or(false, |inv| inv.has_spell(&spell.id))
+            })
+            .filter(|spell| cooldowns.is_ready(&spell.id) && mana.can_afford(spell.mana_cost))
+            .max_by_key(|spell| spell.damage);
+        let spell = match spell {
+            Some(spell) => spell,
+            None => continue,
+        };
+
+        let profile = profile(brain.difficulty);
+        let mut aim = target.0;
+        if profile.leads_shots {
+            // Players walk towards their waypoint, knockback shows up as rigidbody velocity.
+            let speed = stats.map_or(BASE_MOVE_SPEED, |stats| stats.move_speed);
+            let walking = waypoint.map_or(Vec3::ZERO, |waypoint| {
+                flat(waypoint.0 - target.0).normalize_or_zero() * speed
+            });
+            let pushed = velocity.map_or(Vec3::ZERO, |velocity| Vec3::from(velocity.linvel));
+            let target_velocity = flat(walking + pushed);
+            let after_cast = target.0 + target_velocity * spell.cast_time;
+            aim = lead_target(position.0, after_cast, target_velocity, spell.speed);
+        }
+        if profile.aim_error > 0.0 {
+            let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+            let offset = rng.gen_range(0.0..profile.aim_error);
+            aim += Quat::from_rotation_y(angle) * Vec3::X * offset;
+        }
+
+        if try_cast(spell, mana, cooldowns, inventory, effects).is_ok() {
+            cmd.entity(bot)
+                .remove::<Waypoint>()
+                .insert(Casting::new(spell, aim));
+        }
+    }
+}
+
+fn flat(vector: Vec3) -> Vec3 {
+    Vec3::new(vector.x, 0.0, vector.z)
+}
+
+fn keep_inside(position: Vec3, safe_zone: Option<&SafeZone>) -> Vec3 {
+    let radius = match safe_zone {
+        Some(safe_zone) => safe_zone.radius() * SAFE_MARGIN,
+        None => return position,
+    };
+    let horizontal = flat(position);
+    if horizontal.length() <= radius {
+        return position;
+    }
+
+    horizontal.normalize_or_zero() * radius + Vec3::Y * position.y
+}
+
+/// Point to shoot at to hit a target moving at a constant velocity, the target itself if the
+/// projectile is too slow to ever reach it.
+fn lead_target(shooter: Vec3, target: Vec3, target_velocity: Vec3, projectile_speed: f32) -> Vec3 {
+    let offset = flat(target - shooter);
+    let velocity = flat(target_velocity);
+
+    // |offset + velocity * t| = projectile_speed * t
+    let a = velocity.length_squared() - projectile_speed * projectile_speed;
+    let b = 2.0 * offset.dot(velocity);
+    let c = offset.length_squared();
+
+    let time = if a.abs() < f32::EPSILON {
+        if b.abs() < f32::EPSILON {
+            None
+        } else {
+            Some(-c / b)
+        }
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            None
+        } else {
+            let root = discriminant.sqrt();
+            let t1 = (-b - root) / (2.0 * a);
+            let t2 = (-b + root) / (2.0 * a);
+            [t1, t2]
+                .iter()
+                .copied()
+                .filter(|t| *t > 0.0)
+                .min_by(|x, y| x.partial_cmp(y).unwrap_or(std::cmp::Ordering::Equal))
+        }
+    };
+
+    match time {
+        Some(time) if time > 0.0 => target + velocity * time,
+        _ => target,
+    }
+}
+
+/// Seconds until a projectile passes within `DODGE_RADIUS` of the position, `None` if it never
+/// does.
+fn time_to_impact(position: Vec3, projectile: Vec3, velocity: Vec3) -> Option<f32> {
+    let offset = flat(position - projectile);
+    let velocity = flat(velocity);
+    let speed_squared = velocity.length_squared();
+    if speed_squared < f32::EPSILON {
+        return None;
+    }
+
+    let time = offset.dot(velocity) / speed_squared;
+    if time < 0.0 {
+        return None;
+    }
+    let miss = offset - velocity * time;
+    if miss.length() > DODGE_RADIUS {
+        return None;
+    }
+
+    Some(time)
+}
+
+/// Sideways away from the projectile's path.
+fn dodge_direction(position: Vec3, projectile: Vec3, velocity: Vec3) -> Vec3 {
+    let offset = flat(position - projectile);
+    let velocity = flat(velocity).normalize_or_zero();
+    let miss = offset - velocity * offset.dot(velocity);
+    if miss.length_squared() > f32::EPSILON {
+        miss.normalize()
+    } else {
+        Vec3::new(-velocity.z, 0.0, velocity.x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!(a.distance(b) < 0.001, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn leads_moving_targets() {
+        let target = Vec3::new(10.0, 0.0, 0.0);
+        let velocity = Vec3::new(0.0, 0.0, 1.0);
+
+        assert_close(lead_target(Vec3::ZERO, target, Vec3::ZERO, 5.0), target);
+
+        let aim = lead_target(Vec3::ZERO, target, velocity, 5.0);
+        let time = aim.length() / 5.0;
+        assert_close(aim, target + velocity * time);
+    }
+
+    #[test]
+    fn aims_at_unreachable_targets() {
+        let target = Vec3::new(10.0, 0.0, 0.0);
+        let velocity = Vec3::new(10.0, 0.0, 0.0);
+
+        assert_close(lead_target(Vec3::ZERO, target, velocity, 5.0), target);
+    }
+
+    #[test]
+    fn detects_incoming_projectiles() {
+        let position = Vec3::new(5.0, 0.0, 0.0);
+        let velocity = Vec3::new(5.0, 0.0, 0.0);
+
+        let time = time_to_impact(position, Vec3::new(0.0, 0.5, 0.5), velocity).unwrap();
+        assert!((time - 1.0).abs() < 0.001);
+        assert_eq!(
+            time_to_impact(position, Vec3::new(0.0, 0.0, 3.0), velocity),
+            None
+        );
+        assert_eq!(
+            time_to_impact(position, Vec3::new(10.0, 0.0, 0.0), velocity),
+            None
+        );
+
+        let direction = dodge_direction(position, Vec3::new(0.0, 0.0, 0.5), velocity);
+        assert_close(direction, Vec3::new(0.0, 0.0, -1.0));
+    }
+}
@@ -0,0 +1,407 @@
+use crate::{
+    economy::EconomyConfig, knockback::KnockbackConfig, melee::MeleeConfig,
+    safe_zone::SafeZoneConfig,
+};
+use serde::Deserialize;
+use std::{
+    fmt::Formatter,
+    fs,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use structopt::StructOpt;
+use wizardwars_shared::{
+    items::DEFAULT_ITEMS_PATH, network::DISCOVERY_PORT, resources::MAX_PLAYERS,
+    spells::DEFAULT_SPELLS_PATH, validation::non_negative,
+};
+
+#[derive(StructOpt, Debug, Default)]
+#[structopt(name = "wizardwars_server")]
+pub struct ServerOptions {
+    /// Path to a `.ron` or `.toml` config file
+    #[structopt(short, long, parse(from_os_str))]
+    pub config: Option<PathBuf>,
+    /// Address to listen on, e.g. `0.0.0.0:9001`
+    #[structopt(short, long)]
+    pub address: Option<SocketAddr>,
+    /// Server name shown to players looking for games on the LAN
+    #[structopt(short, long)]
+    pub name: Option<String>,
+    /// UDP port to answer LAN discovery queries on
+    #[structopt(long)]
+    pub discovery_port: Option<u16>,
+    /// Do not answer LAN discovery queries
+    #[structopt(long)]
+    pub no_discovery: bool,
+    /// Server updates per second
+    #[structopt(long)]
+    pub tick_rate: Option<u32>,
+    /// Number of rounds in a match
+    #[structopt(long)]
+    pub rounds: Option<u32>,
+    /// Shop phase duration in seconds
+    #[structopt(long)]
+    pub shopping_time: Option<f32>,
+    /// Delay before each round starts in seconds
+    #[structopt(long)]
+    pub preparation_time: Option<f32>,
+    /// Maximum number of players in the lobby, bots included
+    #[structopt(long)]
+    pub max_players: Option<usize>,
+    /// Health every player starts a round with
+    #[structopt(long)]
+    pub starting_health: Option<u32>,
+    /// Mana every player starts a round with
+    #[structopt(long)]
+    pub mana: Option<f32>,
+    /// Mana restored per second
+    #[structopt(long)]
+    pub mana_regeneration: Option<f32>,
+    /// Path to the spell definitions file
+    #[structopt(long, parse(from_os_str))]
+    pub spells: Option<PathBuf>,
+    /// Path to the shop items file
+    #[structopt(long, parse(from_os_str))]
+    pub items: Option<PathBuf>,
+    /// Shut the server down after the first match instead of returning to the lobby
+    #[structopt(long)]
+    pub exit_after_match: bool,
+    /// Seconds a player who lost the connection during a match can reconnect, a bot plays meanwhile
+    #[structopt(long)]
+    pub reconnect_grace_period: Option<f32>,
+    /// Number of matches the server runs at the same time
+    #[structopt(long)]
+    pub max_rooms: Option<usize>,
+    /// Record every match into a replay file in this directory
+    #[structopt(long, parse(from_os_str))]
+    pub replay_dir: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub address: SocketAddr,
+    /// Name of the main room, also announced on the LAN.
+    pub name: String,
+    /// UDP port to answer LAN discovery queries on, `None` to stay hidden.
+    pub discovery_port: Option<u16>,
+    pub tick_rate: u32,
+    pub rounds: u32,
+    pub shopping_time: f32,
+    pub preparation_time: f32,
+    pub max_players: usize,
+    pub starting_health: u32,
+    pub mana: f32,
+    pub mana_regeneration: f32,
+    pub spells: PathBuf,
+    pub items: PathBuf,
+    pub safe_zone: SafeZoneConfig,
+    pub economy: EconomyConfig,
+    pub melee: MeleeConfig,
+    pub knockback: KnockbackConfig,
+    /// Shut the server down once a match is over instead of returning to the lobby.
+    pub exit_after_match: bool,
+    /// Seconds a player who lost the connection during a match can reconnect. A bot plays in
+    /// their place until then or until the match is over.
+    pub reconnect_grace_period: f32,
+    /// Matches running at the same time. Every room listens on its own port, counting up from
+    /// the port of `address`.
+    pub max_rooms: usize,
+    /// Directory matches are recorded to, `None` to not record them.
+    pub replay_dir: Option<PathBuf>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            address: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 9001),
+            name: "Wizard Wars".to_owned(),
+            discovery_port: Some(DISCOVERY_PORT),
+            tick_rate: 60,
+            rounds: 5,
+            shopping_time: 0.0,
+            preparation_time: 0.0,
+            max_players: MAX_PLAYERS,
+            starting_health: 20,
+            mana: 100.0,
+            mana_regeneration: 5.0,
+            spells: PathBuf::from(DEFAULT_SPELLS_PATH),
+            items: PathBuf::from(DEFAULT_ITEMS_PATH),
+            safe_zone: SafeZoneConfig::default(),
+            economy: EconomyConfig::default(),
+            melee: MeleeConfig::default(),
+            knockback: KnockbackConfig::default(),
+            exit_after_match: false,
+            reconnect_grace_period: 30.0,
+            max_rooms: 4,
+            replay_dir: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Loads the config file given in `options` (if any), applies command line
+    /// overrides on top of it and validates the result.
+    pub fn from_options(options: &ServerOptions) -> Result<Self, ConfigError> {
+        let mut config = match &options.config {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        config.apply_options(options);
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let contents =
+            fs::read_to_string(path).map_err(|err| ConfigError::Io(path.to_owned(), err))?;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("ron") => ron::from_str(&contents)
+                .map_err(|err| ConfigError::Parse(path.to_owned(), err.to_string())),
+            Some("toml") => toml::from_str(&contents)
+                .map_err(|err| ConfigError::Parse(path.to_owned(), err.to_string())),
+            _ => Err(ConfigError::UnsupportedFormat(path.to_owned())),
+        }
+    }
+
+    pub fn apply_options(&mut self, options: &ServerOptions) {
+        if let Some(address) = options.address {
+            self.address = address;
+        }
+        if let Some(name) = &options.name {
+            self.name = name.clone();
+        }
+        if let Some(discovery_port) = options.discovery_port {
+            self.discovery_port = Some(discovery_port);
+        }
+        if options.no_discovery {
+            self.discovery_port = None;
+        }
+        if let Some(tick_rate) = options.tick_rate {
+            self.tick_rate = tick_rate;
+        }
+        if let Some(rounds) = options.rounds {
+            self.rounds = rounds;
+        }
+        if let Some(shopping_time) = options.shopping_time {
+            self.shopping_time = shopping_time;
+        }
+        if let Some(preparation_time) = options.preparation_time {
+            self.preparation_time = preparation_time;
+        }
+        if let Some(max_players) = options.max_players {
+            self.max_players = max_players;
+        }
+        if let Some(starting_health) = options.starting_health {
+            self.starting_health = starting_health;
+        }
+        if let Some(mana) = options.mana {
+            self.mana = mana;
+        }
+        if let Some(mana_regeneration) = options.mana_regeneration {
+            self.mana_regeneration = mana_regeneration;
+        }
+        if let Some(spells) = &options.spells {
+            self.spells = spells.clone();
+        }
+        if let Some(items) = &options.items {
+            self.items = items.clone();
+        }
+        if options.exit_after_match {
+            self.exit_after_match = true;
+        }
+        if let Some(reconnect_grace_period) = options.reconnect_grace_period {
+            self.reconnect_grace_period = reconnect_grace_period;
+        }
+        if let Some(max_rooms) = options.max_rooms {
+            self.max_rooms = max_rooms;
+        }
+        if let Some(replay_dir) = &options.replay_dir {
+            self.replay_dir = Some(replay_dir.clone());
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.tick_rate == 0 || self.tick_rate > 1000 {
+            return Err(ConfigError::Invalid(format!(
+                "tick_rate must be between 1 and 1000, got {}",
+                self.tick_rate
+            )));
+        }
+        if self.rounds == 0 {
+            return Err(ConfigError::Invalid(
+                "rounds must be greater than 0".to_owned(),
+            ));
+        }
+        non_negative("shopping_time", self.shopping_time).map_err(ConfigError::Invalid)?;
+        non_negative("preparation_time", self.preparation_time).map_err(ConfigError::Invalid)?;
+        if self.max_players < 2 || self.max_players > MAX_PLAYERS {
+            return Err(ConfigError::Invalid(format!(
+                "max_players must be between 2 and {}, got {}",
+                MAX_PLAYERS, self.max_players
+            )));
+        }
+        if self.starting_health == 0 {
+            return Err(ConfigError::Invalid(
+                "starting_health must be greater than 0".to_owned(),
+            ));
+        }
+        non_negative("mana", self.mana).map_err(ConfigError::Invalid)?;
+        non_negative("mana_regeneration", self.mana_regeneration).map_err(ConfigError::Invalid)?;
+        non_negative("reconnect_grace_period", self.reconnect_grace_period)
+            .map_err(ConfigError::Invalid)?;
+        if self.max_rooms == 0 {
+            return Err(ConfigError::Invalid(
+                "max_rooms must be greater than 0".to_owned(),
+            ));
+        }
+        if self.address.port() as usize + self.max_rooms > u16::MAX as usize + 1 {
+            return Err(ConfigError::Invalid(format!(
+                "max_rooms ({}) does not fit into the ports after {}",
+                self.max_rooms,
+                self.address.port()
+            )));
+        }
+        let room_ports =
+            self.address.port() as usize..self.address.port() as usize + self.max_rooms;
+        if let Some(port) = self
+            .discovery_port
+            .filter(|port| room_ports.contains(&(*port as usize)))
+        {
+            return Err(ConfigError::Invalid(format!(
+                "discovery_port {} is used by one of the rooms",
+                port
+            )));
+        }
+        self.safe_zone.validate().map_err(ConfigError::Invalid)?;
+        self.economy.validate().map_err(ConfigError::Invalid)?;
+        self.melee.validate().map_err(ConfigError::Invalid)?;
+        self.knockback.validate().map_err(ConfigError::Invalid)?;
+
+        Ok(())
+    }
+
+    pub fn tick_duration(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.tick_rate as f64)
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, String),
+    UnsupportedFormat(PathBuf),
+    Invalid(String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "Cannot read {}: {}", path.display(), err),
+            ConfigError::Parse(path, err) => write!(f, "Cannot parse {}: {}", path.display(), err),
+            ConfigError::UnsupportedFormat(path) => write!(
+                f,
+                "Unsupported config format: {} (expected .ron or .toml)",
+                path.display()
+            ),
+            ConfigError::Invalid(reason) => write!(f, "Invalid config: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_is_valid() {
+        assert!(ServerConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn options_override_config() {
+        let options = ServerOptions {
+            address: Some("0.0.0.0:9100".parse().unwrap()),
+            rounds: Some(3),
+            max_players: Some(4),
+            ..Default::default()
+        };
+        let mut config = ServerConfig::default();
+        config.apply_options(&options);
+
+        assert_eq!(config.address, "0.0.0.0:9100".parse().unwrap());
+        assert_eq!(config.rounds, 3);
+        assert_eq!(config.max_players, 4);
+        assert_eq!(config.tick_rate, 60);
+        assert!(!config.exit_after_match);
+        assert_eq!(config.discovery_port, Some(DISCOVERY_PORT));
+
+        assert_eq!(config.replay_dir, None);
+
+        config.apply_options(&ServerOptions {
+            no_discovery: true,
+            replay_dir: Some(PathBuf::from("replays")),
+            ..Default::default()
+        });
+        assert_eq!(config.discovery_port, None);
+        assert_eq!(config.replay_dir, Some(PathBuf::from("replays")));
+    }
+
+    #[test]
+    fn parse_partial_ron() {
+        let config: ServerConfig = ron::from_str("(rounds: 7, shopping_time: 15.0)").unwrap();
+
+        assert_eq!(config.rounds, 7);
+        assert!((config.shopping_time - 15.0).abs() < f32::EPSILON);
+        assert_eq!(config.max_players, MAX_PLAYERS);
+    }
+
+    #[test]
+    fn parse_partial_toml() {
+        let config: ServerConfig =
+            toml::from_str("address = \"0.0.0.0:9002\"\ntick_rate = 30").unwrap();
+
+        assert_eq!(config.address, "0.0.0.0:9002".parse().unwrap());
+        assert_eq!(config.tick_rate, 30);
+        assert_eq!(config.rounds, 5);
+    }
+
+    #[test]
+    fn reject_invalid_values() {
+        let invalid = [
+            ServerConfig {
+                tick_rate: 0,
+                ..Default::default()
+            },
+            ServerConfig {
+                rounds: 0,
+                ..Default::default()
+            },
+            ServerConfig {
+                shopping_time: -1.0,
+                ..Default::default()
+            },
+            ServerConfig {
+                max_players: MAX_PLAYERS + 1,
+                ..Default::default()
+            },
+            ServerConfig {
+                starting_health: 0,
+                ..Default::default()
+            },
+            ServerConfig {
+                max_rooms: 0,
+                ..Default::default()
+            },
+        ];
+
+        for config in invalid.iter() {
+            assert!(config.validate().is_err(), "{:?} should be invalid", config);
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use crate::rooms::RoomHandle;
+use bevy::prelude::*;
+use std::net::SocketAddr;
+use wizardwars_shared::{
+    network::{DiscoveryResponder, ServerAnnouncement},
+    version::GameVersion,
+};
+
+/// Answers LAN discovery queries with the state of the main room.
+pub struct DiscoveryPlugin {
+    pub port: u16,
+    /// Address the game listens on, other machines cannot join through a loopback one.
+    pub address: SocketAddr,
+}
+
+impl Plugin for DiscoveryPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        if self.address.ip().is_loopback() {
+            warn!(
+                "The game listens on {}, only clients on this machine can join the servers \
+                 they discover. Set `address` to a LAN or unspecified address to play on the LAN.",
+                self.address
+            );
+        }
+        match DiscoveryResponder::bind(self.port) {
+            Ok(responder) => {
+                info!("Answering LAN discovery on port {}", self.port);
+                app.insert_resource(responder)
+                    .add_system(answer_discovery_system.system());
+            }
+            Err(err) => warn!(
+                "LAN discovery is disabled, cannot bind port {}: {}",
+                self.port, err
+            ),
+        }
+    }
+}
+
+fn answer_discovery_system(
+    responder: Res<DiscoveryResponder>,
+    room: Res<RoomHandle>,
+    version: Res<GameVersion>,
+) {
+    let info = match room.info() {
+        Some(info) => info,
+        None => return,
+    };
+    let announcement = ServerAnnouncement {
+        name: info.name,
+        port: info.port,
+        players: info.players,
+        max_players: info.max_players,
+        state: info.state,
+        version: *version,
+    };
+    if let Err(err) = responder.respond(&announcement) {
+        warn!("Cannot answer discovery query: {}", err);
+    }
+}
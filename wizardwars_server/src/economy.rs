@@ -0,0 +1,219 @@
+use crate::{
+    battle::{BattleState, BattleSystem},
+    network::ServerPacket,
+    states::ServerState,
+};
+use bevy::prelude::*;
+use serde::Deserialize;
+use wizardwars_shared::{
+    components::{Client, Dead, Gold, Inventory, Player, Winner},
+    events::{DamageApplied, Killed},
+    messages::server_messages::ShoppingServerMessage,
+};
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct EconomyConfig {
+    pub starting_gold: u32,
+    pub gold_per_kill: u32,
+    /// Gold for every point of damage dealt to other players.
+    pub gold_per_damage: u32,
+    /// Gold for finishing a round in the given place, first entry is the winner.
+    pub placement_gold: Vec<u32>,
+    /// Fraction of the price refunded when selling.
+    pub sell_ratio: f32,
+}
+
+impl Default for EconomyConfig {
+    fn default() -> Self {
+        Self {
+            starting_gold: 20,
+            gold_per_kill: 15,
+            gold_per_damage: 1,
+            placement_gold: vec![30, 20, 10, 5],
+            sell_ratio: 0.5,
+        }
+    }
+}
+
+impl EconomyConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.sell_ratio.is_finite() || self.sell_ratio < 0.0 || self.sell_ratio > 1.0 {
+            return Err(format!(
+                "economy.sell_ratio must be between 0 and 1, got {}",
+                self.sell_ratio
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn round_reward(&self, earnings: &RoundEarnings) -> u32 {
+        let placement = earnings
+            .placement
+            .and_then(|place| self.placement_gold.get(place as usize - 1))
+            .copied()
+            .unwrap_or(0);
+
+        earnings.kills * self.gold_per_kill
+            + earnings.damage_dealt * self.gold_per_damage
+            + placement
+    }
+
+    pub fn sell_price(&self, price: u32) -> u32 {
+        (price as f32 * self.sell_ratio).floor() as u32
+    }
+}
+
+/// What a player has achieved during the current round.
+#[derive(Debug, Default, Clone)]
+pub struct RoundEarnings {
+    pub damage_dealt: u32,
+    pub kills: u32,
+    /// 1 for the winner, `n` for the first player to die in a round of `n` players.
+    pub placement: Option<u32>,
+}
+
+pub struct EconomyPlugin;
+
+impl Plugin for EconomyPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_enter(ServerState::WaitLoading).with_system(setup_wallets.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(BattleState::Battle)
+                .with_system(
+                    track_damage_system
+                        .system()
+                        .after(BattleSystem::ApplyDamage),
+                )
+                .with_system(track_placement_system.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(ServerState::Battle).with_system(award_gold_system.system()),
+        );
+    }
+}
+
+fn setup_wallets(
+    mut cmd: Commands,
+    config: Res<EconomyConfig>,
+    players: Query<Entity, With<Player>>,
+) {
+    for entity in players.iter() {
+        cmd.entity(entity)
+            .insert(Gold(config.starting_gold))
+            .insert(Inventory::default())
+            .insert(RoundEarnings::default());
+    }
+}
+
+fn track_damage_system(
+    mut applied: EventReader<DamageApplied>,
+    mut killed: EventReader<Killed>,
+    players: Query<&Player>,
+    mut earnings: Query<&mut RoundEarnings>,
+) {
+    for event in applied.iter() {
+        let source = match event.source {
+            Some(source) if source != event.target && players.get(event.target).is_ok() => source,
+            _ => continue,
+        };
+        if let Ok(mut earnings) = earnings.get_mut(source) {
+            earnings.damage_dealt += event.amount;
+        }
+    }
+    for event in killed.iter() {
+        let source = match event.source {
+            Some(source) if source != event.target && players.get(event.target).is_ok() => source,
+            _ => continue,
+        };
+        if let Ok(mut earnings) = earnings.get_mut(source) {
+            earnings.kills += 1;
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn track_placement_system(
+    mut dead: Query<&mut RoundEarnings, (With<Player>, With<Dead>, Added<Dead>)>,
+    mut winners: Query<&mut RoundEarnings, (With<Player>, Added<Winner>, Without<Dead>)>,
+    alive: Query<&Player, Without<Dead>>,
+) {
+    let place = alive.iter().count() as u32 + 1;
+    for mut earnings in dead.iter_mut() {
+        earnings.placement = Some(place);
+    }
+    for mut earnings in winners.iter_mut() {
+        earnings.placement = Some(1);
+    }
+}
+
+fn award_gold_system(
+    config: Res<EconomyConfig>,
+    mut packets: EventWriter<ServerPacket>,
+    mut players: Query<(&mut Gold, &mut RoundEarnings, Option<&Client>)>,
+) {
+    for (mut gold, mut earnings, client) in players.iter_mut() {
+        gold.0 += config.round_reward(&earnings);
+        *earnings = RoundEarnings::default();
+
+        if let Some(client) = client {
+            packets.send(ServerPacket::single(
+                ShoppingServerMessage::Gold(gold.0),
+                *client,
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_reward() {
+        let config = EconomyConfig {
+            gold_per_kill: 10,
+            gold_per_damage: 2,
+            placement_gold: vec![30, 20],
+            ..Default::default()
+        };
+
+        let winner = RoundEarnings {
+            damage_dealt: 15,
+            kills: 2,
+            placement: Some(1),
+        };
+        assert_eq!(config.round_reward(&winner), 20 + 30 + 30);
+
+        let last = RoundEarnings {
+            damage_dealt: 0,
+            kills: 0,
+            placement: Some(5),
+        };
+        assert_eq!(config.round_reward(&last), 0);
+    }
+
+    #[test]
+    fn sell_price() {
+        let config = EconomyConfig {
+            sell_ratio: 0.5,
+            ..Default::default()
+        };
+
+        assert_eq!(config.sell_price(25), 12);
+        assert_eq!(config.sell_price(0), 0);
+    }
+
+    #[test]
+    fn validate_sell_ratio() {
+        let config = EconomyConfig {
+            sell_ratio: 1.5,
+            ..Default::default()
+        };
+
+        assert!(config.validate().is_err());
+    }
+}
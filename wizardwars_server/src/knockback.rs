@@ -0,0 +1,112 @@
+use crate::battle::{BattleState, BattleSystem};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::{RigidBodyActivation, RigidBodyMassProps, RigidBodyVelocity};
+use serde::Deserialize;
+use wizardwars_shared::{
+    components::{damage::Resistances, Health},
+    validation::non_negative,
+};
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct KnockbackConfig {
+    /// How much stronger knockback gets as health drops. At no health left it is `1 + growth`
+    /// times the base strength.
+    pub growth: f32,
+}
+
+impl Default for KnockbackConfig {
+    fn default() -> Self {
+        Self { growth: 2.0 }
+    }
+}
+
+impl KnockbackConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        non_negative("knockback.growth", self.growth)
+    }
+}
+
+/// Pushes the target away along `direction`. `strength` is the impulse an unhurt target
+/// without knockback resistance receives.
+pub struct KnockbackEvent {
+    pub target: Entity,
+    pub direction: Vec3,
+    pub strength: f32,
+    /// Who pushed, credited when the target falls off the arena.
+    pub source: Option<Entity>,
+}
+
+pub struct KnockbackPlugin;
+
+impl Plugin for KnockbackPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<KnockbackEvent>().add_system_set(
+            // Knockback scales with missing health, push with the health after this frame's hits.
+            SystemSet::on_update(BattleState::Battle).with_system(
+                apply_knockback_system
+                    .system()
+                    .after(BattleSystem::ApplyDamage),
+            ),
+        );
+    }
+}
+
+/// Knockback grows with the fraction of health the target has lost.
+pub fn scaled_strength(strength: f32, health: Option<&Health>, growth: f32) -> f32 {
+    let missing = health.map_or(0.0, |health| 1.0 - health.fraction());
+
+    strength * (1.0 + growth * missing)
+}
+
+#[allow(clippy::type_complexity)]
+fn apply_knockback_system(
+    config: Res<KnockbackConfig>,
+    mut events: EventReader<KnockbackEvent>,
+    mut bodies: Query<(
+        &mut RigidBodyVelocity,
+        &RigidBodyMassProps,
+        &mut RigidBodyActivation,
+        Option<&Health>,
+        Option<&Resistances>,
+    )>,
+) {
+    for event in events.iter() {
+        let (mut velocity, mass, mut activation, health, resistances) =
+            match bodies.get_mut(event.target) {
+                Ok(body) => body,
+                Err(_) => continue,
+            };
+
+        let strength = scaled_strength(event.strength, health, config.growth)
+            * resistances.map_or(1.0, |resistances| resistances.knockback_multiplier());
+        let direction = Vec3::new(event.direction.x, 0.0, event.direction.z).normalize_or_zero();
+        let impulse = direction * strength;
+        velocity.apply_impulse(mass, [impulse.x, 0.0, impulse.z].into());
+        activation.wake_up(true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn knockback_grows_as_health_drops() {
+        let mut health = Health::new(20);
+        assert!((scaled_strength(2.0, Some(&health), 2.0) - 2.0).abs() < f32::EPSILON);
+
+        health.set_to(10);
+        assert!((scaled_strength(2.0, Some(&health), 2.0) - 4.0).abs() < f32::EPSILON);
+
+        health.set_to(0);
+        assert!((scaled_strength(2.0, Some(&health), 2.0) - 6.0).abs() < f32::EPSILON);
+        assert!((scaled_strength(2.0, None, 2.0) - 2.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn reject_negative_growth() {
+        assert!(KnockbackConfig::default().validate().is_ok());
+        assert!(KnockbackConfig { growth: -1.0 }.validate().is_err());
+    }
+}
@@ -1,60 +1,173 @@
 mod arena;
 mod battle;
+mod bots;
+mod config;
+mod discovery;
+mod economy;
+mod knockback;
 mod loading;
 mod lobby;
+mod melee;
 mod network;
+mod projectiles;
+mod reconnect;
+mod replay;
 mod result;
+mod rooms;
+mod safe_zone;
 mod shopping;
+mod spectators;
+mod spells;
 mod states;
+mod statistics;
+mod status_effects;
 mod util;
 
-use battle::BattlePlugin;
+use arena::ArenaConfig;
+use battle::{BattleConfig, BattlePlugin};
 use bevy::app::ScheduleRunnerSettings;
 use bevy::log::LogPlugin;
 use bevy::prelude::*;
 use bevy_rapier3d::physics::{NoUserData, RapierPhysicsPlugin};
+use bots::BotsPlugin;
+use discovery::DiscoveryPlugin;
+use economy::EconomyPlugin;
+use knockback::KnockbackPlugin;
 use loading::WaitLoadingPlugin;
-use lobby::LobbyPlugin;
-use network::NetworkPlugin;
-use result::ResultPlugin;
+use lobby::{LobbyConfig, LobbyPlugin};
+use melee::MeleePlugin;
+use network::{NetworkConfig, NetworkPlugin};
+use projectiles::ProjectilesPlugin;
+use reconnect::ReconnectPlugin;
+use replay::ReplayPlugin;
+use result::{ResultConfig, ResultPlugin};
+use rooms::{RoomHandle, Rooms, RoomsPlugin};
+use safe_zone::SafeZonePlugin;
 use shopping::{ShoppingConfig, ShoppingTimerPlugin};
+use spectators::SpectatorsPlugin;
+use spells::SpellsPlugin;
 use states::ServerState;
-use std::time::Duration;
+use statistics::StatisticsPlugin;
+use status_effects::StatusEffectsPlugin;
 use util::PrintStateNamesPlugin;
 use wizardwars_shared::{
     events::ClientEvent,
+    items::Items,
     messages::client_messages::ActionMessage,
     resources::{ArenaDimensions, CharacterDimensions, PlayerColors},
+    spells::Spells,
+    version::GameVersion,
 };
 
-pub struct ServerPlugin;
+pub use config::{ConfigError, ServerConfig, ServerOptions};
+
+pub struct ServerPlugin {
+    config: ServerConfig,
+    spells: Spells,
+    items: Items,
+    room: RoomHandle,
+}
+
+impl ServerPlugin {
+    /// The main room of the server, it opens further rooms on request.
+    pub fn new(config: ServerConfig, spells: Spells, items: Items) -> Self {
+        let rooms = Rooms::new(config.clone(), spells.clone(), items.clone());
+        let room = rooms
+            .register(&config.name)
+            .expect("There is always space for the main room");
+
+        Self::for_room(config, spells, items, room)
+    }
+
+    fn for_room(config: ServerConfig, spells: Spells, items: Items, room: RoomHandle) -> Self {
+        Self {
+            config,
+            spells,
+            items,
+            room,
+        }
+    }
+}
 
 impl Plugin for ServerPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_millis(
-            1000 / 60,
-        )))
-        .insert_resource(ShoppingConfig {
-            time_in_seconds: 0.0,
-        })
-        .insert_resource(CharacterDimensions::default())
-        .insert_resource(ArenaDimensions::default())
-        .insert_resource(PlayerColors::default())
-        .add_event::<ClientEvent<ActionMessage>>()
-        .add_state(ServerState::Init)
-        .add_system_set(
-            SystemSet::on_update(ServerState::Init).with_system(check_init_system.system()),
-        )
-        .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
-        .add_plugins(MinimalPlugins)
-        .add_plugin(LogPlugin::default())
-        .add_plugin(NetworkPlugin)
-        .add_plugin(LobbyPlugin)
-        .add_plugin(WaitLoadingPlugin)
-        .add_plugin(ShoppingTimerPlugin)
-        .add_plugin(BattlePlugin)
-        .add_plugin(ResultPlugin)
-        .add_plugin(PrintStateNamesPlugin);
+        let config = &self.config;
+        app.insert_resource(ScheduleRunnerSettings::run_loop(config.tick_duration()))
+            .insert_resource(NetworkConfig {
+                address: config.address,
+                tick_rate: config.tick_rate,
+                reconnect_grace_period: config.reconnect_grace_period,
+            })
+            .insert_resource(LobbyConfig {
+                max_players: config.max_players,
+            })
+            .insert_resource(ArenaConfig {
+                total_rounds: config.rounds,
+            })
+            .insert_resource(ShoppingConfig {
+                time_in_seconds: config.shopping_time,
+            })
+            .insert_resource(BattleConfig {
+                preparation_time: config.preparation_time,
+                starting_health: config.starting_health,
+                mana: config.mana,
+                mana_regeneration: config.mana_regeneration,
+            })
+            .insert_resource(ResultConfig {
+                exit_after_match: config.exit_after_match,
+            })
+            .insert_resource(config.safe_zone.clone())
+            .insert_resource(config.economy.clone())
+            .insert_resource(config.melee.clone())
+            .insert_resource(config.knockback.clone())
+            .insert_resource(self.spells.clone())
+            .insert_resource(self.items.clone())
+            .insert_resource(GameVersion::new(&self.spells, &self.items))
+            .insert_resource(self.room.clone())
+            .insert_resource(CharacterDimensions::default())
+            .insert_resource(ArenaDimensions::default())
+            .insert_resource(PlayerColors::default())
+            .add_event::<ClientEvent<ActionMessage>>()
+            .add_state(ServerState::Init)
+            .add_system_set(
+                SystemSet::on_update(ServerState::Init).with_system(check_init_system.system()),
+            )
+            .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
+            .add_plugins(MinimalPlugins);
+        // Logging and LAN discovery are set up once per process, by the main room.
+        if self.room.is_main() {
+            app.add_plugin(LogPlugin::default());
+            if let Some(port) = config.discovery_port {
+                app.add_plugin(DiscoveryPlugin {
+                    port,
+                    address: config.address,
+                });
+            }
+        }
+        app.add_plugin(NetworkPlugin)
+            .add_plugin(RoomsPlugin)
+            .add_plugin(LobbyPlugin)
+            .add_plugin(ReconnectPlugin)
+            .add_plugin(SpectatorsPlugin)
+            .add_plugin(WaitLoadingPlugin)
+            .add_plugin(ShoppingTimerPlugin)
+            .add_plugin(BattlePlugin)
+            .add_plugin(SpellsPlugin)
+            .add_plugin(MeleePlugin)
+            .add_plugin(KnockbackPlugin)
+            .add_plugin(ProjectilesPlugin)
+            .add_plugin(StatusEffectsPlugin)
+            .add_plugin(BotsPlugin)
+            .add_plugin(SafeZonePlugin)
+            .add_plugin(EconomyPlugin)
+            .add_plugin(StatisticsPlugin)
+            .add_plugin(ResultPlugin)
+            .add_plugin(PrintStateNamesPlugin);
+        if let Some(directory) = &config.replay_dir {
+            app.add_plugin(ReplayPlugin {
+                directory: directory.clone(),
+            });
+        }
     }
 }
 
@@ -1,5 +1,5 @@
 use crate::{
-    arena::{ArenaBuilder, SpawnPointsBuilder},
+    arena::{ArenaBuilder, ArenaConfig, SpawnPointsBuilder},
     network::ServerPacket,
     states::ServerState,
 };
@@ -21,6 +21,9 @@ pub struct LoadCompleteEvent {
 
 struct Loading;
 
+/// Marks the collider of the arena floor.
+pub struct ArenaFloor;
+
 pub struct WaitLoadingPlugin;
 
 impl Plugin for WaitLoadingPlugin {
@@ -45,7 +48,7 @@ impl Plugin for WaitLoadingPlugin {
 fn notify_clients(
     mut cmd: Commands,
     mut packets: EventWriter<ServerPacket>,
-    clients: Query<Entity, With<Client>>,
+    clients: Query<Entity, (With<Client>, With<Player>)>,
 ) {
     for e in clients.iter() {
         cmd.entity(e).insert(Loading);
@@ -59,6 +62,7 @@ fn notify_clients(
 fn create_arena(
     mut cmd: Commands,
     arena_dimensions: Res<ArenaDimensions>,
+    arena_config: Res<ArenaConfig>,
     players: Query<Entity, With<Player>>,
 ) {
     let clients_count = players.iter().count() as u32;
@@ -66,7 +70,10 @@ fn create_arena(
     let spawn_points = SpawnPointsBuilder::new()
         .with_circle_points(clients_count, 2.0)
         .build();
-    let arena = ArenaBuilder::new().with_spawn_points(spawn_points).build();
+    let arena = ArenaBuilder::new()
+        .with_rounds(arena_config.total_rounds)
+        .with_spawn_points(spawn_points)
+        .build();
 
     let height = 2.0;
     let collider = ColliderBundle {
@@ -75,7 +82,7 @@ fn create_arena(
         shape: ColliderShape::cuboid(arena_dimensions.radius, height, arena_dimensions.radius),
         ..Default::default()
     };
-    cmd.spawn_bundle(collider);
+    cmd.spawn_bundle(collider).insert(ArenaFloor);
 
     cmd.insert_resource(arena);
 }
@@ -108,7 +115,7 @@ fn handle_loading_events(
 }
 
 fn check_players_loading(
-    clients: Query<Option<&Loading>, With<Client>>,
+    clients: Query<Option<&Loading>, (With<Client>, With<Player>)>,
     mut state: ResMut<State<ServerState>>,
 ) {
     let all_loaded = clients.iter().all(|loading| loading.is_none());
@@ -1,5 +1,6 @@
 use super::{
-    network::{Host, IdFactory, ServerPacket},
+    bots::BotBrain,
+    network::{Host, IdFactory, NetworkConfig, ServerPacket},
     states::ServerState,
 };
 use bevy::{prelude::*, utils::HashMap};
@@ -8,14 +9,18 @@ use wizardwars_shared::{
     events::ClientEvent,
     messages::{
         client_messages::LobbyClientMessage,
-        server_messages::{LobbyServerMessage, RejectReason, ServerMessage},
+        server_messages::{LobbyServerMessage, ReconnectToken, RejectReason, ServerMessage},
     },
     network::Pack,
-    resources::MAX_PLAYERS,
+    version::GameVersion,
 };
 
 pub type LobbyEvent = ClientEvent<LobbyClientMessage>;
 
+pub struct LobbyConfig {
+    pub max_players: usize,
+}
+
 #[derive(PartialEq)]
 pub struct LobbyReadyState(ReadyState);
 
@@ -49,20 +54,60 @@ fn teardown_lobby(mut cmd: Commands) {
     cmd.remove_resource::<LobbyReadyState>();
 }
 
+/// Rejects clients built with other messages or game data, they cannot talk to this server.
+/// The connection is closed once the rejection went out.
+pub fn check_version(
+    client: Client,
+    client_version: &GameVersion,
+    version: &GameVersion,
+    packets: &mut EventWriter<ServerPacket>,
+) -> bool {
+    if client_version == version {
+        return true;
+    }
+
+    warn!(
+        "Client version mismatch: {}, expected {}",
+        client_version, version
+    );
+    packets.send(Pack::single(
+        LobbyServerMessage::Reject {
+            reason: RejectReason::VersionMismatch {
+                server_version: *version,
+            },
+            disconnect: true,
+        },
+        client,
+    ));
+
+    false
+}
+
+#[allow(clippy::too_many_arguments)]
 fn handle_client_joined(
     mut cmd: Commands,
     mut lobby_evets: EventReader<LobbyEvent>,
     mut host: ResMut<Host>,
     mut id_factory: ResMut<IdFactory>,
     mut packets: EventWriter<ServerPacket>,
+    config: Res<LobbyConfig>,
+    network_config: Res<NetworkConfig>,
+    version: Res<GameVersion>,
     clients: Query<(&Uuid, &Name), With<Client>>,
     players: Query<&Player>,
 ) {
     let mut players_count = players.iter().count();
     for event in lobby_evets.iter() {
         let client = *event.client();
-        if let LobbyClientMessage::Join(name) = event.event() {
-            if players_count >= MAX_PLAYERS {
+        if let LobbyClientMessage::Join {
+            version: client_version,
+            name,
+        } = event.event()
+        {
+            if !check_version(client, client_version, &version, &mut packets) {
+                continue;
+            }
+            if players_count >= config.max_players {
                 warn!("Max players reached");
                 packets.send(Pack::single(
                     LobbyServerMessage::Reject {
@@ -82,16 +127,22 @@ fn handle_client_joined(
             }
 
             let client_name = Name::new(name.clone());
+            let reconnect_token = ReconnectToken(rand::random());
 
             cmd.spawn()
                 .insert(client)
                 .insert(Player)
                 .insert(client_name.clone())
                 .insert(ReadyState::NotReady)
+                .insert(reconnect_token)
                 .insert(network_id);
 
             packets.send(Pack::single(
-                LobbyServerMessage::Welcome(network_id),
+                LobbyServerMessage::Welcome {
+                    id: network_id,
+                    tick_rate: network_config.tick_rate,
+                    reconnect_token,
+                },
                 client,
             ));
             packets.send(Pack::single(
@@ -122,14 +173,15 @@ fn handle_create_bot(
     host: Res<Host>,
     mut id_factory: ResMut<IdFactory>,
     mut packets: EventWriter<ServerPacket>,
+    config: Res<LobbyConfig>,
     clients: Query<(&Uuid, &Client)>,
     players: Query<&Player>,
 ) {
     let mut players_count = players.iter().count();
     let clients_map = clients.iter().collect::<HashMap<_, _>>();
     for event in lobby_evets.iter() {
-        if let LobbyClientMessage::AddBot = event.event() {
-            if players_count >= MAX_PLAYERS {
+        if let &LobbyClientMessage::AddBot(difficulty) = event.event() {
+            if players_count >= config.max_players {
                 warn!("Cannot add a bot, lobby is full");
                 if let Some(host_client) = host.0.and_then(|id| clients_map.get(&id)) {
                     packets.send(Pack::single(
@@ -150,6 +202,7 @@ fn handle_create_bot(
             cmd.spawn()
                 .insert(Player)
                 .insert(Bot)
+                .insert(BotBrain::new(difficulty))
                 .insert(name.clone())
                 .insert(ReadyState::Ready)
                 .insert(network_id);
@@ -164,7 +217,7 @@ fn handle_create_bot(
 fn handle_client_ready_events(
     mut cmd: Commands,
     mut lobby_evets: EventReader<LobbyEvent>,
-    clients: Query<(Entity, &Client)>,
+    clients: Query<(Entity, &Client), With<Player>>,
 ) {
     let clients_map = clients
         .iter()
@@ -0,0 +1,256 @@
+use crate::{
+    battle::BattleState, knockback::KnockbackEvent, network::ServerPacket, spells::Casting,
+};
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use wizardwars_shared::{
+    components::{damage::Attack, Client, Dead, Health, Position, StatusEffects, Uuid, Waypoint},
+    events::{ClientEvent, DamageEvent},
+    messages::{
+        client_messages::ActionMessage,
+        server_messages::{RejectReason, ServerMessage},
+    },
+    resources::CharacterDimensions,
+    validation::non_negative,
+};
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct MeleeConfig {
+    pub damage: u32,
+    pub knockback: f32,
+    /// How far past touching the target a hit still lands.
+    pub range: f32,
+    /// Seconds between starting a swing and the hit.
+    pub wind_up: f32,
+    /// Seconds after a hit before the next swing can start.
+    pub cooldown: f32,
+}
+
+impl Default for MeleeConfig {
+    fn default() -> Self {
+        Self {
+            damage: 1,
+            knockback: 0.8,
+            range: 0.5,
+            wind_up: 0.3,
+            cooldown: 1.0,
+        }
+    }
+}
+
+impl MeleeConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        non_negative("melee.knockback", self.knockback)?;
+        non_negative("melee.range", self.range)?;
+        non_negative("melee.wind_up", self.wind_up)?;
+        non_negative("melee.cooldown", self.cooldown)
+    }
+}
+
+/// Basic attack against another player. The attacker walks up to the target, winds up and hits
+/// again and again until given another order.
+pub struct Attacking {
+    target: Entity,
+    wind_up: Option<Timer>,
+}
+
+impl Attacking {
+    pub fn new(target: Entity) -> Self {
+        Self {
+            target,
+            wind_up: None,
+        }
+    }
+}
+
+pub struct AttackCooldown(Timer);
+
+pub struct MeleePlugin;
+
+impl Plugin for MeleePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_update(BattleState::Battle)
+                .with_system(handle_attack_events_system.system())
+                .with_system(attack_system.system())
+                .with_system(tick_attack_cooldowns_system.system()),
+        );
+    }
+}
+
+/// Both characters are capsules of the same size, so they touch at twice the radius.
+pub fn in_range(
+    attacker: Vec3,
+    target: Vec3,
+    dimensions: &CharacterDimensions,
+    range: f32,
+) -> bool {
+    let distance = Vec3::new(target.x - attacker.x, 0.0, target.z - attacker.z).length();
+    distance <= dimensions.width() + range
+}
+
+fn handle_attack_events_system(
+    mut cmd: Commands,
+    mut events: EventReader<ClientEvent<ActionMessage>>,
+    mut packets: EventWriter<ServerPacket>,
+    attackers: Query<(Entity, &Client), (With<Attack>, Without<Dead>)>,
+    targets: Query<(Entity, &Uuid), (With<Health>, Without<Dead>)>,
+) {
+    let attackers = attackers
+        .iter()
+        .map(|(entity, client)| (*client, entity))
+        .collect::<HashMap<_, _>>();
+
+    for event in events.iter() {
+        if let ActionMessage::Attack { target } = event.event() {
+            let client = *event.client();
+            let attacker = match attackers.get(&client) {
+                Some(&attacker) => attacker,
+                None => continue,
+            };
+            let target_entity = targets
+                .iter()
+                .find(|(entity, id)| *id == target && *entity != attacker)
+                .map(|(entity, _)| entity);
+
+            match target_entity {
+                Some(target) => {
+                    cmd.entity(attacker)
+                        .remove::<Casting>()
+                        .insert(Attacking::new(target));
+                }
+                None => packets.send(ServerPacket::single(
+                    ServerMessage::ActionRejected(RejectReason::InvalidTarget(*target)),
+                    client,
+                )),
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn attack_system(
+    mut cmd: Commands,
+    mut damages: EventWriter<DamageEvent>,
+    config: Res<MeleeConfig>,
+    dimensions: Res<CharacterDimensions>,
+    time: Res<Time>,
+    mut attackers: Query<
+        (
+            Entity,
+            &Position,
+            &Attack,
+            &mut Attacking,
+            Option<&AttackCooldown>,
+            Option<&StatusEffects>,
+        ),
+        Without<Dead>,
+    >,
+    targets: Query<&Position, (With<Health>, Without<Dead>)>,
+    mut knockbacks: EventWriter<KnockbackEvent>,
+) {
+    for (attacker, position, attack, mut attacking, cooldown, effects) in attackers.iter_mut() {
+        let target = match targets.get(attacking.target) {
+            Ok(target) => target.0,
+            Err(_) => {
+                cmd.entity(attacker)
+                    .remove::<Attacking>()
+                    .remove::<Waypoint>();
+                continue;
+            }
+        };
+        // A stun interrupts the swing, the attack goes on once it wears off.
+        if effects.map_or(false, |effects| effects.is_stunned()) {
+            attacking.wind_up = None;
+            continue;
+        }
+        let in_range = in_range(position.0, target, &dimensions, config.range);
+
+        match attacking.wind_up.as_mut() {
+            Some(wind_up) => {
+                if !wind_up.tick(time.delta()).finished() {
+                    continue;
+                }
+                attacking.wind_up = None;
+                // The target may have walked away during the swing.
+                if !in_range {
+                    continue;
+                }
+
+                damages.send(attack.damage(attacking.target, attacker));
+                knockbacks.send(KnockbackEvent {
+                    target: attacking.target,
+                    direction: target - position.0,
+                    strength: attack.knockback_force(),
+                    source: Some(attacker),
+                });
+                cmd.entity(attacker)
+                    .insert(AttackCooldown(Timer::from_seconds(config.cooldown, false)));
+            }
+            None if !in_range => {
+                cmd.entity(attacker).insert(Waypoint(target));
+            }
+            None => {
+                cmd.entity(attacker).remove::<Waypoint>();
+                if cooldown.is_none() {
+                    attacking.wind_up = Some(Timer::from_seconds(config.wind_up, false));
+                }
+            }
+        }
+    }
+}
+
+fn tick_attack_cooldowns_system(
+    mut cmd: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut AttackCooldown)>,
+) {
+    for (entity, mut cooldown) in query.iter_mut() {
+        if cooldown.0.tick(time.delta()).finished() {
+            cmd.entity(entity).remove::<AttackCooldown>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_is_measured_on_the_ground() {
+        let dimensions = CharacterDimensions::default();
+        let reach = dimensions.width() + 0.5;
+
+        assert!(in_range(
+            Vec3::ZERO,
+            Vec3::new(reach, 0.0, 0.0),
+            &dimensions,
+            0.5
+        ));
+        assert!(in_range(
+            Vec3::ZERO,
+            Vec3::new(0.0, 3.0, reach),
+            &dimensions,
+            0.5
+        ));
+        assert!(!in_range(
+            Vec3::ZERO,
+            Vec3::new(reach, 0.0, 0.1),
+            &dimensions,
+            0.5
+        ));
+    }
+
+    #[test]
+    fn reject_negative_values() {
+        assert!(MeleeConfig::default().validate().is_ok());
+        assert!(MeleeConfig {
+            wind_up: -1.0,
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+    }
+}
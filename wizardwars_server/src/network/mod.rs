@@ -1,16 +1,28 @@
+mod snapshot;
+
 use crate::loading::LoadCompleteEvent;
 use crate::lobby::LobbyEvent;
+use crate::reconnect::Disconnected;
+use crate::rooms::RoomEvent;
+use crate::shopping::ShopEvent;
 use crate::states::ServerState;
 use bevy::prelude::*;
 use bevy::utils::HashMap;
 use bevy_networking_turbulence::{NetworkEvent, NetworkResource, NetworkingPlugin};
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-use wizardwars_shared::components::{Client, Position, Uuid};
+use snapshot::{read_snapshot_acks_system, send_snapshots_system, AckedSnapshot};
+use std::net::SocketAddr;
+use wizardwars_shared::components::{Bot, Client, Position, Uuid};
 use wizardwars_shared::events::ClientEvent;
 use wizardwars_shared::messages::client_messages::{ActionMessage, ClientMessage, Verify};
-use wizardwars_shared::messages::server_messages::LobbyServerMessage;
-use wizardwars_shared::messages::{network_channels_setup, server_messages::ServerMessage};
-use wizardwars_shared::network::{Dest, Pack};
+use wizardwars_shared::messages::server_messages::{InputAck, LobbyServerMessage};
+use wizardwars_shared::messages::{
+    handshake::Handshake, network_channels_setup, server_messages::ServerMessage, ReplicationPlugin,
+};
+use wizardwars_shared::network::{Dest, NetworkRole, Pack, SnapshotHistory, Tick};
+use wizardwars_shared::version::GameVersion;
+
+/// Seconds between rejecting a client and closing its connection, so the reply can go out.
+const DISCONNECT_DELAY: f32 = 0.5;
 
 #[derive(Default)]
 pub struct IdFactory(u32);
@@ -24,6 +36,19 @@ impl IdFactory {
     }
 }
 
+pub struct NetworkConfig {
+    pub address: SocketAddr,
+    pub tick_rate: u32,
+    /// Seconds a disconnected player can take over their character again.
+    pub reconnect_grace_period: f32,
+}
+
+/// The last move of a player applied by the server and the tick it was applied at.
+pub struct LastInput {
+    pub sequence: u32,
+    pub tick: Tick,
+}
+
 #[derive(Default)]
 pub struct Host(pub Option<Uuid>);
 
@@ -35,6 +60,39 @@ impl Host {
     pub fn is_host(&self, id: &Uuid) -> bool {
         Some(*id) == self.0
     }
+
+    /// Hands the host over to one of `candidates` if the leaving player was the host and
+    /// returns the new host.
+    pub fn replace(
+        &mut self,
+        leaving: &Uuid,
+        mut candidates: impl Iterator<Item = Uuid>,
+    ) -> Option<Uuid> {
+        if !self.is_host(leaving) {
+            return None;
+        }
+        self.0 = candidates.find(|id| id != leaving);
+
+        self.0
+    }
+}
+
+/// Connections closed once the last messages sent on them had time to leave.
+#[derive(Default)]
+struct PendingDisconnects(Vec<(u32, Timer)>);
+
+impl PendingDisconnects {
+    fn push(&mut self, handle: u32) {
+        if self.0.iter().all(|&(pending, _)| pending != handle) {
+            self.0
+                .push((handle, Timer::from_seconds(DISCONNECT_DELAY, false)));
+        }
+    }
+}
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NetworkSystem {
+    AdvanceTick,
 }
 
 pub struct NetworkPlugin;
@@ -45,29 +103,46 @@ impl Plugin for NetworkPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.insert_resource(IdFactory::default())
             .insert_resource(Host::default())
+            .insert_resource(Tick::default())
+            .insert_resource(SnapshotHistory::default())
+            .insert_resource(NetworkRole::Server)
+            .insert_resource(PendingDisconnects::default())
             .add_event::<ServerPacket>()
             .add_plugin(NetworkingPlugin {
                 idle_timeout_ms: Some(3000),
                 auto_heartbeat_ms: Some(1000),
                 ..Default::default()
             })
+            .add_plugin(ReplicationPlugin)
             .add_system_set(
                 SystemSet::on_enter(ServerState::Init)
                     .with_system(network_channels_setup.system())
                     .with_system(server_setup_system.system()),
             )
+            .add_system_to_stage(
+                CoreStage::First,
+                advance_tick_system
+                    .system()
+                    .label(NetworkSystem::AdvanceTick),
+            )
             .add_system(handle_network_events_system.system())
+            .add_system(read_handshakes_system.system())
             .add_system(read_network_channels_system.system())
             .add_system(send_packets_system.system())
-            .add_system(broadcast_changes_system.system());
+            .add_system(read_snapshot_acks_system.system())
+            .add_system(send_input_acks_system.system())
+            .add_system(disconnect_clients_system.system())
+            .add_system_to_stage(CoreStage::Last, send_snapshots_system.system());
     }
 }
 
-pub fn server_setup_system(mut net: ResMut<NetworkResource>) {
-    let ip_address = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
-    let socket_address = SocketAddr::new(ip_address, 9001);
-    net.listen(socket_address, None, None);
-    info!("Listening...");
+pub fn server_setup_system(mut net: ResMut<NetworkResource>, config: Res<NetworkConfig>) {
+    net.listen(config.address, None, None);
+    info!("Listening on {}...", config.address);
+}
+
+fn advance_tick_system(mut tick: ResMut<Tick>) {
+    tick.0 = tick.0.wrapping_add(1);
 }
 
 fn handle_network_events_system(
@@ -75,12 +150,15 @@ fn handle_network_events_system(
     mut net: ResMut<NetworkResource>,
     mut network_event_reader: EventReader<NetworkEvent>,
     mut host: ResMut<Host>,
+    config: Res<NetworkConfig>,
+    state: Res<State<ServerState>>,
     clients: Query<(Entity, &Client, &Uuid)>,
 ) {
     let clients_map = clients
         .iter()
         .map(|(entity, client, id)| (client.0, (entity, id)))
         .collect::<HashMap<_, _>>();
+    let in_match = !matches!(state.current(), ServerState::Init | ServerState::Lobby);
     let mut disconnected = Vec::new();
 
     for event in network_event_reader.iter() {
@@ -93,9 +171,21 @@ fn handle_network_events_system(
             },
             NetworkEvent::Disconnected(handle) => {
                 info!("Disconnected handle: {:?}", &handle);
-                if let Some(&(entity, &id)) = clients_map.get(handle) {
-                    cmd.entity(entity).despawn();
-                    disconnected.push(id);
+                match clients_map.get(handle) {
+                    Some(&(entity, id)) if in_match => {
+                        info!("A bot takes over for {:?} until it reconnects", id);
+                        cmd.entity(entity)
+                            .remove::<Client>()
+                            .remove::<LastInput>()
+                            .remove::<AckedSnapshot>()
+                            .insert(Bot)
+                            .insert(Disconnected::new(config.reconnect_grace_period));
+                    }
+                    Some(&(entity, &id)) => {
+                        cmd.entity(entity).despawn();
+                        disconnected.push(id);
+                    }
+                    None => {}
                 }
             }
             _ => {}
@@ -104,28 +194,55 @@ fn handle_network_events_system(
     for id in disconnected.into_iter() {
         net.broadcast_message(ServerMessage::Despawn(id));
 
-        if host.is_host(&id) {
-            let new_host_id = clients_map.iter().find_map(|(_, (_, &client_id))| {
-                if client_id != id {
-                    Some(client_id)
-                } else {
-                    None
-                }
-            });
-            host.set_host(new_host_id);
+        let candidates = clients_map.values().map(|&(_, &id)| id);
+        if let Some(host_id) = host.replace(&id, candidates) {
+            net.broadcast_message(ServerMessage::Lobby(LobbyServerMessage::SetHost(host_id)));
+        }
+    }
+}
 
-            if let Some(host_id) = new_host_id {
-                net.broadcast_message(ServerMessage::Lobby(LobbyServerMessage::SetHost(host_id)));
+/// Tells clients running another version so before they get to send anything else, their
+/// other messages may not even be readable.
+fn read_handshakes_system(
+    mut net: ResMut<NetworkResource>,
+    mut disconnects: ResMut<PendingDisconnects>,
+    version: Res<GameVersion>,
+) {
+    let mut mismatched = Vec::new();
+    for (&handle, connection) in net.connections.iter_mut() {
+        let channels = connection.channels().unwrap();
+        while let Some(message) = channels.recv::<Handshake>() {
+            if let Handshake::Hello(client_version) = message {
+                if client_version != *version {
+                    warn!(
+                        "Client version mismatch: {}, expected {}",
+                        client_version, *version
+                    );
+                    mismatched.push(handle);
+                }
             }
         }
     }
+
+    for handle in mismatched {
+        let reply = Handshake::VersionMismatch {
+            server_version: *version,
+        };
+        if let Err(err) = net.send_message(handle, reply) {
+            warn!("Unable to answer the handshake of {}: {}", handle, err);
+        }
+        disconnects.push(handle);
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn read_network_channels_system(
     mut net: ResMut<NetworkResource>,
     mut action_events: EventWriter<ClientEvent<ActionMessage>>,
     mut lobby_events: EventWriter<LobbyEvent>,
+    mut room_events: EventWriter<RoomEvent>,
     mut loading_events: EventWriter<LoadCompleteEvent>,
+    mut shop_events: EventWriter<ShopEvent>,
     host: Res<Host>,
     query: Query<(&Client, &Uuid)>,
 ) {
@@ -141,8 +258,8 @@ fn read_network_channels_system(
         let client_id = client_map.get(&client);
         while let Some(message) = channels.recv::<ClientMessage>() {
             let is_host = client_id.map(|id| host.is_host(id)).unwrap_or(false);
-            if !message.verify(is_host) {
-                error!("Only host can use: {:?}", message);
+            if !message.verify(is_host, client_id.is_some()) {
+                error!("Not allowed to use: {:?}", message);
                 continue;
             }
 
@@ -150,10 +267,12 @@ fn read_network_channels_system(
                 ClientMessage::LobbyMessage(msg) => {
                     lobby_events.send(ClientEvent::new(client, msg))
                 }
+                ClientMessage::Room(msg) => room_events.send(ClientEvent::new(client, msg)),
                 ClientMessage::Action(msg) => action_events.send(ClientEvent::new(client, msg)),
                 ClientMessage::Loaded => {
                     loading_events.send(LoadCompleteEvent { client });
                 }
+                ClientMessage::Shop(msg) => shop_events.send(ClientEvent::new(client, msg)),
             }
         }
     }
@@ -161,6 +280,7 @@ fn read_network_channels_system(
 
 fn send_packets_system(
     mut net: ResMut<NetworkResource>,
+    mut disconnects: ResMut<PendingDisconnects>,
     mut events: EventReader<ServerPacket>,
     clients: Query<&Client>,
 ) {
@@ -169,6 +289,12 @@ fn send_packets_system(
             Dest::Single(client) => {
                 net.send_message(client.0, pack.msg.clone())
                     .expect("Unable to send message");
+                if let ServerMessage::Lobby(LobbyServerMessage::Reject {
+                    disconnect: true, ..
+                }) = pack.msg
+                {
+                    disconnects.push(client.0);
+                }
             }
             Dest::AllExcept(exclude_client) => {
                 for &client in clients.iter() {
@@ -183,12 +309,40 @@ fn send_packets_system(
     }
 }
 
-fn broadcast_changes_system(
+fn disconnect_clients_system(
+    mut net: ResMut<NetworkResource>,
+    mut disconnects: ResMut<PendingDisconnects>,
+    time: Res<Time>,
+) {
+    for (_, timer) in disconnects.0.iter_mut() {
+        timer.tick(time.delta());
+    }
+    let (due, waiting) = disconnects
+        .0
+        .drain(..)
+        .partition::<Vec<_>, _>(|(_, timer)| timer.finished());
+    disconnects.0 = waiting;
+
+    for (handle, _) in due {
+        info!("Disconnecting rejected client {}", handle);
+        net.disconnect(handle);
+    }
+}
+
+fn send_input_acks_system(
     mut net: ResMut<NetworkResource>,
-    changed_positions: Query<(&Uuid, &Position), Changed<Position>>,
+    tick: Res<Tick>,
+    config: Res<NetworkConfig>,
+    changed: Query<(&Client, &Position, &LastInput), Changed<Position>>,
 ) {
-    for (id, position) in changed_positions.iter() {
-        let _ = net.broadcast_message((*id, *position));
+    for (client, position, input) in changed.iter() {
+        let elapsed_ticks = tick.0.wrapping_sub(input.tick.0);
+        let ack = InputAck {
+            sequence: input.sequence,
+            elapsed: elapsed_ticks as f32 / config.tick_rate as f32,
+            position: position.0,
+        };
+        let _ = net.send_message(client.0, ack);
     }
 }
 
@@ -205,4 +359,32 @@ mod tests {
         let id2 = factory.generate();
         assert_eq!(id2, Uuid(1));
     }
+
+    #[test]
+    fn disconnects_are_scheduled_once() {
+        let mut disconnects = PendingDisconnects::default();
+        disconnects.push(3);
+        disconnects.push(3);
+        disconnects.push(4);
+
+        assert_eq!(disconnects.0.len(), 2);
+    }
+
+    #[test]
+    fn host_is_replaced_when_leaving() {
+        let mut host = Host(Some(Uuid(0)));
+        let players = vec![Uuid(0), Uuid(1), Uuid(2)];
+
+        assert_eq!(host.replace(&Uuid(2), players.iter().copied()), None);
+        assert!(host.is_host(&Uuid(0)));
+
+        assert_eq!(
+            host.replace(&Uuid(0), players.iter().copied()),
+            Some(Uuid(1))
+        );
+        assert!(host.is_host(&Uuid(1)));
+
+        assert_eq!(host.replace(&Uuid(1), std::iter::once(Uuid(1))), None);
+        assert_eq!(host.0, None);
+    }
 }
@@ -0,0 +1,73 @@
+use bevy::prelude::*;
+use bevy_networking_turbulence::NetworkResource;
+use std::collections::HashMap;
+use wizardwars_shared::{
+    components::{Client, Cooldowns, Mana, Position, Uuid},
+    network::{
+        EntitySnapshot, SnapshotAck, SnapshotHistory, Tick, WorldSnapshot,
+        MAX_SNAPSHOT_MESSAGE_SIZE,
+    },
+};
+
+/// Newest snapshot a client has acknowledged, deltas for it are encoded against that one.
+pub struct AckedSnapshot(pub Tick);
+
+pub fn read_snapshot_acks_system(
+    mut cmd: Commands,
+    mut net: ResMut<NetworkResource>,
+    clients: Query<(Entity, &Client, Option<&AckedSnapshot>)>,
+) {
+    let clients = clients
+        .iter()
+        .map(|(entity, client, acked)| (client.0, (entity, acked.map(|acked| acked.0))))
+        .collect::<HashMap<_, _>>();
+
+    for (handle, connection) in net.connections.iter_mut() {
+        let channels = connection.channels().unwrap();
+        let mut newest = None;
+        while let Some(SnapshotAck(tick)) = channels.recv::<SnapshotAck>() {
+            newest = newest.max(Some(tick));
+        }
+
+        if let (Some(tick), Some(&(entity, acked))) = (newest, clients.get(handle)) {
+            if acked.map_or(true, |acked| acked < tick) {
+                cmd.entity(entity).insert(AckedSnapshot(tick));
+            }
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn send_snapshots_system(
+    mut net: ResMut<NetworkResource>,
+    mut history: ResMut<SnapshotHistory>,
+    tick: Res<Tick>,
+    replicated: Query<(&Uuid, Option<&Position>, Option<&Mana>, Option<&Cooldowns>)>,
+    clients: Query<(&Client, Option<&AckedSnapshot>)>,
+) {
+    let mut snapshot = WorldSnapshot::new(*tick);
+    for (id, position, mana, cooldowns) in replicated.iter() {
+        let entity = EntitySnapshot {
+            position: position.copied(),
+            mana: mana.copied(),
+            cooldowns: cooldowns.cloned(),
+        };
+        snapshot.entities.insert(*id, entity);
+    }
+
+    for (client, acked) in clients.iter() {
+        let baseline = acked.and_then(|acked| history.get(acked.0));
+        let parts = snapshot
+            .delta_from(baseline)
+            .split(MAX_SNAPSHOT_MESSAGE_SIZE);
+        for part in parts {
+            match net.send_message(client.0, part) {
+                Ok(None) => {}
+                Ok(Some(_)) => warn!("Snapshot channel of client {} is full", client.0),
+                Err(err) => warn!("Unable to send snapshot to client {}: {}", client.0, err),
+            }
+        }
+    }
+
+    history.push(snapshot);
+}
@@ -0,0 +1,351 @@
+use crate::{
+    battle::{BattleState, BattleSystem},
+    knockback::KnockbackEvent,
+    network::ServerPacket,
+};
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use bevy_rapier3d::{
+    physics::{
+        IntoEntity, IntoHandle, QueryPipelineColliderComponentsQuery,
+        QueryPipelineColliderComponentsSet,
+    },
+    prelude::{
+        ColliderShape, InteractionGroups, Isometry, NarrowPhase, QueryPipeline, RigidBodyVelocity,
+    },
+};
+use std::{cmp::Ordering, f32::consts::PI};
+use wizardwars_shared::{
+    components::{
+        damage::{DamageKind, Projectile},
+        Dead, Health, LifeTime, Owner, Player, Position, StatusEffects, Uuid,
+    },
+    events::DamageEvent,
+    messages::server_messages::ServerMessage,
+    network::Pack,
+    spells::{Explosion, ProjectileBehaviour},
+};
+
+/// Turns the projectile towards the nearest enemy, in radians per second.
+pub struct Homing(pub f32);
+
+/// Targets the projectile still passes through, and the ones it already went through.
+pub struct Piercing {
+    remaining: u32,
+    hit: Vec<Entity>,
+}
+
+/// Explodes when the projectile is destroyed or runs out of time.
+pub struct Explosive(pub Explosion);
+
+struct ExplodeEvent {
+    position: Vec3,
+    owner: Entity,
+    explosion: Explosion,
+}
+
+pub struct ProjectilesPlugin;
+
+impl Plugin for ProjectilesPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<ExplodeEvent>().add_system_set(
+            SystemSet::on_update(BattleState::Battle)
+                .with_system(homing_system.system())
+                .with_system(
+                    projectile_collision_system
+                        .system()
+                        .before(BattleSystem::TrackLifetime),
+                )
+                .with_system(
+                    explode_on_expiry_system
+                        .system()
+                        .after(BattleSystem::TrackLifetime),
+                )
+                .with_system(explode_system.system()),
+        );
+    }
+}
+
+/// Adds the components for the behaviours of a freshly spawned projectile.
+pub fn insert_behaviour(
+    projectile: &mut EntityCommands,
+    behaviour: &ProjectileBehaviour,
+    damage_multiplier: f32,
+) {
+    if behaviour.homing > 0.0 {
+        projectile.insert(Homing(behaviour.homing));
+    }
+    if behaviour.pierce > 0 {
+        projectile.insert(Piercing {
+            remaining: behaviour.pierce,
+            hit: Vec::new(),
+        });
+    }
+    if let Some(explosion) = &behaviour.explosion {
+        projectile.insert(Explosive(Explosion {
+            damage: (explosion.damage as f32 * damage_multiplier).round() as u32,
+            ..explosion.clone()
+        }));
+    }
+}
+
+/// Rotates `velocity` on the ground plane towards `direction` by at most `max_angle` radians,
+/// keeping its speed.
+fn turn_towards(velocity: Vec3, direction: Vec3, max_angle: f32) -> Vec3 {
+    let current = velocity.z.atan2(velocity.x);
+    let desired = direction.z.atan2(direction.x);
+    let difference = (desired - current + PI).rem_euclid(2.0 * PI) - PI;
+    let angle = current + difference.max(-max_angle).min(max_angle);
+    let speed = Vec3::new(velocity.x, 0.0, velocity.z).length();
+
+    Vec3::new(angle.cos() * speed, velocity.y, angle.sin() * speed)
+}
+
+/// Share of the explosion a target `distance` away from its centre receives.
+fn falloff(distance: f32, radius: f32) -> f32 {
+    (1.0 - distance / radius).max(0.0)
+}
+
+fn homing_system(
+    time: Res<Time>,
+    mut projectiles: Query<(&Homing, &Owner, &Position, &mut RigidBodyVelocity)>,
+    enemies: Query<(Entity, &Position), (With<Player>, Without<Dead>)>,
+) {
+    for (homing, owner, position, mut velocity) in projectiles.iter_mut() {
+        let nearest = enemies
+            .iter()
+            .filter(|&(enemy, _)| enemy != owner.entity())
+            .map(|(_, enemy)| enemy.0 - position.0)
+            .min_by(|a, b| {
+                a.length_squared()
+                    .partial_cmp(&b.length_squared())
+                    .unwrap_or(Ordering::Equal)
+            });
+
+        if let Some(direction) = nearest {
+            let linvel = turn_towards(
+                velocity.linvel.into(),
+                direction,
+                homing.0 * time.delta_seconds(),
+            );
+            velocity.linvel = linvel.into();
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn projectile_collision_system(
+    mut cmd: Commands,
+    mut packets: EventWriter<ServerPacket>,
+    mut damages: EventWriter<DamageEvent>,
+    mut knockbacks: EventWriter<KnockbackEvent>,
+    mut explosions: EventWriter<ExplodeEvent>,
+    mut projectiles: Query<(
+        Entity,
+        &Projectile,
+        &Owner,
+        &Uuid,
+        &Position,
+        &RigidBodyVelocity,
+        Option<&mut Piercing>,
+        Option<&Explosive>,
+        Option<&mut LifeTime>,
+    )>,
+    targets: Query<(&Position, &RigidBodyVelocity), (With<Health>, Without<Projectile>)>,
+    mut effects: Query<&mut StatusEffects>,
+    narrow_phase: Res<NarrowPhase>,
+) {
+    for (
+        projectile_entity,
+        projectile,
+        projectile_owner,
+        projectile_id,
+        projectile_position,
+        projectile_velocity,
+        mut piercing,
+        explosive,
+        lifetime,
+    ) in projectiles.iter_mut()
+    {
+        let mut destroyed = false;
+        for (collider1, collider2, intersecting) in
+            narrow_phase.intersections_with(projectile_entity.handle())
+        {
+            let target_entity = {
+                let e1 = collider1.entity();
+                let e2 = collider2.entity();
+
+                if e1 == projectile_entity {
+                    e2
+                } else {
+                    e1
+                }
+            };
+
+            if !intersecting || projectile_owner.entity() == target_entity {
+                continue;
+            }
+
+            if let Ok((target_position, target_velocity)) = targets.get(target_entity) {
+                if piercing
+                    .as_ref()
+                    .map_or(false, |piercing| piercing.hit.contains(&target_entity))
+                {
+                    continue;
+                }
+
+                let mut direction = target_position.0 - projectile_position.0;
+                direction.y = 0.0;
+
+                let mut diff_velocity: Vec3 =
+                    (projectile_velocity.linvel - target_velocity.linvel).into();
+                diff_velocity.y = 0.0;
+
+                // Glancing hits push less than head-on ones.
+                let dot = direction
+                    .normalize_or_zero()
+                    .dot(diff_velocity.normalize_or_zero());
+
+                knockbacks.send(KnockbackEvent {
+                    target: target_entity,
+                    direction,
+                    strength: projectile.attack.knockback_force() * dot.max(0.0),
+                    source: Some(projectile_owner.entity()),
+                });
+                damages.send(
+                    projectile
+                        .attack
+                        .damage(target_entity, projectile_owner.entity()),
+                );
+                if let Ok(mut effects) = effects.get_mut(target_entity) {
+                    for effect in projectile.effects.iter() {
+                        effects.add(
+                            effect.effect,
+                            effect.duration,
+                            Some(projectile_owner.entity()),
+                        );
+                    }
+                }
+
+                if let Some(piercing) = piercing.as_deref_mut() {
+                    if piercing.remaining > 0 {
+                        piercing.remaining -= 1;
+                        piercing.hit.push(target_entity);
+                        continue;
+                    }
+                }
+            }
+
+            destroyed = true;
+            break;
+        }
+
+        if destroyed {
+            // Keeps the lifetime from running out this frame too, the projectile would
+            // explode and be despawned a second time.
+            if let Some(mut lifetime) = lifetime {
+                lifetime.timer.pause();
+            }
+            if let Some(explosive) = explosive {
+                explosions.send(ExplodeEvent {
+                    position: projectile_position.0,
+                    owner: projectile_owner.entity(),
+                    explosion: explosive.0.clone(),
+                });
+            }
+            cmd.entity(projectile_entity).despawn();
+            packets.send(Pack::all(ServerMessage::Despawn(*projectile_id)));
+        }
+    }
+}
+
+fn explode_on_expiry_system(
+    mut explosions: EventWriter<ExplodeEvent>,
+    projectiles: Query<(&LifeTime, &Position, &Owner, &Explosive)>,
+) {
+    for (lifetime, position, owner, explosive) in projectiles.iter() {
+        if lifetime.timer.just_finished() {
+            explosions.send(ExplodeEvent {
+                position: position.0,
+                owner: owner.entity(),
+                explosion: explosive.0.clone(),
+            });
+        }
+    }
+}
+
+/// Damages and pushes away everything around the explosion except the caster.
+fn explode_system(
+    mut events: EventReader<ExplodeEvent>,
+    mut damages: EventWriter<DamageEvent>,
+    mut knockbacks: EventWriter<KnockbackEvent>,
+    query_pipeline: Res<QueryPipeline>,
+    colliders: QueryPipelineColliderComponentsQuery,
+    targets: Query<&Position, (With<Health>, Without<Dead>)>,
+) {
+    let collider_set = QueryPipelineColliderComponentsSet(&colliders);
+
+    for event in events.iter() {
+        let explosion = &event.explosion;
+        let shape = ColliderShape::ball(explosion.radius);
+        let center = Isometry::translation(event.position.x, event.position.y, event.position.z);
+
+        query_pipeline.intersections_with_shape(
+            &collider_set,
+            &center,
+            &*shape,
+            InteractionGroups::all(),
+            None,
+            |handle| {
+                let target = handle.entity();
+                if target == event.owner {
+                    return true;
+                }
+                if let Ok(position) = targets.get(target) {
+                    let mut offset = position.0 - event.position;
+                    offset.y = 0.0;
+                    let share = falloff(offset.length(), explosion.radius);
+
+                    let damage = (explosion.damage as f32 * share).round() as u32;
+                    if damage > 0 {
+                        damages.send(
+                            DamageEvent::new(target, damage, DamageKind::Spell)
+                                .with_source(event.owner),
+                        );
+                    }
+                    knockbacks.send(KnockbackEvent {
+                        target,
+                        direction: offset,
+                        strength: explosion.knockback * share,
+                        source: Some(event.owner),
+                    });
+                }
+
+                true
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn homing_turns_at_most_the_turn_rate() {
+        let velocity = Vec3::new(2.0, 0.0, 0.0);
+
+        let turned = turn_towards(velocity, Vec3::new(0.0, 0.0, 5.0), 0.5);
+        assert!((turned.length() - 2.0).abs() < 1e-5);
+        assert!((turned.z.atan2(turned.x) - 0.5).abs() < 1e-5);
+
+        let turned = turn_towards(velocity, Vec3::new(1.0, 0.0, -0.1), 0.5);
+        assert!((turned.z.atan2(turned.x) - (-0.1f32).atan2(1.0)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn explosion_falls_off_towards_the_edge() {
+        assert!((falloff(0.0, 2.0) - 1.0).abs() < f32::EPSILON);
+        assert!((falloff(1.0, 2.0) - 0.5).abs() < f32::EPSILON);
+        assert!(falloff(3.0, 2.0).abs() < f32::EPSILON);
+    }
+}
@@ -0,0 +1,264 @@
+use crate::{
+    arena::Arena,
+    battle::PlayerColor,
+    bots::BotBrain,
+    lobby::{check_version, LobbyEvent},
+    network::{Host, NetworkConfig, ServerPacket},
+    safe_zone::SafeZone,
+    shopping::ShoppingTimer,
+    states::ServerState,
+};
+use bevy::prelude::*;
+use wizardwars_shared::{
+    components::{
+        damage::Projectile, Bot, Client, Gold, Inventory, Player, Position, StatusEffects, Uuid,
+        Waypoint,
+    },
+    events::{InsertPlayerEvent, SpawnEvent},
+    messages::{
+        client_messages::LobbyClientMessage,
+        server_messages::{
+            LobbyServerMessage, ReconnectToken, RejectReason, ServerMessage, ShoppingServerMessage,
+            StatusEffectMessage, TimerInfo,
+        },
+    },
+    network::{Pack, ResyncClient},
+    version::GameVersion,
+};
+
+/// A player whose connection dropped during a match. A bot plays in their place until the
+/// match is over, the client can take over again until the timer finishes.
+pub struct Disconnected(Timer);
+
+impl Disconnected {
+    pub fn new(grace_period: f32) -> Self {
+        Self(Timer::from_seconds(grace_period, false))
+    }
+
+    pub fn can_reconnect(&self) -> bool {
+        !self.0.finished()
+    }
+}
+
+/// A client joined a running match, either taking over its player again or as a spectator.
+pub struct CatchUpEvent {
+    pub client: Client,
+    pub player: Option<Entity>,
+}
+
+pub struct ReconnectPlugin;
+
+impl Plugin for ReconnectPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<CatchUpEvent>()
+            .add_system(handle_reconnect_events_system.system())
+            .add_system(catch_up_system.system())
+            .add_system(tick_disconnected_system.system())
+            .add_system_set(
+                SystemSet::on_enter(ServerState::Lobby)
+                    .with_system(remove_disconnected_system.system()),
+            );
+    }
+}
+
+fn handle_reconnect_events_system(
+    mut cmd: Commands,
+    mut lobby_events: EventReader<LobbyEvent>,
+    mut packets: EventWriter<ServerPacket>,
+    mut catch_ups: EventWriter<CatchUpEvent>,
+    version: Res<GameVersion>,
+    disconnected: Query<(Entity, &ReconnectToken, &Disconnected)>,
+) {
+    for event in lobby_events.iter() {
+        let client = *event.client();
+        if let LobbyClientMessage::Reconnect {
+            version: client_version,
+            token,
+        } = event.event()
+        {
+            if !check_version(client, client_version, &version, &mut packets) {
+                continue;
+            }
+
+            let player = disconnected
+                .iter()
+                .find(|(_, player_token, disconnected)| {
+                    *player_token == token && disconnected.can_reconnect()
+                })
+                .map(|(entity, ..)| entity);
+            let player = match player {
+                Some(player) => player,
+                None => {
+                    warn!("Unknown reconnect token from {:?}", client);
+                    packets.send(Pack::single(
+                        LobbyServerMessage::Reject {
+                            reason: RejectReason::InvalidReconnectToken,
+                            disconnect: false,
+                        },
+                        client,
+                    ));
+                    continue;
+                }
+            };
+
+            cmd.entity(player)
+                .remove::<Disconnected>()
+                .remove::<Bot>()
+                .remove::<BotBrain>()
+                .remove::<Waypoint>()
+                .insert(client);
+            catch_ups.send(CatchUpEvent {
+                client,
+                player: Some(player),
+            });
+        }
+    }
+}
+
+/// Sends a client everything it would have learned had it been connected all along.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn catch_up_system(
+    mut events: EventReader<CatchUpEvent>,
+    mut packets: EventWriter<ServerPacket>,
+    mut resyncs: EventWriter<ResyncClient>,
+    config: Res<NetworkConfig>,
+    host: Res<Host>,
+    state: Res<State<ServerState>>,
+    arena: Option<Res<Arena>>,
+    shopping_timer: Option<Res<ShoppingTimer>>,
+    safe_zone: Option<Res<SafeZone>>,
+    players: Query<
+        (
+            Entity,
+            &Uuid,
+            &Name,
+            Option<&ReconnectToken>,
+            Option<&Position>,
+            Option<&PlayerColor>,
+            Option<&Gold>,
+            Option<&Inventory>,
+        ),
+        With<Player>,
+    >,
+    projectiles: Query<(&Uuid, &Projectile)>,
+    effects: Query<(&Uuid, &StatusEffects)>,
+) {
+    for event in events.iter() {
+        let client = event.client;
+        let player = event.player.and_then(|player| players.get(player).ok());
+        let send = |message: ServerMessage| ServerPacket::single(message, client);
+
+        if let Some((_, &id, name, token, ..)) = player {
+            info!("{} reconnected as {:?}", name.as_str(), id);
+            if let Some(&reconnect_token) = token {
+                packets.send(send(
+                    LobbyServerMessage::Welcome {
+                        id,
+                        tick_rate: config.tick_rate,
+                        reconnect_token,
+                    }
+                    .into(),
+                ));
+            }
+        }
+        if let Some(host) = host.0 {
+            packets.send(send(LobbyServerMessage::SetHost(host).into()));
+        }
+        for (entity, &other, name, ..) in players.iter() {
+            if Some(entity) != event.player {
+                let joined = LobbyServerMessage::PlayerJoined(other, name.as_str().to_owned());
+                packets.send(send(joined.into()));
+            }
+        }
+        if let Some(arena) = &arena {
+            packets.send(send(ServerMessage::Round {
+                current: arena.current_round(),
+                total: arena.total_rounds(),
+            }));
+        }
+
+        match state.current() {
+            ServerState::WaitLoading => {
+                packets.send(send(LobbyServerMessage::StartLoading.into()));
+            }
+            ServerState::Shopping => {
+                if let Some(timer) = &shopping_timer {
+                    let timer_info = TimerInfo {
+                        duration: timer.timer.duration(),
+                        elapsed: timer.timer.elapsed(),
+                    };
+                    packets.send(send(ShoppingServerMessage::Timer(timer_info).into()));
+                }
+                if let Some((.., Some(gold), Some(inventory))) = player {
+                    packets.send(send(ShoppingServerMessage::Gold(gold.0).into()));
+                    packets.send(send(
+                        ShoppingServerMessage::Inventory(inventory.clone()).into(),
+                    ));
+                }
+            }
+            ServerState::Battle => {
+                for (entity, &other, _, _, position, color, ..) in players.iter() {
+                    if let (Some(position), Some(color)) = (position, color) {
+                        packets.send(send(ServerMessage::InsertPlayer(InsertPlayerEvent {
+                            id: other,
+                            position: position.0,
+                            is_local: Some(entity) == event.player,
+                            color: color.0,
+                        })));
+                    }
+                }
+                for (&id, active) in effects.iter() {
+                    for kind in active.kinds() {
+                        packets.send(send(
+                            StatusEffectMessage::Started {
+                                id,
+                                kind,
+                                duration: active.remaining(kind),
+                            }
+                            .into(),
+                        ));
+                    }
+                }
+                for (&id, projectile) in projectiles.iter() {
+                    packets.send(send(ServerMessage::Spawn(SpawnEvent::Projectile {
+                        id,
+                        spell: projectile.spell.clone(),
+                    })));
+                }
+                if let Some(safe_zone) = &safe_zone {
+                    packets.send(send(ServerMessage::SafeZoneRadius(safe_zone.radius())));
+                }
+            }
+            _ => {}
+        }
+
+        resyncs.send(ResyncClient(client));
+    }
+}
+
+fn tick_disconnected_system(time: Res<Time>, mut query: Query<(&Uuid, &mut Disconnected)>) {
+    for (id, mut disconnected) in query.iter_mut() {
+        if disconnected.0.tick(time.delta()).just_finished() {
+            info!("{:?} can no longer reconnect to this match", id);
+        }
+    }
+}
+
+/// Nobody waits for missing players in the lobby.
+fn remove_disconnected_system(
+    mut cmd: Commands,
+    mut packets: EventWriter<ServerPacket>,
+    mut host: ResMut<Host>,
+    disconnected: Query<(Entity, &Uuid), With<Disconnected>>,
+    clients: Query<&Uuid, With<Client>>,
+) {
+    for (entity, &id) in disconnected.iter() {
+        info!("Removing disconnected player {:?}", id);
+        cmd.entity(entity).despawn();
+        packets.send(Pack::all(ServerMessage::Despawn(id)));
+
+        if let Some(host_id) = host.replace(&id, clients.iter().copied()) {
+            packets.send(Pack::all(LobbyServerMessage::SetHost(host_id)));
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{
+    network::{NetworkConfig, NetworkSystem, ServerPacket},
+    states::ServerState,
+};
+use bevy::prelude::*;
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use wizardwars_shared::{
+    components::{Player, Uuid},
+    messages::server_messages::LobbyServerMessage,
+    network::{Pack, ReplicationLog, SnapshotHistory, Tick, WorldSnapshot},
+    replay::{ReplayFrame, ReplayHeader, ReplayRecord, ReplayWriter, REPLAY_EXTENSION},
+    version::GameVersion,
+};
+
+/// Records every match of the room into a replay file in `directory`.
+pub struct ReplayPlugin {
+    pub directory: PathBuf,
+}
+
+struct ReplayDirectory(PathBuf);
+
+/// The match currently being recorded.
+struct Recorder {
+    writer: ReplayWriter<BufWriter<File>>,
+    path: PathBuf,
+    /// Sent since the last frame was written.
+    records: Vec<ReplayRecord>,
+    /// Snapshots are recorded as deltas against this one.
+    last_snapshot: Option<WorldSnapshot>,
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(ReplayDirectory(self.directory.clone()))
+            .add_system_set(
+                SystemSet::on_enter(ServerState::WaitLoading)
+                    .with_system(start_recording_system.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(ServerState::Lobby).with_system(stop_recording_system.system()),
+            )
+            // Runs before the tick advances, so everything sent during a tick is stamped with it.
+            .add_system_to_stage(
+                CoreStage::First,
+                record_frame_system
+                    .system()
+                    .before(NetworkSystem::AdvanceTick),
+            );
+    }
+}
+
+fn start_recording_system(
+    mut cmd: Commands,
+    directory: Res<ReplayDirectory>,
+    config: Res<NetworkConfig>,
+    version: Res<GameVersion>,
+    players: Query<(&Uuid, &Name), With<Player>>,
+) {
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    let path = directory.0.join(format!(
+        "{}-{}.{}",
+        started,
+        config.address.port(),
+        REPLAY_EXTENSION
+    ));
+    if let Err(err) = fs::create_dir_all(&directory.0) {
+        warn!("Cannot create {}: {}", directory.0.display(), err);
+        return;
+    }
+    let header = ReplayHeader {
+        version: *version,
+        tick_rate: config.tick_rate,
+    };
+    let writer = match ReplayWriter::create(&path, &header) {
+        Ok(writer) => writer,
+        Err(err) => {
+            warn!("The match is not recorded: {}", err);
+            return;
+        }
+    };
+
+    // Players joined before the recording started, the replay needs to know them.
+    let records = players
+        .iter()
+        .map(|(&id, name)| {
+            let joined = LobbyServerMessage::PlayerJoined(id, name.as_str().to_owned());
+            ReplayRecord::Packet(Pack::all(joined))
+        })
+        .collect();
+
+    info!("Recording the match to {}", path.display());
+    cmd.insert_resource(Recorder {
+        writer,
+        path,
+        records,
+        last_snapshot: None,
+    });
+    cmd.insert_resource(ReplicationLog::default());
+}
+
+fn record_frame_system(
+    mut packets: EventReader<ServerPacket>,
+    recorder: Option<ResMut<Recorder>>,
+    log: Option<ResMut<ReplicationLog>>,
+    history: Res<SnapshotHistory>,
+    tick: Res<Tick>,
+) {
+    let packets = packets.iter().cloned().collect::<Vec<_>>();
+    let mut recorder = match recorder {
+        Some(recorder) => recorder,
+        None => return,
+    };
+
+    recorder
+        .records
+        .extend(packets.into_iter().map(ReplayRecord::Packet));
+    if let Some(snapshot) = history.latest().filter(|snapshot| snapshot.tick == *tick) {
+        let delta = snapshot.delta_from(recorder.last_snapshot.as_ref());
+        if delta.baseline.is_none() || !delta.changed.is_empty() || !delta.removed.is_empty() {
+            recorder.records.push(ReplayRecord::Snapshot(delta));
+            recorder.last_snapshot = Some(snapshot.clone());
+        }
+    }
+    if let Some(mut log) = log {
+        let updates = log.drain();
+        recorder
+            .records
+            .extend(updates.into_iter().map(ReplayRecord::Component));
+    }
+
+    if recorder.records.is_empty() {
+        return;
+    }
+    let frame = ReplayFrame {
+        tick: *tick,
+        records: std::mem::take(&mut recorder.records),
+    };
+    if let Err(err) = recorder.writer.write(&frame) {
+        warn!("Cannot record to {}: {}", recorder.path.display(), err);
+    }
+}
+
+fn stop_recording_system(mut cmd: Commands, recorder: Option<ResMut<Recorder>>) {
+    let mut recorder = match recorder {
+        Some(recorder) => recorder,
+        None => return,
+    };
+
+    match recorder.writer.flush() {
+        Ok(()) => info!("Match recorded to {}", recorder.path.display()),
+        Err(err) => warn!("Cannot record to {}: {}", recorder.path.display(), err),
+    }
+    cmd.remove_resource::<Recorder>();
+    cmd.remove_resource::<ReplicationLog>();
+}
@@ -1,28 +1,149 @@
 use bevy::{app::AppExit, prelude::*};
 use bevy_networking_turbulence::NetworkResource;
+use bevy_rapier3d::physics::{ColliderBundle, RigidBodyBundle, RigidBodyPositionSync};
+use wizardwars_shared::{
+    components::{
+        damage::Projectile, Bot, Cooldowns, Dead, Gold, Health, Inventory, Mana, Player,
+        ReadyState, Stats, Uuid, Waypoint, Winner,
+    },
+    messages::server_messages::{LobbyServerMessage, ServerMessage},
+};
 
-use crate::states::ServerState;
+use crate::{
+    arena::Arena,
+    battle::PlayerColor,
+    economy::RoundEarnings,
+    loading::ArenaFloor,
+    network::{LastInput, ServerPacket},
+    states::ServerState,
+    statistics::MatchStatistics,
+};
+
+/// Time the clients get to receive the results before the match is closed.
+const RESULT_DELAY: f32 = 1.0;
+
+pub struct ResultConfig {
+    pub exit_after_match: bool,
+}
 
 pub struct ResultPlugin;
 
+struct ResultTimer(Timer);
+
 impl Plugin for ResultPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.add_system_set(
-            SystemSet::on_enter(ServerState::ShowResult).with_system(
-                send_statistics
+            SystemSet::on_enter(ServerState::ShowResult).with_system(send_statistics.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(ServerState::ShowResult).with_system(
+                check_result_timer
                     .system()
                     .chain(disconnect_all_clients.system())
                     .chain(close_app.system()),
             ),
+        )
+        .add_system_set(
+            SystemSet::on_exit(ServerState::ShowResult).with_system(reset_match.system()),
         );
     }
 }
 
-fn send_statistics() {
-    // TODO
+fn send_statistics(
+    mut cmd: Commands,
+    statistics: Res<MatchStatistics>,
+    mut packets: EventWriter<ServerPacket>,
+) {
+    let results = statistics.results();
+    for (place, player) in results.scoreboard.iter().enumerate() {
+        info!("{}. {} {:?}", place + 1, player.name, player.statistics);
+    }
+
+    packets.send(ServerPacket::all(ServerMessage::Results(results)));
+    cmd.insert_resource(ResultTimer(Timer::from_seconds(RESULT_DELAY, false)));
+}
+
+/// Returns `true` once the server should shut down.
+fn check_result_timer(
+    mut timer: ResMut<ResultTimer>,
+    time: Res<Time>,
+    config: Res<ResultConfig>,
+    mut state: ResMut<State<ServerState>>,
+) -> bool {
+    if !timer.0.tick(time.delta()).just_finished() {
+        return false;
+    }
+    if config.exit_after_match {
+        return true;
+    }
+
+    state
+        .set(ServerState::Lobby)
+        .expect("Unable to change state");
+
+    false
 }
 
-fn disconnect_all_clients(mut net: ResMut<NetworkResource>) {
+/// Brings the connected players back into the lobby: the arena, projectiles and all per match
+/// state are dropped, while the clients and the host stay as they are.
+#[allow(clippy::type_complexity)]
+fn reset_match(
+    mut cmd: Commands,
+    mut packets: EventWriter<ServerPacket>,
+    players: Query<(Entity, Option<&Bot>), With<Player>>,
+    projectiles: Query<(Entity, &Uuid), With<Projectile>>,
+    floors: Query<Entity, With<ArenaFloor>>,
+) {
+    info!("Returning to lobby");
+
+    cmd.remove_resource::<Arena>();
+    cmd.remove_resource::<MatchStatistics>();
+    cmd.remove_resource::<ResultTimer>();
+
+    for entity in floors.iter() {
+        cmd.entity(entity).despawn();
+    }
+
+    for (entity, &id) in projectiles.iter() {
+        cmd.entity(entity).despawn();
+        packets.send(ServerPacket::all(ServerMessage::Despawn(id)));
+    }
+
+    for (entity, bot) in players.iter() {
+        let ready_state = if bot.is_some() {
+            ReadyState::Ready
+        } else {
+            ReadyState::NotReady
+        };
+
+        cmd.entity(entity)
+            .remove::<Health>()
+            .remove::<Dead>()
+            .remove::<Winner>()
+            .remove::<Mana>()
+            .remove::<Stats>()
+            .remove::<Cooldowns>()
+            .remove::<Waypoint>()
+            .remove::<LastInput>()
+            .remove::<PlayerColor>()
+            .remove::<Gold>()
+            .remove::<Inventory>()
+            .remove::<RoundEarnings>()
+            .remove::<Transform>()
+            .remove::<RigidBodyPositionSync>()
+            .remove_bundle::<ColliderBundle>()
+            .remove_bundle::<RigidBodyBundle>()
+            .insert(ready_state);
+    }
+
+    packets.send(ServerPacket::all(LobbyServerMessage::ReturnToLobby));
+}
+
+fn disconnect_all_clients(In(exit): In<bool>, mut net: ResMut<NetworkResource>) -> bool {
+    if !exit {
+        return false;
+    }
+
     info!("Disconnecting all clients");
     let connections = net
         .connections
@@ -33,8 +154,12 @@ fn disconnect_all_clients(mut net: ResMut<NetworkResource>) {
     for handle in connections {
         net.disconnect(handle);
     }
+
+    true
 }
 
-fn close_app(mut events: EventWriter<AppExit>) {
-    events.send(AppExit);
+fn close_app(In(exit): In<bool>, mut events: EventWriter<AppExit>) {
+    if exit {
+        events.send(AppExit);
+    }
 }
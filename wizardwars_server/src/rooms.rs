@@ -0,0 +1,318 @@
+use crate::{config::ServerConfig, network::ServerPacket, states::ServerState, ServerPlugin};
+use bevy::{app::AppExit, prelude::*};
+use std::{
+    net::UdpSocket,
+    sync::{Arc, Mutex, MutexGuard},
+    thread,
+};
+use wizardwars_shared::{
+    components::{Client, Player},
+    events::ClientEvent,
+    items::Items,
+    messages::{
+        client_messages::RoomClientMessage,
+        server_messages::{
+            LobbyServerMessage, RejectReason, RoomInfo, RoomServerMessage, ServerMessage,
+        },
+    },
+    spells::Spells,
+};
+
+/// Seconds a room created by a client stays open without anybody in it.
+const EMPTY_ROOM_TIMEOUT: f32 = 60.0;
+const MAX_NAME_LENGTH: usize = 32;
+
+pub type RoomEvent = ClientEvent<RoomClientMessage>;
+
+/// What every new room is started with.
+struct RoomTemplate {
+    config: ServerConfig,
+    spells: Spells,
+    items: Items,
+}
+
+#[derive(Default)]
+struct RoomList {
+    rooms: Vec<RoomInfo>,
+    next_id: u32,
+}
+
+/// All rooms running in this process. Every room is a separate app on its own thread and
+/// port, so entities, physics and the state machine are never shared between matches.
+#[derive(Clone)]
+pub struct Rooms {
+    list: Arc<Mutex<RoomList>>,
+    template: Arc<RoomTemplate>,
+}
+
+impl Rooms {
+    pub fn new(config: ServerConfig, spells: Spells, items: Items) -> Self {
+        Self {
+            list: Default::default(),
+            template: Arc::new(RoomTemplate {
+                config,
+                spells,
+                items,
+            }),
+        }
+    }
+
+    pub fn list(&self) -> Vec<RoomInfo> {
+        self.lock().rooms.clone()
+    }
+
+    /// Reserves an id and a port for a new room.
+    pub fn register(&self, name: &str) -> Result<RoomHandle, RejectReason> {
+        let config = &self.template.config;
+        let mut list = self.lock();
+        let free_port = (0..config.max_rooms)
+            .map(|offset| config.address.port() + offset as u16)
+            .find(|port| list.rooms.iter().all(|room| room.port != *port));
+        let port = match free_port {
+            Some(port) => port,
+            None => return Err(RejectReason::TooManyRooms),
+        };
+
+        let id = list.next_id;
+        list.next_id += 1;
+        let name = name
+            .trim()
+            .chars()
+            .take(MAX_NAME_LENGTH)
+            .collect::<String>();
+        list.rooms.push(RoomInfo {
+            id,
+            name: if name.is_empty() {
+                format!("Room {}", id)
+            } else {
+                name
+            },
+            port,
+            players: 0,
+            max_players: config.max_players,
+            state: ServerState::Init,
+        });
+
+        Ok(RoomHandle {
+            id,
+            port,
+            rooms: self.clone(),
+        })
+    }
+
+    /// Starts a new room on its own thread.
+    pub fn create(&self, name: &str) -> Result<RoomInfo, RejectReason> {
+        let room = self.register(name)?;
+        let info = room.info().expect("The room was just registered");
+
+        let mut config = self.template.config.clone();
+        config.address.set_port(room.port);
+        // The room only binds once its thread runs, catch a taken port while the client can
+        // still be told.
+        if let Err(err) = UdpSocket::bind(config.address) {
+            warn!("Cannot open a room on {}: {}", config.address, err);
+            self.remove(room.id);
+            return Err(RejectReason::RoomUnavailable);
+        }
+        let plugin = ServerPlugin::for_room(
+            config,
+            self.template.spells.clone(),
+            self.template.items.clone(),
+            room.clone(),
+        );
+        thread::Builder::new()
+            .name(format!("room-{}", room.id))
+            .spawn(move || {
+                let _registration = Registration(room);
+                App::build().add_plugin(plugin).run();
+            })
+            .expect("Unable to start a room");
+
+        Ok(info)
+    }
+
+    fn remove(&self, id: u32) {
+        self.lock().rooms.retain(|room| room.id != id);
+    }
+
+    /// The list stays usable after a room panicked while holding the lock, every change to it
+    /// is a single step.
+    fn lock(&self) -> MutexGuard<'_, RoomList> {
+        self.list
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Takes the room off the list when its thread ends, even if the room panicked.
+struct Registration(RoomHandle);
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.0.rooms.remove(self.0.id);
+    }
+}
+
+/// The room an app is running.
+#[derive(Clone)]
+pub struct RoomHandle {
+    id: u32,
+    port: u16,
+    rooms: Rooms,
+}
+
+impl RoomHandle {
+    /// The room that was started with the server, it never closes.
+    pub fn is_main(&self) -> bool {
+        self.id == 0
+    }
+
+    pub fn info(&self) -> Option<RoomInfo> {
+        self.rooms
+            .lock()
+            .rooms
+            .iter()
+            .find(|room| room.id == self.id)
+            .cloned()
+    }
+
+    fn update(&self, players: usize, state: &ServerState) {
+        let mut list = self.rooms.lock();
+        if let Some(room) = list.rooms.iter_mut().find(|room| room.id == self.id) {
+            room.players = players;
+            room.state = state.clone();
+        }
+    }
+}
+
+pub struct RoomsPlugin;
+
+impl Plugin for RoomsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<RoomEvent>()
+            .add_system(handle_room_events_system.system())
+            .add_system(update_room_info_system.system())
+            .add_system(close_empty_room_system.system());
+    }
+}
+
+fn handle_room_events_system(
+    mut events: EventReader<RoomEvent>,
+    mut packets: EventWriter<ServerPacket>,
+    room: Res<RoomHandle>,
+) {
+    for event in events.iter() {
+        let client = *event.client();
+        let message: ServerMessage = match event.event() {
+            RoomClientMessage::List => RoomServerMessage::List(room.rooms.list()).into(),
+            RoomClientMessage::Create { name } => match room.rooms.create(name) {
+                Ok(info) => {
+                    info!(
+                        "Room {} ({}) opened on port {}",
+                        info.id, info.name, info.port
+                    );
+                    RoomServerMessage::Created(info).into()
+                }
+                Err(reason) => LobbyServerMessage::Reject {
+                    reason,
+                    disconnect: false,
+                }
+                .into(),
+            },
+        };
+        packets.send(ServerPacket::single(message, client));
+    }
+}
+
+fn update_room_info_system(
+    room: Res<RoomHandle>,
+    state: Res<State<ServerState>>,
+    players: Query<&Player>,
+) {
+    room.update(players.iter().count(), state.current());
+}
+
+/// Rooms opened by clients close once everybody left.
+fn close_empty_room_system(
+    mut empty_for: Local<f32>,
+    mut exit: EventWriter<AppExit>,
+    room: Res<RoomHandle>,
+    state: Res<State<ServerState>>,
+    time: Res<Time>,
+    clients: Query<&Client>,
+) {
+    if room.is_main() || *state.current() != ServerState::Lobby || clients.iter().next().is_some() {
+        *empty_for = 0.0;
+        return;
+    }
+
+    *empty_for += time.delta_seconds();
+    if *empty_for >= EMPTY_ROOM_TIMEOUT {
+        info!("Closing empty room {}", room.id);
+        exit.send(AppExit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rooms(max_rooms: usize) -> Rooms {
+        let config = ServerConfig {
+            max_rooms,
+            ..Default::default()
+        };
+        Rooms::new(config, Spells::default(), Items::default())
+    }
+
+    #[test]
+    fn rooms_get_their_own_ports() {
+        let rooms = rooms(2);
+        let base = ServerConfig::default().address.port();
+
+        let main = rooms.register("").unwrap();
+        assert!(main.is_main());
+        assert_eq!(main.port, base);
+        let second = rooms.register("  Duel  ").unwrap();
+        assert_eq!(second.port, base + 1);
+        assert!(matches!(
+            rooms.register("Full"),
+            Err(RejectReason::TooManyRooms)
+        ));
+
+        let names = rooms
+            .list()
+            .into_iter()
+            .map(|room| room.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["Room 0".to_owned(), "Duel".to_owned()]);
+    }
+
+    #[test]
+    fn closed_rooms_free_their_port() {
+        let rooms = rooms(2);
+        rooms.register("Main").unwrap();
+        let second = rooms.register("Second").unwrap();
+        rooms.remove(second.id);
+
+        let third = rooms.register("Third").unwrap();
+        assert_eq!(third.port, second.port);
+        assert_ne!(third.id, second.id);
+    }
+
+    #[test]
+    fn panicking_rooms_are_removed() {
+        let rooms = rooms(2);
+        rooms.register("Main").unwrap();
+        let second = rooms.register("Second").unwrap();
+
+        let result = thread::spawn(move || {
+            let _registration = Registration(second);
+            panic!("Room failed to start");
+        })
+        .join();
+
+        assert!(result.is_err());
+        assert_eq!(rooms.list().len(), 1);
+    }
+}
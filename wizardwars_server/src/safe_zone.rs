@@ -0,0 +1,313 @@
+use crate::{
+    battle::BattleState, knockback::KnockbackEvent, network::ServerPacket, states::ServerState,
+};
+use bevy::prelude::*;
+use serde::Deserialize;
+use wizardwars_shared::{
+    components::{damage::DamageKind, Dead, Health, Player, Position},
+    events::DamageEvent,
+    messages::server_messages::ServerMessage,
+    validation::non_negative,
+};
+
+/// How often the current radius is sent to the clients while it is shrinking.
+const BROADCAST_INTERVAL: f32 = 0.1;
+/// Seconds a hit is remembered, a player falling off the arena in that time was killed by
+/// whoever hit them last.
+const LAST_HIT_WINDOW: f32 = 5.0;
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SafeZoneConfig {
+    pub initial_radius: f32,
+    pub final_radius: f32,
+    /// Seconds after the battle starts before the zone begins to shrink.
+    pub shrink_delay: f32,
+    /// Seconds it takes to shrink from the initial to the final radius.
+    pub shrink_duration: f32,
+    /// Damage dealt once per second to every player outside the zone.
+    pub damage_per_second: u32,
+    /// Players that fall this far below the arena floor die instantly.
+    pub fall_depth: f32,
+}
+
+impl Default for SafeZoneConfig {
+    fn default() -> Self {
+        Self {
+            initial_radius: 10.0,
+            final_radius: 2.0,
+            shrink_delay: 10.0,
+            shrink_duration: 60.0,
+            damage_per_second: 2,
+            fall_depth: 5.0,
+        }
+    }
+}
+
+impl SafeZoneConfig {
+    pub fn radius_at(&self, elapsed: f32) -> f32 {
+        if elapsed <= self.shrink_delay {
+            return self.initial_radius;
+        }
+        if self.shrink_duration <= 0.0 {
+            return self.final_radius;
+        }
+
+        let progress = ((elapsed - self.shrink_delay) / self.shrink_duration).min(1.0);
+        self.initial_radius + (self.final_radius - self.initial_radius) * progress
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        non_negative("safe_zone.initial_radius", self.initial_radius)?;
+        non_negative("safe_zone.final_radius", self.final_radius)?;
+        non_negative("safe_zone.shrink_delay", self.shrink_delay)?;
+        non_negative("safe_zone.shrink_duration", self.shrink_duration)?;
+        non_negative("safe_zone.fall_depth", self.fall_depth)?;
+        if self.final_radius > self.initial_radius {
+            return Err(format!(
+                "safe_zone.final_radius ({}) must not exceed initial_radius ({})",
+                self.final_radius, self.initial_radius
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+pub struct SafeZone {
+    radius: f32,
+    elapsed: f32,
+    broadcast_timer: Timer,
+    damage_timer: Timer,
+}
+
+impl SafeZone {
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn contains(&self, position: Vec3) -> bool {
+        Vec2::new(position.x, position.z).length() <= self.radius
+    }
+}
+
+/// The last one to damage or push the player, while it still counts.
+struct LastHit {
+    source: Entity,
+    timer: Timer,
+}
+
+impl LastHit {
+    fn new(source: Entity) -> Self {
+        Self {
+            source,
+            timer: Timer::from_seconds(LAST_HIT_WINDOW, false),
+        }
+    }
+
+    fn source(&self) -> Option<Entity> {
+        if self.timer.finished() {
+            None
+        } else {
+            Some(self.source)
+        }
+    }
+}
+
+pub struct SafeZonePlugin;
+
+impl Plugin for SafeZonePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_enter(ServerState::Battle).with_system(setup_safe_zone.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(ServerState::Battle).with_system(teardown_safe_zone.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(BattleState::Battle)
+                .with_system(shrink_safe_zone_system.system())
+                .with_system(safe_zone_damage_system.system())
+                .with_system(track_last_hit_system.system())
+                .with_system(fall_death_system.system()),
+        );
+    }
+}
+
+fn setup_safe_zone(
+    mut cmd: Commands,
+    config: Res<SafeZoneConfig>,
+    mut packets: EventWriter<ServerPacket>,
+) {
+    let radius = config.radius_at(0.0);
+    cmd.insert_resource(SafeZone {
+        radius,
+        elapsed: 0.0,
+        broadcast_timer: Timer::from_seconds(BROADCAST_INTERVAL, true),
+        damage_timer: Timer::from_seconds(1.0, true),
+    });
+
+    packets.send(ServerPacket::all(ServerMessage::SafeZoneRadius(radius)));
+}
+
+fn teardown_safe_zone(mut cmd: Commands) {
+    cmd.remove_resource::<SafeZone>();
+}
+
+fn shrink_safe_zone_system(
+    mut zone: ResMut<SafeZone>,
+    config: Res<SafeZoneConfig>,
+    time: Res<Time>,
+    mut packets: EventWriter<ServerPacket>,
+) {
+    zone.elapsed += time.delta_seconds();
+    let radius = config.radius_at(zone.elapsed);
+    if (radius - zone.radius).abs() < f32::EPSILON {
+        return;
+    }
+    zone.radius = radius;
+
+    let finished_shrinking = (radius - config.final_radius).abs() < f32::EPSILON;
+    if zone.broadcast_timer.tick(time.delta()).just_finished() || finished_shrinking {
+        packets.send(ServerPacket::all(ServerMessage::SafeZoneRadius(radius)));
+    }
+}
+
+fn safe_zone_damage_system(
+    mut damages: EventWriter<DamageEvent>,
+    mut zone: ResMut<SafeZone>,
+    config: Res<SafeZoneConfig>,
+    time: Res<Time>,
+    query: Query<(Entity, &Position), (With<Player>, With<Health>, Without<Dead>)>,
+) {
+    if !zone.damage_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    for (entity, position) in query.iter() {
+        if !zone.contains(position.0) {
+            damages.send(DamageEvent::new(
+                entity,
+                config.damage_per_second,
+                DamageKind::Environment,
+            ));
+        }
+    }
+}
+
+fn track_last_hit_system(
+    mut cmd: Commands,
+    time: Res<Time>,
+    mut damages: EventReader<DamageEvent>,
+    mut knockbacks: EventReader<KnockbackEvent>,
+    mut last_hits: Query<&mut LastHit>,
+    players: Query<(), With<Player>>,
+) {
+    for mut last_hit in last_hits.iter_mut() {
+        last_hit.timer.tick(time.delta());
+    }
+
+    let hits = damages
+        .iter()
+        .map(|damage| (damage.target, damage.source))
+        .chain(
+            knockbacks
+                .iter()
+                .map(|knockback| (knockback.target, knockback.source)),
+        );
+    for (target, source) in hits {
+        let source = match source {
+            Some(source) if source != target => source,
+            _ => continue,
+        };
+        if players.get(target).is_ok() {
+            cmd.entity(target).insert(LastHit::new(source));
+        }
+    }
+}
+
+fn fall_death_system(
+    mut damages: EventWriter<DamageEvent>,
+    config: Res<SafeZoneConfig>,
+    query: Query<(Entity, &Position, &Health, Option<&LastHit>), (With<Player>, Without<Dead>)>,
+) {
+    for (entity, position, health, last_hit) in query.iter() {
+        if position.0.y < -config.fall_depth && !health.should_die() {
+            let damage = DamageEvent::new(entity, health.current(), DamageKind::Environment);
+            // Whoever knocked the player off gets the kill.
+            damages.send(match last_hit.and_then(LastHit::source) {
+                Some(source) => damage.with_source(source),
+                None => damage,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn radius_schedule() {
+        let config = SafeZoneConfig {
+            initial_radius: 10.0,
+            final_radius: 2.0,
+            shrink_delay: 5.0,
+            shrink_duration: 8.0,
+            ..Default::default()
+        };
+
+        assert!((config.radius_at(0.0) - 10.0).abs() < f32::EPSILON);
+        assert!((config.radius_at(5.0) - 10.0).abs() < f32::EPSILON);
+        assert!((config.radius_at(9.0) - 6.0).abs() < 0.0001);
+        assert!((config.radius_at(13.0) - 2.0).abs() < f32::EPSILON);
+        assert!((config.radius_at(100.0) - 2.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn instant_shrink() {
+        let config = SafeZoneConfig {
+            shrink_delay: 1.0,
+            shrink_duration: 0.0,
+            ..Default::default()
+        };
+
+        assert!((config.radius_at(2.0) - config.final_radius).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn validate_config() {
+        assert!(SafeZoneConfig::default().validate().is_ok());
+
+        let config = SafeZoneConfig {
+            initial_radius: 1.0,
+            final_radius: 2.0,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn last_hit_expires() {
+        let mut last_hit = LastHit::new(Entity::new(1));
+        assert_eq!(last_hit.source(), Some(Entity::new(1)));
+
+        last_hit
+            .timer
+            .tick(std::time::Duration::from_secs_f32(LAST_HIT_WINDOW));
+        assert_eq!(last_hit.source(), None);
+    }
+
+    #[test]
+    fn zone_contains_ignores_height() {
+        let zone = SafeZone {
+            radius: 5.0,
+            elapsed: 0.0,
+            broadcast_timer: Timer::from_seconds(BROADCAST_INTERVAL, true),
+            damage_timer: Timer::from_seconds(1.0, true),
+        };
+
+        assert!(zone.contains(Vec3::new(3.0, 10.0, 3.0)));
+        assert!(!zone.contains(Vec3::new(4.0, 0.0, 4.0)));
+    }
+}
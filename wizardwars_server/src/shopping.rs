@@ -1,6 +1,18 @@
-use crate::{network::ServerPacket, states::ServerState};
+use crate::{arena::Arena, economy::EconomyConfig, network::ServerPacket, states::ServerState};
 use bevy::prelude::*;
-use wizardwars_shared::messages::server_messages::{ShoppingServerMessage, TimerInfo};
+use std::collections::HashMap;
+use wizardwars_shared::{
+    components::{Client, Gold, Inventory},
+    events::ClientEvent,
+    items::{Items, ShopItem},
+    messages::{
+        client_messages::ShopClientMessage,
+        server_messages::{RejectReason, ServerMessage, ShoppingServerMessage, TimerInfo},
+    },
+    spells::Spells,
+};
+
+pub type ShopEvent = ClientEvent<ShopClientMessage>;
 
 pub struct ShoppingTimer {
     pub timer: Timer,
@@ -14,15 +26,22 @@ pub struct ShoppingTimerPlugin;
 
 impl Plugin for ShoppingTimerPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_system_set(
-            SystemSet::on_enter(ServerState::Shopping).with_system(on_enter.system()),
-        )
-        .add_system_set(
-            SystemSet::on_update(ServerState::Shopping)
-                .with_system(update_timer.system())
-                .with_system(check_timer.system()),
-        )
-        .add_system_set(SystemSet::on_exit(ServerState::Shopping).with_system(on_exit.system()));
+        app.add_event::<ShopEvent>()
+            .add_system_set(
+                SystemSet::on_enter(ServerState::Shopping)
+                    .with_system(on_enter.system())
+                    .with_system(send_round.system())
+                    .with_system(send_wallets.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(ServerState::Shopping)
+                    .with_system(update_timer.system())
+                    .with_system(check_timer.system())
+                    .with_system(handle_shop_events.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(ServerState::Shopping).with_system(on_exit.system()),
+            );
     }
 }
 
@@ -33,6 +52,13 @@ fn on_enter(mut cmd: Commands, config: Res<ShoppingConfig>) {
     });
 }
 
+fn send_round(arena: Res<Arena>, mut packets: EventWriter<ServerPacket>) {
+    packets.send(ServerPacket::all(ServerMessage::Round {
+        current: arena.current_round(),
+        total: arena.total_rounds(),
+    }));
+}
+
 fn on_exit(mut cmd: Commands) {
     cmd.remove_resource::<ShoppingTimer>();
 }
@@ -61,3 +87,166 @@ fn check_timer(timer: Res<ShoppingTimer>, mut state: ResMut<State<ServerState>>)
         .set(ServerState::Battle)
         .expect("Unable to change state");
 }
+
+fn send_wallets(
+    mut packets: EventWriter<ServerPacket>,
+    query: Query<(&Client, &Gold, &Inventory)>,
+) {
+    for (client, gold, inventory) in query.iter() {
+        packets.send(ServerPacket::single(
+            ShoppingServerMessage::Gold(gold.0),
+            *client,
+        ));
+        packets.send(ServerPacket::single(
+            ShoppingServerMessage::Inventory(inventory.clone()),
+            *client,
+        ));
+    }
+}
+
+pub fn price(spells: &Spells, items: &Items, shop_item: &ShopItem) -> Option<u32> {
+    match shop_item {
+        ShopItem::Spell(id) => spells.get(id).map(|spell| spell.price),
+        ShopItem::Item(id) => items.get(id).map(|item| item.price),
+    }
+}
+
+pub fn buy(
+    inventory: &mut Inventory,
+    gold: &mut Gold,
+    shop_item: &ShopItem,
+    price: u32,
+) -> Result<(), RejectReason> {
+    if gold.0 < price {
+        return Err(RejectReason::NotEnoughGold(shop_item.clone()));
+    }
+    match shop_item {
+        ShopItem::Spell(id) => {
+            if price == 0 || !inventory.add_spell(id.clone()) {
+                return Err(RejectReason::AlreadyOwned(shop_item.clone()));
+            }
+        }
+        ShopItem::Item(id) => {
+            if !inventory.add_item(id.clone()) {
+                return Err(RejectReason::InventoryFull);
+            }
+        }
+    }
+    gold.0 -= price;
+
+    Ok(())
+}
+
+fn sell(
+    inventory: &mut Inventory,
+    gold: &mut Gold,
+    shop_item: &ShopItem,
+    refund: u32,
+) -> Result<(), RejectReason> {
+    let removed = match shop_item {
+        ShopItem::Spell(id) => inventory.remove_spell(id),
+        ShopItem::Item(id) => inventory.remove_item(id),
+    };
+    if !removed {
+        return Err(RejectReason::NotOwned(shop_item.clone()));
+    }
+    gold.0 += refund;
+
+    Ok(())
+}
+
+fn handle_shop_events(
+    mut events: EventReader<ShopEvent>,
+    mut packets: EventWriter<ServerPacket>,
+    config: Res<EconomyConfig>,
+    spells: Res<Spells>,
+    items: Res<Items>,
+    mut query: Query<(&Client, &mut Gold, &mut Inventory)>,
+) {
+    let mut wallets = query
+        .iter_mut()
+        .map(|(client, gold, inventory)| (*client, (gold, inventory)))
+        .collect::<HashMap<_, _>>();
+
+    for event in events.iter() {
+        let client = *event.client();
+        let (gold, inventory) = match wallets.get_mut(&client) {
+            Some(wallet) => wallet,
+            None => continue,
+        };
+
+        let result = match event.event() {
+            ShopClientMessage::Buy(shop_item) => match price(&spells, &items, shop_item) {
+                Some(price) => buy(inventory, gold, shop_item, price),
+                None => Err(RejectReason::UnknownShopItem(shop_item.clone())),
+            },
+            ShopClientMessage::Sell(shop_item) => match price(&spells, &items, shop_item) {
+                Some(price) => sell(inventory, gold, shop_item, config.sell_price(price)),
+                None => Err(RejectReason::UnknownShopItem(shop_item.clone())),
+            },
+        };
+
+        match result {
+            Ok(()) => {
+                packets.send(ServerPacket::single(
+                    ShoppingServerMessage::Gold(gold.0),
+                    client,
+                ));
+                packets.send(ServerPacket::single(
+                    ShoppingServerMessage::Inventory(inventory.clone()),
+                    client,
+                ));
+            }
+            Err(reason) => {
+                packets.send(ServerPacket::single(
+                    ServerMessage::ActionRejected(reason),
+                    client,
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wizardwars_shared::{items::ItemId, spells::SpellId};
+
+    #[test]
+    fn buy_and_sell_item() {
+        let mut inventory = Inventory::default();
+        let mut gold = Gold(30);
+        let boots = ShopItem::Item(ItemId::new("boots"));
+
+        assert!(buy(&mut inventory, &mut gold, &boots, 20).is_ok());
+        assert_eq!(gold, Gold(10));
+        assert_eq!(inventory.items().count(), 1);
+
+        assert!(buy(&mut inventory, &mut gold, &boots, 20).is_err());
+        assert_eq!(gold, Gold(10));
+
+        assert!(sell(&mut inventory, &mut gold, &boots, 10).is_ok());
+        assert_eq!(gold, Gold(20));
+        assert!(sell(&mut inventory, &mut gold, &boots, 10).is_err());
+    }
+
+    #[test]
+    fn spells_are_bought_once() {
+        let mut inventory = Inventory::default();
+        let mut gold = Gold(100);
+        let frostbolt = ShopItem::Spell(SpellId::new("frostbolt"));
+
+        assert!(buy(&mut inventory, &mut gold, &frostbolt, 30).is_ok());
+        assert!(buy(&mut inventory, &mut gold, &frostbolt, 30).is_err());
+        assert_eq!(gold, Gold(70));
+    }
+
+    #[test]
+    fn free_spells_cannot_be_bought() {
+        let mut inventory = Inventory::default();
+        let mut gold = Gold(100);
+        let fireball = ShopItem::Spell(SpellId::new("fireball"));
+
+        assert!(buy(&mut inventory, &mut gold, &fireball, 0).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{
+    lobby::{check_version, LobbyEvent},
+    network::{NetworkConfig, ServerPacket},
+    reconnect::CatchUpEvent,
+    states::ServerState,
+};
+use bevy::prelude::*;
+use bevy_networking_turbulence::NetworkEvent;
+use wizardwars_shared::{
+    components::Client,
+    messages::{client_messages::LobbyClientMessage, server_messages::LobbyServerMessage},
+    version::GameVersion,
+};
+
+/// A client watching the matches. It gets every broadcast but has no player and does not count
+/// against the player limit.
+pub struct Spectator;
+
+pub struct SpectatorsPlugin;
+
+impl Plugin for SpectatorsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(handle_spectate_events_system.system())
+            .add_system(handle_spectator_join_system.system())
+            .add_system(remove_disconnected_spectators_system.system());
+    }
+}
+
+fn handle_spectate_events_system(
+    mut cmd: Commands,
+    mut lobby_events: EventReader<LobbyEvent>,
+    mut packets: EventWriter<ServerPacket>,
+    mut catch_ups: EventWriter<CatchUpEvent>,
+    config: Res<NetworkConfig>,
+    version: Res<GameVersion>,
+    clients: Query<&Client>,
+) {
+    for event in lobby_events.iter() {
+        let client = *event.client();
+        if let LobbyClientMessage::Spectate {
+            version: client_version,
+            name,
+        } = event.event()
+        {
+            if !check_version(client, client_version, &version, &mut packets) {
+                continue;
+            }
+            if clients.iter().any(|other| *other == client) {
+                warn!("{:?} is already in the game", client);
+                continue;
+            }
+
+            info!("{} is spectating", name);
+            cmd.spawn()
+                .insert(client)
+                .insert(Spectator)
+                .insert(Name::new(name.clone()));
+            packets.send(ServerPacket::single(
+                LobbyServerMessage::Spectating {
+                    tick_rate: config.tick_rate,
+                },
+                client,
+            ));
+            catch_ups.send(CatchUpEvent {
+                client,
+                player: None,
+            });
+        }
+    }
+}
+
+/// Spectators can take part in the next match by joining from the lobby.
+fn handle_spectator_join_system(
+    mut cmd: Commands,
+    mut lobby_events: EventReader<LobbyEvent>,
+    state: Res<State<ServerState>>,
+    spectators: Query<(Entity, &Client), With<Spectator>>,
+) {
+    for event in lobby_events.iter() {
+        if *state.current() != ServerState::Lobby {
+            continue;
+        }
+        if let LobbyClientMessage::Join { .. } = event.event() {
+            for (entity, client) in spectators.iter() {
+                if client == event.client() {
+                    cmd.entity(entity).despawn();
+                }
+            }
+        }
+    }
+}
+
+fn remove_disconnected_spectators_system(
+    mut cmd: Commands,
+    mut network_events: EventReader<NetworkEvent>,
+    spectators: Query<(Entity, &Client, &Name), With<Spectator>>,
+) {
+    for event in network_events.iter() {
+        if let NetworkEvent::Disconnected(handle) = event {
+            for (entity, client, name) in spectators.iter() {
+                if client.0 == *handle {
+                    info!("{} stopped spectating", name.as_str());
+                    cmd.entity(entity).despawn();
+                }
+            }
+        }
+    }
+}
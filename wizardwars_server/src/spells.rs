@@ -0,0 +1,286 @@
+use crate::{
+    battle::BattleState,
+    melee::Attacking,
+    network::{IdFactory, ServerPacket},
+    projectiles,
+};
+use bevy::prelude::*;
+use bevy_rapier3d::{
+    physics::{ColliderBundle, RigidBodyBundle, RigidBodyPositionSync},
+    prelude::{ActiveEvents, ColliderShape, ColliderType, RigidBodyForces, RigidBodyVelocity},
+};
+use std::collections::HashMap;
+use wizardwars_shared::{
+    components::{
+        damage::Projectile, Client, Cooldowns, Dead, Health, Inventory, LifeTime, Mana, Owner,
+        Position, Stats, StatusEffects, Uuid, Waypoint,
+    },
+    events::{ClientEvent, SpawnEvent},
+    messages::{
+        client_messages::ActionMessage,
+        server_messages::{RejectReason, ServerMessage},
+    },
+    network::Pack,
+    spells::{EffectTarget, SpellDefinition, SpellId, Spells},
+};
+
+/// A spell that is being cast and will be released once the timer finishes.
+pub struct Casting {
+    spell: SpellId,
+    target: Vec3,
+    timer: Timer,
+}
+
+impl Casting {
+    pub fn new(spell: &SpellDefinition, target: Vec3) -> Self {
+        Self {
+            spell: spell.id.clone(),
+            target,
+            timer: Timer::from_seconds(spell.cast_time, false),
+        }
+    }
+}
+
+pub struct SpellsPlugin;
+
+impl Plugin for SpellsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_update(BattleState::Battle)
+                .with_system(handle_cast_events_system.system())
+                .with_system(cast_spells_system.system())
+                .with_system(regenerate_mana_system.system())
+                .with_system(tick_cooldowns_system.system()),
+        );
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn handle_cast_events_system(
+    mut cmd: Commands,
+    spells: Res<Spells>,
+    mut events: EventReader<ClientEvent<ActionMessage>>,
+    mut packets: EventWriter<ServerPacket>,
+    query: Query<
+        (
+            Entity,
+            &Client,
+            &Mana,
+            &Cooldowns,
+            Option<&Inventory>,
+            Option<&StatusEffects>,
+        ),
+        (With<Health>, Without<Dead>),
+    >,
+) {
+    let casters = query
+        .iter()
+        .map(|(entity, client, ..)| (*client, entity))
+        .collect::<HashMap<_, _>>();
+
+    for event in events.iter() {
+        if let ActionMessage::Cast { spell_id, target } = event.event() {
+            let client = *event.client();
+            let (caster, _, mana, cooldowns, inventory, effects) =
+                match casters.get(&client).and_then(|&e| query.get(e).ok()) {
+                    Some(caster) => caster,
+                    None => continue,
+                };
+            let reject = |reason: RejectReason| {
+                ServerPacket::single(ServerMessage::ActionRejected(reason), client)
+            };
+
+            let spell = match spells.get(spell_id) {
+                Some(spell) => spell,
+                None => {
+                    warn!("Unknown spell: {:?}", spell_id);
+                    packets.send(reject(RejectReason::UnknownSpell(spell_id.clone())));
+                    continue;
+                }
+            };
+            if let Err(reason) = try_cast(spell, mana, cooldowns, inventory, effects) {
+                packets.send(reject(reason));
+                continue;
+            }
+
+            cmd.entity(caster)
+                .remove::<Waypoint>()
+                .remove::<Attacking>()
+                .insert(Casting::new(spell, *target));
+        }
+    }
+}
+
+/// Checks that the caster is allowed to start casting the spell. Nothing is paid until the
+/// spell is released, so interrupted casts cost nothing.
+pub fn try_cast(
+    spell: &SpellDefinition,
+    mana: &Mana,
+    cooldowns: &Cooldowns,
+    inventory: Option<&Inventory>,
+    effects: Option<&StatusEffects>,
+) -> Result<(), RejectReason> {
+    if let Some(kind) = effects.and_then(|effects| effects.prevents_casting()) {
+        return Err(RejectReason::CannotCast(kind));
+    }
+    let owned = spell.price == 0 || inventory.map_or(false, |inv| inv.has_spell(&spell.id));
+    if !owned {
+        return Err(RejectReason::SpellNotOwned(spell.id.clone()));
+    }
+    if !cooldowns.is_ready(&spell.id) {
+        return Err(RejectReason::SpellOnCooldown(spell.id.clone()));
+    }
+    if !mana.can_afford(spell.mana_cost) {
+        return Err(RejectReason::NotEnoughMana(spell.id.clone()));
+    }
+
+    Ok(())
+}
+
+/// Pays for a spell that is being released and starts its cooldown.
+fn pay_for_cast(
+    spell: &SpellDefinition,
+    mana: &mut Mana,
+    cooldowns: &mut Cooldowns,
+) -> Result<(), RejectReason> {
+    if !cooldowns.is_ready(&spell.id) {
+        return Err(RejectReason::SpellOnCooldown(spell.id.clone()));
+    }
+    if !mana.spend(spell.mana_cost) {
+        return Err(RejectReason::NotEnoughMana(spell.id.clone()));
+    }
+    cooldowns.start(&spell.id, spell.cooldown);
+
+    Ok(())
+}
+
+fn regenerate_mana_system(time: Res<Time>, mut query: Query<&mut Mana, Without<Dead>>) {
+    for mut mana in query.iter_mut() {
+        if !mana.is_full() {
+            mana.regenerate(time.delta_seconds());
+        }
+    }
+}
+
+fn tick_cooldowns_system(time: Res<Time>, mut query: Query<&mut Cooldowns>) {
+    for mut cooldowns in query.iter_mut() {
+        if !cooldowns.is_empty() {
+            cooldowns.tick(time.delta_seconds());
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn cast_spells_system(
+    mut cmd: Commands,
+    mut id_factory: ResMut<IdFactory>,
+    mut packets: EventWriter<ServerPacket>,
+    spells: Res<Spells>,
+    time: Res<Time>,
+    mut casters: Query<
+        (
+            Entity,
+            &Position,
+            &mut Casting,
+            &mut Mana,
+            &mut Cooldowns,
+            Option<&Client>,
+            Option<&Stats>,
+            Option<&mut StatusEffects>,
+        ),
+        Without<Dead>,
+    >,
+) {
+    for (caster, position, mut casting, mut mana, mut cooldowns, client, stats, effects) in
+        casters.iter_mut()
+    {
+        if !casting.timer.tick(time.delta()).finished() {
+            continue;
+        }
+        cmd.entity(caster).remove::<Casting>();
+
+        if let Some(spell) = spells.get(&casting.spell) {
+            if let Err(reason) = pay_for_cast(spell, &mut mana, &mut cooldowns) {
+                if let Some(&client) = client {
+                    packets.send(ServerPacket::single(
+                        ServerMessage::ActionRejected(reason),
+                        client,
+                    ));
+                }
+                continue;
+            }
+
+            let id = id_factory.generate();
+            let damage_multiplier = stats.map_or(1.0, |stats| stats.damage_multiplier);
+            spawn_projectile(
+                &mut cmd,
+                spell,
+                id,
+                caster,
+                position.0,
+                casting.target,
+                damage_multiplier,
+            );
+            if let Some(mut effects) = effects {
+                let on_caster = spell
+                    .effects
+                    .iter()
+                    .filter(|effect| effect.target == EffectTarget::Caster);
+                for effect in on_caster {
+                    effects.add(effect.effect, effect.duration, Some(caster));
+                }
+            }
+            packets.send(Pack::all(ServerMessage::Spawn(SpawnEvent::Projectile {
+                id,
+                spell: spell.id.clone(),
+            })));
+        }
+    }
+}
+
+pub fn spawn_projectile(
+    cmd: &mut Commands,
+    spell: &SpellDefinition,
+    id: Uuid,
+    caster: Entity,
+    caster_position: Vec3,
+    target: Vec3,
+    damage_multiplier: f32,
+) {
+    let offset = 0.5;
+    let origin = caster_position + Vec3::Y * offset;
+    let target = Vec3::new(target.x, origin.y, target.z);
+    let dir = (target - origin).normalize_or_zero();
+
+    let collider = ColliderBundle {
+        collider_type: ColliderType::Sensor,
+        shape: ColliderShape::ball(spell.radius),
+        flags: (ActiveEvents::INTERSECTION_EVENTS).into(),
+        ..Default::default()
+    };
+    let rigidbody = RigidBodyBundle {
+        position: origin.into(),
+        velocity: RigidBodyVelocity {
+            linvel: (dir * spell.speed).into(),
+            ..Default::default()
+        },
+        forces: RigidBodyForces {
+            gravity_scale: 0.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let mut projectile = cmd.spawn();
+    projectile
+        .insert(Position(origin))
+        .insert(Projectile::from_spell(spell, damage_multiplier))
+        .insert(Owner::new(caster))
+        .insert(LifeTime::from_seconds(spell.lifetime))
+        .insert(Transform::default())
+        .insert_bundle(collider)
+        .insert_bundle(rigidbody)
+        .insert(RigidBodyPositionSync::Discrete)
+        .insert(id);
+    projectiles::insert_behaviour(&mut projectile, &spell.projectile, damage_multiplier);
+}
@@ -0,0 +1,246 @@
+use crate::{
+    battle::{BattleState, BattleSystem},
+    states::ServerState,
+};
+use bevy::prelude::*;
+use std::collections::HashMap;
+use wizardwars_shared::{
+    components::{
+        damage::{DamageKind, Projectile},
+        Dead, Owner, Player, Uuid, Winner,
+    },
+    events::{DamageApplied, Killed},
+    messages::server_messages::{MatchResults, PlayerResults, PlayerStatistics, RoundResults},
+};
+
+/// Statistics of the match in progress, collected round by round.
+#[derive(Debug, Default)]
+pub struct MatchStatistics {
+    names: HashMap<Uuid, String>,
+    current: HashMap<Uuid, PlayerStatistics>,
+    winner: Option<Uuid>,
+    rounds: Vec<RoundResults>,
+}
+
+impl MatchStatistics {
+    pub fn new(names: HashMap<Uuid, String>) -> Self {
+        let current = names
+            .keys()
+            .map(|&id| (id, PlayerStatistics::default()))
+            .collect();
+
+        Self {
+            names,
+            current,
+            ..Default::default()
+        }
+    }
+
+    pub fn player_mut(&mut self, id: Uuid) -> &mut PlayerStatistics {
+        self.current.entry(id).or_default()
+    }
+
+    pub fn set_winner(&mut self, id: Uuid) {
+        self.player_mut(id).round_wins += 1;
+        self.winner = Some(id);
+    }
+
+    pub fn finish_round(&mut self) {
+        let mut players = self
+            .current
+            .iter_mut()
+            .map(|(&id, statistics)| (id, std::mem::take(statistics)))
+            .collect::<Vec<_>>();
+        players.sort_by_key(|(id, _)| id.0);
+
+        self.rounds.push(RoundResults {
+            winner: self.winner.take(),
+            players,
+        });
+    }
+
+    pub fn results(&self) -> MatchResults {
+        let mut totals = HashMap::<Uuid, PlayerStatistics>::new();
+        for (id, statistics) in self.rounds.iter().flat_map(|round| round.players.iter()) {
+            let total = totals.entry(*id).or_default();
+            *total = total.combine(*statistics);
+        }
+
+        let mut scoreboard = totals
+            .into_iter()
+            .map(|(id, statistics)| PlayerResults {
+                id,
+                name: self.names.get(&id).cloned().unwrap_or_default(),
+                statistics,
+            })
+            .collect::<Vec<_>>();
+        scoreboard.sort_by_key(|player| {
+            let statistics = &player.statistics;
+            (
+                std::cmp::Reverse(statistics.round_wins),
+                std::cmp::Reverse(statistics.kills),
+                std::cmp::Reverse(statistics.damage_dealt),
+                player.id.0,
+            )
+        });
+
+        MatchResults {
+            scoreboard,
+            rounds: self.rounds.clone(),
+        }
+    }
+}
+
+pub struct StatisticsPlugin;
+
+impl Plugin for StatisticsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_enter(ServerState::WaitLoading)
+                .with_system(setup_statistics_system.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(BattleState::Battle)
+                .with_system(
+                    record_damage_system
+                        .system()
+                        .after(BattleSystem::ApplyDamage),
+                )
+                .with_system(record_casts_system.system())
+                .with_system(record_deaths_system.system())
+                .with_system(record_winner_system.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(ServerState::Battle).with_system(finish_round_system.system()),
+        );
+    }
+}
+
+fn setup_statistics_system(mut cmd: Commands, players: Query<(&Uuid, &Name), With<Player>>) {
+    let names = players
+        .iter()
+        .map(|(&id, name)| (id, name.as_str().to_owned()))
+        .collect();
+
+    cmd.insert_resource(MatchStatistics::new(names));
+}
+
+fn record_damage_system(
+    mut statistics: ResMut<MatchStatistics>,
+    mut applied: EventReader<DamageApplied>,
+    mut killed: EventReader<Killed>,
+    players: Query<&Uuid, With<Player>>,
+) {
+    // The other player, damage to oneself is not counted.
+    let attacker = |target: Uuid, source: Option<Entity>| {
+        source
+            .and_then(|source| players.get(source).ok())
+            .copied()
+            .filter(|&source| source != target)
+    };
+
+    for event in applied.iter() {
+        let target = match players.get(event.target) {
+            Ok(&target) => target,
+            Err(_) => continue,
+        };
+        statistics.player_mut(target).damage_taken += event.amount;
+
+        if let Some(source) = attacker(target, event.source) {
+            let source = statistics.player_mut(source);
+            source.damage_dealt += event.amount;
+            if event.kind == DamageKind::Spell {
+                source.hits += 1;
+            }
+        }
+    }
+    for event in killed.iter() {
+        let target = match players.get(event.target) {
+            Ok(&target) => target,
+            Err(_) => continue,
+        };
+        if let Some(source) = attacker(target, event.source) {
+            statistics.player_mut(source).kills += 1;
+        }
+    }
+}
+
+fn record_casts_system(
+    mut statistics: ResMut<MatchStatistics>,
+    projectiles: Query<&Owner, Added<Projectile>>,
+    players: Query<&Uuid, With<Player>>,
+) {
+    for owner in projectiles.iter() {
+        if let Ok(&id) = players.get(owner.entity()) {
+            statistics.player_mut(id).spells_cast += 1;
+        }
+    }
+}
+
+fn record_deaths_system(
+    mut statistics: ResMut<MatchStatistics>,
+    query: Query<&Uuid, (With<Player>, Added<Dead>)>,
+) {
+    for &id in query.iter() {
+        statistics.player_mut(id).deaths += 1;
+    }
+}
+
+fn record_winner_system(
+    mut statistics: ResMut<MatchStatistics>,
+    query: Query<&Uuid, (With<Player>, Added<Winner>)>,
+) {
+    for &id in query.iter() {
+        statistics.set_winner(id);
+    }
+}
+
+fn finish_round_system(mut statistics: ResMut<MatchStatistics>) {
+    statistics.finish_round();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statistics() -> MatchStatistics {
+        let names = vec![
+            (Uuid(0), "Merlin".to_owned()),
+            (Uuid(1), "Gandalf".to_owned()),
+        ];
+        MatchStatistics::new(names.into_iter().collect())
+    }
+
+    #[test]
+    fn rounds_are_recorded_separately() {
+        let mut statistics = statistics();
+        statistics.player_mut(Uuid(0)).kills += 1;
+        statistics.set_winner(Uuid(0));
+        statistics.finish_round();
+        statistics.player_mut(Uuid(1)).kills += 2;
+        statistics.set_winner(Uuid(1));
+        statistics.finish_round();
+
+        let results = statistics.results();
+        assert_eq!(results.rounds.len(), 2);
+        assert_eq!(results.rounds[0].winner, Some(Uuid(0)));
+        assert_eq!(results.rounds[0].players[1].1, PlayerStatistics::default());
+        assert_eq!(results.rounds[1].players[1].1.kills, 2);
+    }
+
+    #[test]
+    fn scoreboard_is_sorted() {
+        let mut statistics = statistics();
+        statistics.set_winner(Uuid(1));
+        statistics.player_mut(Uuid(0)).kills += 3;
+        statistics.finish_round();
+        statistics.set_winner(Uuid(1));
+        statistics.finish_round();
+
+        let results = statistics.results();
+        assert_eq!(results.scoreboard[0].id, Uuid(1));
+        assert_eq!(results.scoreboard[0].name, "Gandalf");
+        assert_eq!(results.scoreboard[0].statistics.round_wins, 2);
+        assert_eq!(results.scoreboard[1].statistics.kills, 3);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::{battle::BattleState, network::ServerPacket, spells::Casting, states::ServerState};
+use bevy::prelude::*;
+use wizardwars_shared::{
+    components::{StatusEffectKind, StatusEffects, Uuid},
+    events::DamageEvent,
+    messages::server_messages::StatusEffectMessage,
+};
+
+/// Effect kinds the clients were told about.
+#[derive(Default)]
+struct AnnouncedEffects(Vec<StatusEffectKind>);
+
+pub struct StatusEffectsPlugin;
+
+impl Plugin for StatusEffectsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_update(BattleState::Battle)
+                .with_system(tick_status_effects_system.system())
+                .with_system(interrupt_casting_system.system())
+                .with_system(announce_status_effects_system.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(ServerState::Battle).with_system(cleanup_system.system()),
+        );
+    }
+}
+
+fn tick_status_effects_system(
+    mut damages: EventWriter<DamageEvent>,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut StatusEffects)>,
+) {
+    for (entity, mut effects) in query.iter_mut() {
+        if let Some(damage) = effects.tick(entity, time.delta_seconds()) {
+            damages.send(damage);
+        }
+    }
+}
+
+fn interrupt_casting_system(
+    mut cmd: Commands,
+    query: Query<(Entity, &StatusEffects), (With<Casting>, Changed<StatusEffects>)>,
+) {
+    for (entity, effects) in query.iter() {
+        if effects.prevents_casting().is_some() {
+            cmd.entity(entity).remove::<Casting>();
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn announce_status_effects_system(
+    mut cmd: Commands,
+    mut packets: EventWriter<ServerPacket>,
+    query: Query<
+        (Entity, &Uuid, &StatusEffects, Option<&AnnouncedEffects>),
+        Changed<StatusEffects>,
+    >,
+) {
+    for (entity, &id, effects, announced) in query.iter() {
+        let kinds = effects.kinds();
+        let announced = announced.map_or(&[][..], |announced| announced.0.as_slice());
+        if kinds == announced {
+            continue;
+        }
+
+        for &kind in kinds.iter().filter(|kind| !announced.contains(kind)) {
+            packets.send(ServerPacket::all(StatusEffectMessage::Started {
+                id,
+                kind,
+                duration: effects.remaining(kind),
+            }));
+        }
+        for &kind in announced.iter().filter(|kind| !kinds.contains(kind)) {
+            packets.send(ServerPacket::all(StatusEffectMessage::Ended { id, kind }));
+        }
+        cmd.entity(entity).insert(AnnouncedEffects(kinds));
+    }
+}
+
+/// Effects do not carry over into the next round.
+fn cleanup_system(
+    mut cmd: Commands,
+    mut packets: EventWriter<ServerPacket>,
+    query: Query<(Entity, &Uuid, Option<&AnnouncedEffects>), With<StatusEffects>>,
+) {
+    for (entity, &id, announced) in query.iter() {
+        for &kind in announced.iter().flat_map(|announced| announced.0.iter()) {
+            packets.send(ServerPacket::all(StatusEffectMessage::Ended { id, kind }));
+        }
+        cmd.entity(entity)
+            .remove::<StatusEffects>()
+            .remove::<AnnouncedEffects>();
+    }
+}
@@ -0,0 +1,47 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+/// Finds a data file given by a relative path. The working directory is tried first, then the
+/// directory of the executable the files are shipped next to.
+pub fn resolve_path(path: &Path) -> PathBuf {
+    if path.is_absolute() || path.exists() {
+        return path.to_owned();
+    }
+
+    search_dirs()
+        .into_iter()
+        .map(|dir| dir.join(path))
+        .find(|candidate| candidate.exists())
+        .unwrap_or_else(|| path.to_owned())
+}
+
+fn search_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(dir) = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_owned))
+    {
+        dirs.push(dir);
+    }
+    // `cargo run` points this at the crate being run, the data files live in the shared crate
+    // next to it.
+    if let Some(dir) = env::var_os("CARGO_MANIFEST_DIR") {
+        dirs.push(PathBuf::from(dir).join("../wizardwars_shared"));
+    }
+
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_files_keep_their_path() {
+        let path = Path::new("assets/does_not_exist.ron");
+
+        assert_eq!(resolve_path(path), path);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::spells::SpellId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Remaining cooldown in seconds for every spell that cannot be cast yet.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Cooldowns {
+    remaining: HashMap<SpellId, f32>,
+}
+
+impl Cooldowns {
+    pub fn start(&mut self, spell: &SpellId, duration: f32) {
+        if duration > 0.0 {
+            self.remaining.insert(spell.clone(), duration);
+        }
+    }
+
+    pub fn is_ready(&self, spell: &SpellId) -> bool {
+        !self.remaining.contains_key(spell)
+    }
+
+    pub fn remaining(&self, spell: &SpellId) -> f32 {
+        self.remaining.get(spell).copied().unwrap_or(0.0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remaining.is_empty()
+    }
+
+    pub fn tick(&mut self, delta_seconds: f32) {
+        for remaining in self.remaining.values_mut() {
+            *remaining -= delta_seconds;
+        }
+        self.remaining.retain(|_, remaining| *remaining > 0.0);
+    }
+
+    pub fn clear(&mut self) {
+        self.remaining.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_and_tick() {
+        let spell = SpellId::new("fireball");
+        let mut cooldowns = Cooldowns::default();
+        assert!(cooldowns.is_ready(&spell));
+
+        cooldowns.start(&spell, 1.0);
+        assert!(!cooldowns.is_ready(&spell));
+
+        cooldowns.tick(0.6);
+        assert!(!cooldowns.is_ready(&spell));
+        assert!((cooldowns.remaining(&spell) - 0.4).abs() < 0.0001);
+
+        cooldowns.tick(0.6);
+        assert!(cooldowns.is_ready(&spell));
+        assert!(cooldowns.is_empty());
+    }
+
+    #[test]
+    fn zero_cooldown_is_always_ready() {
+        let spell = SpellId::new("fireball");
+        let mut cooldowns = Cooldowns::default();
+
+        cooldowns.start(&spell, 0.0);
+        assert!(cooldowns.is_ready(&spell));
+    }
+}
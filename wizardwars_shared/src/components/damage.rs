@@ -1,33 +1,74 @@
-#[derive(Debug)]
-pub struct Damage {
-    amount: u32,
+use crate::{
+    events::DamageEvent,
+    spells::{EffectTarget, SpellDefinition, SpellEffect, SpellId},
+};
+use bevy::prelude::Entity;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum DamageKind {
+    Spell,
+    /// Basic attacks.
+    Melee,
+    /// Damage over time from a burn.
+    Burn,
+    /// The safe zone and falling off the arena, nothing protects against it.
+    Environment,
+    /// Restores health instead of taking it.
+    Healing,
 }
 
-impl Damage {
-    pub fn new(amount: u32) -> Self {
-        Self { amount }
+/// Highest fraction of the damage a resistance can block.
+pub const MAX_RESISTANCE: f32 = 0.75;
+
+/// Fractions of the incoming damage that are blocked.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Default, PartialEq)]
+pub struct Resistances {
+    /// Against basic attacks.
+    pub armor: f32,
+    /// Against spells and burns.
+    pub magic: f32,
+    /// Fraction of the knockback that is blocked.
+    pub knockback: f32,
+}
+
+impl Resistances {
+    /// Damage left after the resistance against its kind, rounded to the nearest point.
+    pub fn reduce(&self, amount: u32, kind: DamageKind) -> u32 {
+        let resistance = match kind {
+            DamageKind::Melee => self.armor,
+            DamageKind::Spell | DamageKind::Burn => self.magic,
+            DamageKind::Environment | DamageKind::Healing => 0.0,
+        };
+        let resistance = resistance.max(0.0).min(MAX_RESISTANCE);
+
+        (amount as f32 * (1.0 - resistance)).round() as u32
     }
 
-    pub fn amount(&self) -> u32 {
-        self.amount
+    /// Factor applied to the strength of incoming knockback.
+    pub fn knockback_multiplier(&self) -> f32 {
+        1.0 - self.knockback.max(0.0).min(MAX_RESISTANCE)
     }
 }
 
 pub struct Attack {
     damage: u32,
     knockback_force: f32,
+    kind: DamageKind,
 }
 
 impl Attack {
-    pub fn new(damage: u32, knockback_force: f32) -> Self {
+    /// `knockback_force` is the impulse given to an unhurt target.
+    pub fn new(damage: u32, knockback_force: f32, kind: DamageKind) -> Self {
         Attack {
             damage,
             knockback_force,
+            kind,
         }
     }
 
-    pub fn damage(&self) -> Damage {
-        Damage::new(self.damage)
+    pub fn damage(&self, target: Entity, source: Entity) -> DamageEvent {
+        DamageEvent::new(target, self.damage, self.kind).with_source(source)
     }
 
     pub fn knockback_force(&self) -> f32 {
@@ -35,6 +76,57 @@ impl Attack {
     }
 }
 
-pub struct FireBall {
+pub struct Projectile {
+    pub spell: SpellId,
     pub attack: Attack,
+    /// Applied to whoever the projectile hits.
+    pub effects: Vec<SpellEffect>,
+}
+
+impl Projectile {
+    pub fn from_spell(spell: &SpellDefinition, damage_multiplier: f32) -> Self {
+        let damage = (spell.damage as f32 * damage_multiplier).round() as u32;
+        Self {
+            spell: spell.id.clone(),
+            attack: Attack::new(damage, spell.knockback, DamageKind::Spell),
+            effects: spell
+                .effects
+                .iter()
+                .filter(|effect| effect.target == EffectTarget::Hit)
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resistances_depend_on_the_kind() {
+        let resistances = Resistances {
+            armor: 0.5,
+            magic: 0.2,
+            ..Default::default()
+        };
+
+        assert_eq!(resistances.reduce(10, DamageKind::Melee), 5);
+        assert_eq!(resistances.reduce(10, DamageKind::Spell), 8);
+        assert_eq!(resistances.reduce(10, DamageKind::Burn), 8);
+        assert_eq!(resistances.reduce(10, DamageKind::Environment), 10);
+    }
+
+    #[test]
+    fn resistances_are_capped() {
+        let resistances = Resistances {
+            armor: 2.0,
+            magic: -1.0,
+            knockback: 1.0,
+        };
+
+        assert_eq!(resistances.reduce(8, DamageKind::Melee), 2);
+        assert_eq!(resistances.reduce(8, DamageKind::Spell), 8);
+        assert!((resistances.knockback_multiplier() - 0.25).abs() < f32::EPSILON);
+    }
 }
@@ -0,0 +1,99 @@
+use crate::{items::ItemId, spells::SpellId};
+use serde::{Deserialize, Serialize};
+
+pub const MAX_ITEMS: usize = 6;
+
+/// Spells and items bought in the shop. Kept for the whole match.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Inventory {
+    spells: Vec<SpellId>,
+    items: Vec<ItemId>,
+}
+
+impl Inventory {
+    pub fn has_spell(&self, id: &SpellId) -> bool {
+        self.spells.contains(id)
+    }
+
+    pub fn add_spell(&mut self, id: SpellId) -> bool {
+        if self.has_spell(&id) {
+            return false;
+        }
+        self.spells.push(id);
+
+        true
+    }
+
+    pub fn remove_spell(&mut self, id: &SpellId) -> bool {
+        let len = self.spells.len();
+        self.spells.retain(|spell| spell != id);
+
+        self.spells.len() != len
+    }
+
+    pub fn spells(&self) -> impl Iterator<Item = &SpellId> {
+        self.spells.iter()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.items.len() >= MAX_ITEMS
+    }
+
+    pub fn add_item(&mut self, id: ItemId) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        self.items.push(id);
+
+        true
+    }
+
+    /// Removes a single copy of the item.
+    pub fn remove_item(&mut self, id: &ItemId) -> bool {
+        match self.items.iter().position(|item| item == id) {
+            Some(index) => {
+                self.items.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = &ItemId> {
+        self.items.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spells_are_unique() {
+        let mut inventory = Inventory::default();
+        let spell = SpellId::new("frostbolt");
+
+        assert!(inventory.add_spell(spell.clone()));
+        assert!(!inventory.add_spell(spell.clone()));
+        assert!(inventory.has_spell(&spell));
+
+        assert!(inventory.remove_spell(&spell));
+        assert!(!inventory.remove_spell(&spell));
+        assert!(!inventory.has_spell(&spell));
+    }
+
+    #[test]
+    fn items_stack_up_to_limit() {
+        let mut inventory = Inventory::default();
+        let item = ItemId::new("boots");
+
+        for _ in 0..MAX_ITEMS {
+            assert!(inventory.add_item(item.clone()));
+        }
+        assert!(inventory.is_full());
+        assert!(!inventory.add_item(item.clone()));
+
+        assert!(inventory.remove_item(&item));
+        assert_eq!(inventory.items().count(), MAX_ITEMS - 1);
+    }
+}
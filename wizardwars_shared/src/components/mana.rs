@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Mana {
+    current: f32,
+    maximum: f32,
+    regeneration: f32,
+}
+
+impl Mana {
+    pub fn new(amount: f32, regeneration: f32) -> Self {
+        Self {
+            current: amount,
+            maximum: amount,
+            regeneration,
+        }
+    }
+
+    pub fn current(&self) -> f32 {
+        self.current
+    }
+
+    pub fn maximum(&self) -> f32 {
+        self.maximum
+    }
+
+    /// Mana restored per second.
+    pub fn regeneration(&self) -> f32 {
+        self.regeneration
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.current >= self.maximum
+    }
+
+    pub fn can_afford(&self, cost: f32) -> bool {
+        self.current >= cost
+    }
+
+    /// Spends `cost` mana if there is enough of it, returns whether it was spent.
+    pub fn spend(&mut self, cost: f32) -> bool {
+        if !self.can_afford(cost) {
+            return false;
+        }
+        self.current -= cost;
+
+        true
+    }
+
+    pub fn regenerate(&mut self, delta_seconds: f32) {
+        self.current = (self.current + self.regeneration * delta_seconds).min(self.maximum);
+    }
+
+    pub fn set_maximum(&mut self, amount: f32) {
+        self.maximum = amount;
+        self.current = self.current.min(self.maximum);
+    }
+
+    pub fn fraction(&self) -> f32 {
+        if self.maximum <= 0.0 {
+            return 0.0;
+        }
+
+        self.current / self.maximum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spend_mana() {
+        let mut mana = Mana::new(100.0, 5.0);
+
+        assert!(mana.spend(30.0));
+        assert!((mana.current() - 70.0).abs() < f32::EPSILON);
+
+        assert!(!mana.spend(80.0));
+        assert!((mana.current() - 70.0).abs() < f32::EPSILON);
+
+        assert!(mana.spend(70.0));
+        assert!(mana.current().abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn regenerate_mana() {
+        let mut mana = Mana::new(100.0, 10.0);
+        mana.spend(50.0);
+
+        mana.regenerate(2.0);
+        assert!((mana.current() - 70.0).abs() < f32::EPSILON);
+
+        mana.regenerate(10.0);
+        assert!((mana.current() - 100.0).abs() < f32::EPSILON);
+        assert!(mana.is_full());
+    }
+
+    #[test]
+    fn set_maximum() {
+        let mut mana = Mana::new(100.0, 0.0);
+
+        mana.set_maximum(50.0);
+        assert!((mana.current() - 50.0).abs() < f32::EPSILON);
+        assert!((mana.maximum() - 50.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn fraction_without_maximum() {
+        let mana = Mana::new(0.0, 5.0);
+
+        assert!(mana.fraction().abs() < f32::EPSILON);
+    }
+}
@@ -1,12 +1,22 @@
+mod cooldowns;
 pub mod damage;
 mod health;
+mod inventory;
+mod mana;
+mod status_effects;
 
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 
+pub use cooldowns::Cooldowns;
 pub use health::Health;
+pub use inventory::{Inventory, MAX_ITEMS};
+pub use mana::Mana;
+pub use status_effects::{StatusEffect, StatusEffectKind, StatusEffects, MAX_BURN_STACKS};
 
-#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(
+    Serialize, Deserialize, Debug, Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash,
+)]
 pub struct Uuid(pub u32);
 
 #[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Copy, Clone, Hash)]
@@ -18,12 +28,47 @@ pub struct Player;
 #[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Copy, Clone, Hash)]
 pub struct Bot;
 
-#[derive(Serialize, Deserialize, Debug, Copy, Clone, Default)]
+/// How well a bot aims, dodges and shops.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Copy, Clone, Hash)]
+pub enum BotDifficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Default for BotDifficulty {
+    fn default() -> Self {
+        BotDifficulty::Normal
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Default, PartialEq)]
 pub struct Position(pub Vec3);
 
 #[derive(Serialize, Deserialize, Debug, Copy, Clone)]
 pub struct Dead;
 
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Gold(pub u32);
+
+pub const BASE_MOVE_SPEED: f32 = 2.0;
+
+/// Combat stats derived from the inventory at the start of every round.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Stats {
+    pub move_speed: f32,
+    pub damage_multiplier: f32,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            move_speed: BASE_MOVE_SPEED,
+            damage_multiplier: 1.0,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Copy, Clone)]
 pub struct Winner;
 
@@ -0,0 +1,302 @@
+use super::damage::DamageKind;
+use crate::events::DamageEvent;
+use bevy::prelude::Entity;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// Burns from different hits stack up to this many times, a new one then replaces the burn
+/// closest to running out.
+pub const MAX_BURN_STACKS: usize = 3;
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StatusEffectKind {
+    Burn,
+    Slow,
+    Stun,
+    Silence,
+    Shield,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum StatusEffect {
+    /// Damage dealt once per second.
+    Burn(u32),
+    /// Fraction of the movement speed that is lost, between 0 and 1.
+    Slow(f32),
+    /// Cannot move, cast or attack.
+    Stun,
+    /// Cannot cast.
+    Silence,
+    /// Damage absorbed before health is lost.
+    Shield(u32),
+}
+
+impl StatusEffect {
+    pub fn kind(&self) -> StatusEffectKind {
+        match self {
+            StatusEffect::Burn(_) => StatusEffectKind::Burn,
+            StatusEffect::Slow(_) => StatusEffectKind::Slow,
+            StatusEffect::Stun => StatusEffectKind::Stun,
+            StatusEffect::Silence => StatusEffectKind::Silence,
+            StatusEffect::Shield(_) => StatusEffectKind::Shield,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ActiveEffect {
+    effect: StatusEffect,
+    /// Seconds until the effect ends.
+    remaining: f32,
+    /// Seconds since the effect started, burns deal damage every full second.
+    elapsed: f32,
+    source: Option<Entity>,
+}
+
+/// Timed effects on a character. Burns stack up to a limit, slows and shields run side by side
+/// with the strongest slow applying, stuns and silences are extended.
+#[derive(Debug, Clone, Default)]
+pub struct StatusEffects {
+    effects: Vec<ActiveEffect>,
+}
+
+impl StatusEffects {
+    pub fn add(&mut self, effect: StatusEffect, duration: f32, source: Option<Entity>) {
+        let added = ActiveEffect {
+            effect,
+            remaining: duration,
+            elapsed: 0.0,
+            source,
+        };
+        match effect {
+            StatusEffect::Burn(_) => {
+                if self.count(StatusEffectKind::Burn) >= MAX_BURN_STACKS {
+                    let oldest = self
+                        .effects
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, active)| active.effect.kind() == StatusEffectKind::Burn)
+                        .min_by(|(_, a), (_, b)| {
+                            a.remaining
+                                .partial_cmp(&b.remaining)
+                                .unwrap_or(Ordering::Equal)
+                        })
+                        .map(|(index, _)| index);
+                    if let Some(index) = oldest {
+                        self.effects.remove(index);
+                    }
+                }
+                self.effects.push(added);
+            }
+            StatusEffect::Slow(_) | StatusEffect::Shield(_) => self.effects.push(added),
+            StatusEffect::Stun | StatusEffect::Silence => {
+                let existing = self
+                    .effects
+                    .iter_mut()
+                    .find(|active| active.effect.kind() == effect.kind());
+                match existing {
+                    Some(active) => active.remaining = active.remaining.max(duration),
+                    None => self.effects.push(added),
+                }
+            }
+        }
+    }
+
+    /// Advances all effects, returns the burn damage `target` takes during this step.
+    pub fn tick(&mut self, target: Entity, delta: f32) -> Option<DamageEvent> {
+        let mut amount = 0;
+        let mut source = None;
+        for active in self.effects.iter_mut() {
+            let step = delta.min(active.remaining);
+            if let StatusEffect::Burn(damage) = active.effect {
+                let ticks = (active.elapsed + step).floor() - active.elapsed.floor();
+                if ticks > 0.0 {
+                    amount += damage * ticks as u32;
+                    source = source.or(active.source);
+                }
+            }
+            active.elapsed += step;
+            active.remaining -= step;
+        }
+        self.effects.retain(|active| active.remaining > 0.0);
+
+        if amount == 0 {
+            return None;
+        }
+        let damage = DamageEvent::new(target, amount, DamageKind::Burn);
+        Some(match source {
+            Some(source) => damage.with_source(source),
+            None => damage,
+        })
+    }
+
+    /// Lets shields soak up `amount` damage, returns what gets through.
+    pub fn absorb(&mut self, mut amount: u32) -> u32 {
+        for active in self.effects.iter_mut() {
+            if let StatusEffect::Shield(shield) = &mut active.effect {
+                let absorbed = amount.min(*shield);
+                *shield -= absorbed;
+                amount -= absorbed;
+            }
+        }
+        self.effects
+            .retain(|active| active.effect != StatusEffect::Shield(0));
+
+        amount
+    }
+
+    pub fn has(&self, kind: StatusEffectKind) -> bool {
+        self.effects
+            .iter()
+            .any(|active| active.effect.kind() == kind)
+    }
+
+    /// Seconds until the last effect of the kind ends.
+    pub fn remaining(&self, kind: StatusEffectKind) -> f32 {
+        self.effects
+            .iter()
+            .filter(|active| active.effect.kind() == kind)
+            .map(|active| active.remaining)
+            .fold(0.0, f32::max)
+    }
+
+    /// Kinds of the active effects, each kind once.
+    pub fn kinds(&self) -> Vec<StatusEffectKind> {
+        let mut kinds = self
+            .effects
+            .iter()
+            .map(|active| active.effect.kind())
+            .collect::<Vec<_>>();
+        kinds.sort();
+        kinds.dedup();
+
+        kinds
+    }
+
+    /// The effect that keeps the character from casting, if any.
+    pub fn prevents_casting(&self) -> Option<StatusEffectKind> {
+        [StatusEffectKind::Stun, StatusEffectKind::Silence]
+            .iter()
+            .copied()
+            .find(|&kind| self.has(kind))
+    }
+
+    pub fn is_stunned(&self) -> bool {
+        self.has(StatusEffectKind::Stun)
+    }
+
+    /// Factor applied to the movement speed.
+    pub fn speed_multiplier(&self) -> f32 {
+        if self.is_stunned() {
+            return 0.0;
+        }
+        let slow = self
+            .effects
+            .iter()
+            .filter_map(|active| match active.effect {
+                StatusEffect::Slow(amount) => Some(amount),
+                _ => None,
+            })
+            .fold(0.0, f32::max);
+
+        1.0 - slow
+    }
+
+    fn count(&self, kind: StatusEffectKind) -> usize {
+        self.effects
+            .iter()
+            .filter(|active| active.effect.kind() == kind)
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burns_stack_up_to_the_limit() {
+        let mut effects = StatusEffects::default();
+        for duration in [1.0, 2.0, 3.0, 4.0].iter() {
+            effects.add(StatusEffect::Burn(1), *duration, None);
+        }
+
+        assert_eq!(effects.count(StatusEffectKind::Burn), MAX_BURN_STACKS);
+        // The burn with one second left was replaced.
+        assert_eq!(
+            effects
+                .tick(Entity::new(0), 1.0)
+                .map(|damage| damage.amount),
+            Some(3)
+        );
+        assert_eq!(
+            effects
+                .tick(Entity::new(0), 1.0)
+                .map(|damage| damage.amount),
+            Some(3)
+        );
+        assert_eq!(
+            effects
+                .tick(Entity::new(0), 1.0)
+                .map(|damage| damage.amount),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn burns_deal_damage_every_second() {
+        let mut effects = StatusEffects::default();
+        effects.add(StatusEffect::Burn(2), 2.0, None);
+
+        assert!(effects.tick(Entity::new(0), 0.5).is_none());
+        assert_eq!(
+            effects
+                .tick(Entity::new(0), 0.75)
+                .map(|damage| damage.amount),
+            Some(2)
+        );
+        assert!(effects.tick(Entity::new(0), 0.5).is_none());
+        // Only a quarter second is left, the last damage is still dealt.
+        assert_eq!(
+            effects
+                .tick(Entity::new(0), 0.5)
+                .map(|damage| damage.amount),
+            Some(2)
+        );
+        assert!(effects.kinds().is_empty());
+    }
+
+    #[test]
+    fn strongest_slow_applies() {
+        let mut effects = StatusEffects::default();
+        effects.add(StatusEffect::Slow(0.5), 1.0, None);
+        effects.add(StatusEffect::Slow(0.2), 3.0, None);
+
+        assert!((effects.speed_multiplier() - 0.5).abs() < f32::EPSILON);
+        effects.tick(Entity::new(0), 2.0);
+        assert!(effects.has(StatusEffectKind::Slow));
+        // The strong slow ran out, the weak one keeps going.
+        assert!((effects.speed_multiplier() - 0.8).abs() < f32::EPSILON);
+
+        effects.add(StatusEffect::Stun, 1.0, None);
+        assert!(effects.speed_multiplier().abs() < f32::EPSILON);
+        assert_eq!(effects.prevents_casting(), Some(StatusEffectKind::Stun));
+    }
+
+    #[test]
+    fn shields_absorb_damage() {
+        let mut effects = StatusEffects::default();
+        effects.add(StatusEffect::Shield(5), 1.0, None);
+        effects.add(StatusEffect::Shield(5), 1.0, None);
+
+        assert_eq!(effects.absorb(4), 0);
+        assert_eq!(effects.absorb(10), 4);
+        assert!(!effects.has(StatusEffectKind::Shield));
+
+        effects.add(StatusEffect::Shield(5), 3.0, None);
+        effects.add(StatusEffect::Shield(5), 1.0, None);
+        effects.tick(Entity::new(0), 2.0);
+        // Only the longer shield is left.
+        assert_eq!(effects.absorb(10), 5);
+    }
+}
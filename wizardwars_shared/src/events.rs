@@ -1,5 +1,11 @@
-use crate::components::{Client, Uuid};
-use bevy::{math::Vec3, prelude::Color};
+use crate::{
+    components::{damage::DamageKind, Client, Uuid},
+    spells::SpellId,
+};
+use bevy::{
+    math::Vec3,
+    prelude::{Color, Entity},
+};
 use serde::{Deserialize, Serialize};
 
 pub struct DespawnEntityEvent {
@@ -8,7 +14,7 @@ pub struct DespawnEntityEvent {
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum SpawnEvent {
-    Projectile(Uuid),
+    Projectile { id: Uuid, spell: SpellId },
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -19,6 +25,50 @@ pub struct InsertPlayerEvent {
     pub color: Color,
 }
 
+/// Damage or healing about to be dealt. All events of a frame are applied one after the other
+/// in the order they were sent, resistances first and shields second.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DamageEvent {
+    pub target: Entity,
+    /// The entity that dealt the damage, if any.
+    pub source: Option<Entity>,
+    pub amount: u32,
+    pub kind: DamageKind,
+}
+
+impl DamageEvent {
+    pub fn new(target: Entity, amount: u32, kind: DamageKind) -> Self {
+        Self {
+            target,
+            source: None,
+            amount,
+            kind,
+        }
+    }
+
+    pub fn with_source(mut self, source: Entity) -> Self {
+        self.source = Some(source);
+        self
+    }
+}
+
+/// Health actually lost after resistances and shields, never more than the target had left.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DamageApplied {
+    pub target: Entity,
+    pub source: Option<Entity>,
+    pub amount: u32,
+    pub kind: DamageKind,
+}
+
+/// The damage took the last of the target's health.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Killed {
+    pub target: Entity,
+    pub source: Option<Entity>,
+    pub kind: DamageKind,
+}
+
 pub struct ClientEvent<T> {
     client: Client,
     event: T,
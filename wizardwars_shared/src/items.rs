@@ -0,0 +1,227 @@
+use crate::{
+    assets::resolve_path, components::Inventory, spells::SpellId, validation::non_negative,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt::Formatter,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Looked up with [`resolve_path`], so it also works next to the installed executable.
+pub const DEFAULT_ITEMS_PATH: &str = "assets/items.ron";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+pub struct ItemId(pub String);
+
+impl ItemId {
+    pub fn new(id: &str) -> Self {
+        Self(id.to_owned())
+    }
+}
+
+/// Anything that can be bought or sold in the shop.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ShopItem {
+    Spell(SpellId),
+    Item(ItemId),
+}
+
+/// Bonuses granted by a passive item. Item bonuses stack additively.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(default)]
+pub struct ItemModifiers {
+    pub health: u32,
+    pub mana: f32,
+    pub mana_regeneration: f32,
+    /// Fraction added to the base movement speed.
+    pub move_speed: f32,
+    /// Fraction added to the damage of every spell.
+    pub damage: f32,
+    /// Fraction of basic attack damage blocked.
+    pub armor: f32,
+    /// Fraction of spell and burn damage blocked.
+    pub magic_resistance: f32,
+    /// Fraction of knockback blocked.
+    pub knockback_resistance: f32,
+}
+
+impl ItemModifiers {
+    pub fn combine(self, other: ItemModifiers) -> Self {
+        Self {
+            health: self.health + other.health,
+            mana: self.mana + other.mana,
+            mana_regeneration: self.mana_regeneration + other.mana_regeneration,
+            move_speed: self.move_speed + other.move_speed,
+            damage: self.damage + other.damage,
+            armor: self.armor + other.armor,
+            magic_resistance: self.magic_resistance + other.magic_resistance,
+            knockback_resistance: self.knockback_resistance + other.knockback_resistance,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        non_negative("mana", self.mana)?;
+        non_negative("mana_regeneration", self.mana_regeneration)?;
+        non_negative("move_speed", self.move_speed)?;
+        non_negative("damage", self.damage)?;
+        non_negative("armor", self.armor)?;
+        non_negative("magic_resistance", self.magic_resistance)?;
+        non_negative("knockback_resistance", self.knockback_resistance)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ItemDefinition {
+    pub id: ItemId,
+    pub name: String,
+    pub price: u32,
+    #[serde(default)]
+    pub modifiers: ItemModifiers,
+}
+
+/// Registry of all passive items available in the shop, in the order they are
+/// defined in the items file.
+#[derive(Debug, Clone, Default)]
+pub struct Items {
+    definitions: Vec<ItemDefinition>,
+    indices: HashMap<ItemId, usize>,
+}
+
+impl Items {
+    pub fn from_definitions(definitions: Vec<ItemDefinition>) -> Result<Self, ItemsError> {
+        let mut indices = HashMap::new();
+        for (index, definition) in definitions.iter().enumerate() {
+            definition.modifiers.validate().map_err(|reason| {
+                ItemsError::Invalid(format!("{}: {}", definition.id.0, reason))
+            })?;
+            if indices.insert(definition.id.clone(), index).is_some() {
+                return Err(ItemsError::Invalid(format!(
+                    "duplicate item id: {}",
+                    definition.id.0
+                )));
+            }
+        }
+
+        Ok(Self {
+            definitions,
+            indices,
+        })
+    }
+
+    pub fn from_ron_str(source: &str) -> Result<Self, ItemsError> {
+        let definitions =
+            ron::from_str(source).map_err(|err| ItemsError::Parse(err.to_string()))?;
+        Self::from_definitions(definitions)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, ItemsError> {
+        let source = fs::read_to_string(resolve_path(path))
+            .map_err(|err| ItemsError::Io(path.to_owned(), err))?;
+        Self::from_ron_str(&source)
+    }
+
+    pub fn get(&self, id: &ItemId) -> Option<&ItemDefinition> {
+        self.indices.get(id).map(|&index| &self.definitions[index])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ItemDefinition> {
+        self.definitions.iter()
+    }
+
+    /// Sum of the modifiers of every item in the inventory.
+    pub fn modifiers(&self, inventory: &Inventory) -> ItemModifiers {
+        inventory
+            .items()
+            .filter_map(|id| self.get(id))
+            .fold(ItemModifiers::default(), |total, item| {
+                total.combine(item.modifiers)
+            })
+    }
+}
+
+#[derive(Debug)]
+pub enum ItemsError {
+    Io(PathBuf, std::io::Error),
+    Parse(String),
+    Invalid(String),
+}
+
+impl std::fmt::Display for ItemsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ItemsError::Io(path, err) => write!(f, "Cannot read {}: {}", path.display(), err),
+            ItemsError::Parse(err) => write!(f, "Cannot parse items: {}", err),
+            ItemsError::Invalid(reason) => write!(f, "Invalid item: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ItemsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boots() -> ItemDefinition {
+        ItemDefinition {
+            id: ItemId::new("boots"),
+            name: "Boots".to_owned(),
+            price: 20,
+            modifiers: ItemModifiers {
+                move_speed: 0.2,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn amulet() -> ItemDefinition {
+        ItemDefinition {
+            id: ItemId::new("amulet"),
+            name: "Amulet".to_owned(),
+            price: 25,
+            modifiers: ItemModifiers {
+                health: 5,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn default_items_file_is_valid() {
+        let items = Items::from_file(Path::new(DEFAULT_ITEMS_PATH)).unwrap();
+
+        assert!(items.iter().next().is_some());
+    }
+
+    #[test]
+    fn reject_duplicate_ids() {
+        assert!(Items::from_definitions(vec![boots(), boots()]).is_err());
+    }
+
+    #[test]
+    fn reject_invalid_modifiers() {
+        let mut broken = boots();
+        broken.modifiers.move_speed = -0.5;
+        assert!(Items::from_definitions(vec![broken]).is_err());
+
+        let mut broken = amulet();
+        broken.modifiers.mana = f32::NAN;
+        assert!(Items::from_definitions(vec![broken]).is_err());
+    }
+
+    #[test]
+    fn modifiers_stack() {
+        let items = Items::from_definitions(vec![boots(), amulet()]).unwrap();
+        let mut inventory = Inventory::default();
+        inventory.add_item(ItemId::new("boots"));
+        inventory.add_item(ItemId::new("boots"));
+        inventory.add_item(ItemId::new("amulet"));
+
+        let modifiers = items.modifiers(&inventory);
+        assert_eq!(modifiers.health, 5);
+        assert!((modifiers.move_speed - 0.4).abs() < 0.0001);
+    }
+}
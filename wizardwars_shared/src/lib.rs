@@ -1,9 +1,17 @@
+pub mod assets;
 pub mod components;
 pub mod events;
+pub mod items;
 pub mod messages;
+pub mod movement;
 pub mod network;
+pub mod replay;
 pub mod resources;
+pub mod spells;
+pub mod states;
 pub mod systems;
+pub mod validation;
+pub mod version;
 
 #[macro_export]
 macro_rules! enum_from {
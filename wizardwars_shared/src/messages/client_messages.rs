@@ -1,55 +1,116 @@
-use crate::components::{ReadyState, Uuid};
+use crate::{
+    components::{BotDifficulty, ReadyState, Uuid},
+    items::ShopItem,
+    messages::server_messages::ReconnectToken,
+    spells::SpellId,
+    version::GameVersion,
+};
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 
+/// Decides whether the sender may send the message. `has_joined` is set once the sender has
+/// a player.
 pub trait Verify {
-    fn verify(&self, _is_host: bool) -> bool {
+    fn verify(&self, _is_host: bool, _has_joined: bool) -> bool {
         true
     }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum LobbyClientMessage {
-    Join(String),
+    /// Sent after the `Handshake`, the version is checked once more before the player joins.
+    Join {
+        version: GameVersion,
+        name: String,
+    },
+    /// Takes over the player the token was issued for, in place of `Join`.
+    Reconnect {
+        version: GameVersion,
+        token: ReconnectToken,
+    },
+    /// Watches the matches without playing, can be sent at any time.
+    Spectate {
+        version: GameVersion,
+        name: String,
+    },
     ChangeReadyState(ReadyState),
     GetPlayerList,
-    AddBot,
+    AddBot(BotDifficulty),
     StartGame,
 }
 
 impl Verify for LobbyClientMessage {
-    fn verify(&self, is_host: bool) -> bool {
+    fn verify(&self, is_host: bool, _has_joined: bool) -> bool {
         match self {
-            LobbyClientMessage::Join(_)
+            LobbyClientMessage::Join { .. }
+            | LobbyClientMessage::Reconnect { .. }
+            | LobbyClientMessage::Spectate { .. }
             | LobbyClientMessage::ChangeReadyState(_)
             | LobbyClientMessage::GetPlayerList => true,
-            LobbyClientMessage::AddBot | LobbyClientMessage::StartGame => is_host,
+            LobbyClientMessage::AddBot(_) | LobbyClientMessage::StartGame => is_host,
+        }
+    }
+}
+
+/// Browses the matches running on the server, understood in every room.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum RoomClientMessage {
+    List,
+    Create { name: String },
+}
+
+impl Verify for RoomClientMessage {
+    fn verify(&self, _is_host: bool, has_joined: bool) -> bool {
+        match self {
+            RoomClientMessage::List => true,
+            RoomClientMessage::Create { .. } => has_joined,
         }
     }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum ActionMessage {
-    Move { target: Vec3 },
-    Attack { target: Uuid },
-    FireBall(Vec3),
+    /// `sequence` increases with every move, the server acknowledges the last one it applied.
+    Move {
+        target: Vec3,
+        sequence: u32,
+    },
+    Attack {
+        target: Uuid,
+    },
+    Cast {
+        spell_id: SpellId,
+        target: Vec3,
+    },
 }
 
 impl Verify for ActionMessage {}
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ShopClientMessage {
+    Buy(ShopItem),
+    Sell(ShopItem),
+}
+
+impl Verify for ShopClientMessage {}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum ClientMessage {
     LobbyMessage(LobbyClientMessage),
+    Room(RoomClientMessage),
     Loaded,
     Action(ActionMessage),
+    Shop(ShopClientMessage),
 }
 
 impl Verify for ClientMessage {
-    fn verify(&self, is_host: bool) -> bool {
+    fn verify(&self, is_host: bool, has_joined: bool) -> bool {
         match self {
-            ClientMessage::LobbyMessage(message) => message.verify(is_host),
+            ClientMessage::LobbyMessage(message) => message.verify(is_host, has_joined),
+            ClientMessage::Room(message) => message.verify(is_host, has_joined),
             ClientMessage::Loaded => true,
-            ClientMessage::Action(message) => message.verify(is_host),
+            ClientMessage::Action(message) => message.verify(is_host, has_joined),
+            ClientMessage::Shop(message) => message.verify(is_host, has_joined),
         }
     }
 }
@@ -0,0 +1,13 @@
+use crate::version::GameVersion;
+use serde::{Deserialize, Serialize};
+
+/// First message on a connection, sent on a channel of its own. Neither it nor `GameVersion`
+/// may ever change, so that clients and servers of any version can tell each other apart
+/// before they try to read anything else.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Handshake {
+    /// Sent by the client as soon as it is connected.
+    Hello(GameVersion),
+    /// The server runs another version, the client has to disconnect.
+    VersionMismatch { server_version: GameVersion },
+}
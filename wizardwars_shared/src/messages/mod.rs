@@ -1,14 +1,19 @@
 pub mod client_messages;
+pub mod handshake;
 pub mod server_messages;
 
-use crate::components::{Position, Uuid};
+use crate::{
+    components::{Dead, Health, ReadyState, Stats, Winner},
+    network::{Replicate, ReplicationRegistry, ReplicationSettings, SnapshotAck, SnapshotDelta},
+};
 use bevy::prelude::*;
 use bevy_networking_turbulence::{
     ConnectionChannelsBuilder, MessageChannelMode, MessageChannelSettings, NetworkResource,
     ReliableChannelSettings,
 };
 use client_messages::ClientMessage;
-use server_messages::ServerMessage;
+use handshake::Handshake;
+use server_messages::{InputAck, ServerMessage};
 use std::time::Duration;
 
 pub const CLIENT_MESSAGE_SETTINGS: MessageChannelSettings = MessageChannelSettings {
@@ -47,13 +52,20 @@ pub const SERVER_MESSAGE_SETTINGS: MessageChannelSettings = MessageChannelSettin
             rtt_update_factor: 0.1,
             rtt_resend_factor: 1.5,
         },
-        max_message_len: 1024,
+        // Large enough for the end of match results of a full lobby.
+        max_message_len: 8192,
     },
     message_buffer_size: 8,
     packet_buffer_size: 8,
 };
 
-fn player_component_message_settings(channel: u8) -> MessageChannelSettings {
+/// Never changes, see [`Handshake`].
+pub const HANDSHAKE_SETTINGS: MessageChannelSettings = MessageChannelSettings {
+    channel: 5,
+    ..CLIENT_MESSAGE_SETTINGS
+};
+
+fn unreliable_message_settings(channel: u8) -> MessageChannelSettings {
     MessageChannelSettings {
         channel,
         channel_mode: MessageChannelMode::Unreliable,
@@ -62,8 +74,15 @@ fn player_component_message_settings(channel: u8) -> MessageChannelSettings {
     }
 }
 
-pub fn network_channels_setup(mut net: ResMut<NetworkResource>) {
-    net.set_channels_builder(|builder: &mut ConnectionChannelsBuilder| {
+pub fn network_channels_setup(
+    mut net: ResMut<NetworkResource>,
+    registry: Option<Res<ReplicationRegistry>>,
+) {
+    let registry = registry
+        .map(|registry| registry.clone())
+        .unwrap_or_default();
+    net.set_channels_builder(move |builder: &mut ConnectionChannelsBuilder| {
+        builder.register::<Handshake>(HANDSHAKE_SETTINGS).unwrap();
         builder
             .register::<ClientMessage>(CLIENT_MESSAGE_SETTINGS)
             .unwrap();
@@ -71,7 +90,27 @@ pub fn network_channels_setup(mut net: ResMut<NetworkResource>) {
             .register::<ServerMessage>(SERVER_MESSAGE_SETTINGS)
             .unwrap();
         builder
-            .register::<(Uuid, Position)>(player_component_message_settings(2))
+            .register::<SnapshotDelta>(unreliable_message_settings(2))
+            .unwrap();
+        builder
+            .register::<SnapshotAck>(unreliable_message_settings(3))
             .unwrap();
+        builder
+            .register::<InputAck>(unreliable_message_settings(4))
+            .unwrap();
+        registry.register_channels(builder);
     });
 }
+
+/// Components replicated outside of the world snapshots, added by both the server and the client.
+pub struct ReplicationPlugin;
+
+impl Plugin for ReplicationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.replicate::<Health>()
+            .replicate::<Dead>()
+            .replicate::<Winner>()
+            .replicate::<ReadyState>()
+            .replicate_with::<Stats>(ReplicationSettings::default().owner_only());
+    }
+}
@@ -1,19 +1,56 @@
 use crate::{
-    components::{ReadyState, Uuid},
+    components::{damage::DamageKind, Inventory, ReadyState, StatusEffectKind, Uuid},
     enum_from,
     events::{InsertPlayerEvent, SpawnEvent},
+    items::ShopItem,
+    spells::SpellId,
+    states::ServerState,
+    version::GameVersion,
 };
+use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum RejectReason {
     LobbyFull,
+    UnknownSpell(SpellId),
+    SpellOnCooldown(SpellId),
+    NotEnoughMana(SpellId),
+    SpellNotOwned(SpellId),
+    UnknownShopItem(ShopItem),
+    NotEnoughGold(ShopItem),
+    AlreadyOwned(ShopItem),
+    NotOwned(ShopItem),
+    InventoryFull,
+    VersionMismatch {
+        server_version: GameVersion,
+    },
+    InvalidReconnectToken,
+    TooManyRooms,
+    /// The port of the new room is taken by another program.
+    RoomUnavailable,
+    /// The attacked player does not exist or is already dead.
+    InvalidTarget(Uuid),
+    /// The caster is stunned or silenced.
+    CannotCast(StatusEffectKind),
 }
 
+/// Lets a client reclaim its player after the connection dropped.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ReconnectToken(pub u64);
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum LobbyServerMessage {
-    Welcome(Uuid),
+    Welcome {
+        id: Uuid,
+        tick_rate: u32,
+        reconnect_token: ReconnectToken,
+    },
+    /// Answers `Spectate`, the client receives everything but has no player.
+    Spectating {
+        tick_rate: u32,
+    },
     Reject {
         reason: RejectReason,
         disconnect: bool,
@@ -23,6 +60,24 @@ pub enum LobbyServerMessage {
     ReadyState(ReadyState),
     SetHost(Uuid),
     StartLoading,
+    ReturnToLobby,
+}
+
+/// A match running on the server, clients join it by connecting to its port.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RoomInfo {
+    pub id: u32,
+    pub name: String,
+    pub port: u16,
+    pub players: usize,
+    pub max_players: usize,
+    pub state: ServerState,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum RoomServerMessage {
+    List(Vec<RoomInfo>),
+    Created(RoomInfo),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -40,18 +95,110 @@ pub struct TimerInfo {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum ShoppingServerMessage {
     Timer(TimerInfo),
+    Gold(u32),
+    Inventory(Inventory),
+}
+
+/// Everything a single player achieved, either in one round or in the whole match.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerStatistics {
+    pub kills: u32,
+    pub deaths: u32,
+    pub damage_dealt: u32,
+    pub damage_taken: u32,
+    pub spells_cast: u32,
+    /// Spells that damaged another player.
+    pub hits: u32,
+    pub round_wins: u32,
+}
+
+impl PlayerStatistics {
+    pub fn combine(self, other: PlayerStatistics) -> Self {
+        Self {
+            kills: self.kills + other.kills,
+            deaths: self.deaths + other.deaths,
+            damage_dealt: self.damage_dealt + other.damage_dealt,
+            damage_taken: self.damage_taken + other.damage_taken,
+            spells_cast: self.spells_cast + other.spells_cast,
+            hits: self.hits + other.hits,
+            round_wins: self.round_wins + other.round_wins,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlayerResults {
+    pub id: Uuid,
+    pub name: String,
+    pub statistics: PlayerStatistics,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RoundResults {
+    pub winner: Option<Uuid>,
+    pub players: Vec<(Uuid, PlayerStatistics)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MatchResults {
+    /// Sorted from the best to the worst player.
+    pub scoreboard: Vec<PlayerResults>,
+    pub rounds: Vec<RoundResults>,
+}
+
+/// Sent to a player with the authoritative position of their character after the last
+/// applied move, used by the client to reconcile its prediction.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct InputAck {
+    pub sequence: u32,
+    /// Seconds the character has been moving since the move was applied.
+    pub elapsed: f32,
+    pub position: Vec3,
+}
+
+/// A status effect appeared on or disappeared from a player.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum StatusEffectMessage {
+    Started {
+        id: Uuid,
+        kind: StatusEffectKind,
+        /// Seconds the effect lasts.
+        duration: f32,
+    },
+    Ended {
+        id: Uuid,
+        kind: StatusEffectKind,
+    },
+}
+
+/// A player was knocked out.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KillFeedEntry {
+    pub victim: Uuid,
+    /// The other player who dealt the final blow, if any.
+    pub killer: Option<Uuid>,
+    pub kind: DamageKind,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum ServerMessage {
     Lobby(LobbyServerMessage),
+    Room(RoomServerMessage),
     Loading(LoadingServerMessage),
     Shopping(ShoppingServerMessage),
     InsertPlayer(InsertPlayerEvent),
     Spawn(SpawnEvent),
     Despawn(Uuid),
+    ActionRejected(RejectReason),
+    SafeZoneRadius(f32),
+    Round { current: u32, total: u32 },
+    Results(MatchResults),
+    StatusEffect(StatusEffectMessage),
+    KillFeed(KillFeedEntry),
 }
 
 enum_from!(ServerMessage, Lobby, LobbyServerMessage);
+enum_from!(ServerMessage, Room, RoomServerMessage);
 enum_from!(ServerMessage, Loading, LoadingServerMessage);
 enum_from!(ServerMessage, Shopping, ShoppingServerMessage);
+enum_from!(ServerMessage, StatusEffect, StatusEffectMessage);
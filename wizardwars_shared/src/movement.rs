@@ -0,0 +1,43 @@
+use bevy::prelude::*;
+
+/// Distance at which a waypoint counts as reached.
+pub const ARRIVAL_DISTANCE: f32 = 0.02;
+
+/// Moves `position` towards `target` at `speed` units per second without overshooting it.
+/// Shared by the server simulation and the client prediction so both agree on the path.
+pub fn step_towards(position: Vec3, target: Vec3, speed: f32, dt: f32) -> Vec3 {
+    let offset = target - position;
+    let distance = offset.length();
+    let step = speed * dt;
+    if distance <= step {
+        target
+    } else {
+        position + offset / distance * step
+    }
+}
+
+pub fn has_arrived(position: Vec3, target: Vec3) -> bool {
+    position.distance(target) < ARRIVAL_DISTANCE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn step_towards_target() {
+        let position = step_towards(Vec3::ZERO, Vec3::new(10.0, 0.0, 0.0), 2.0, 0.5);
+
+        assert_eq!(position, Vec3::new(1.0, 0.0, 0.0));
+        assert!(!has_arrived(position, Vec3::new(10.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn never_overshoot() {
+        let target = Vec3::new(0.0, 0.0, 1.0);
+        let position = step_towards(Vec3::ZERO, target, 2.0, 1.0);
+
+        assert_eq!(position, target);
+        assert!(has_arrived(position, target));
+    }
+}
@@ -0,0 +1,196 @@
+use crate::{states::ServerState, version::GameVersion};
+use bevy::log::warn;
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+};
+
+/// Port servers listen on for discovery queries.
+pub const DISCOVERY_PORT: u16 = 9000;
+/// Every discovery packet starts with this, anything else arriving on the port is ignored.
+const MAGIC: &[u8] = b"WIZARDWARS";
+const MAX_PACKET_SIZE: usize = 1024;
+
+/// What a server tells about itself when asked on the LAN.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ServerAnnouncement {
+    pub name: String,
+    /// Game port, the address is the one the announcement came from.
+    pub port: u16,
+    pub players: usize,
+    pub max_players: usize,
+    pub state: ServerState,
+    pub version: GameVersion,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+enum DiscoveryMessage {
+    Query,
+    Announce(ServerAnnouncement),
+}
+
+impl DiscoveryMessage {
+    fn encode(&self) -> Vec<u8> {
+        let body = ron::to_string(self).expect("Discovery messages are always serializable");
+        [MAGIC, body.as_bytes()].concat()
+    }
+
+    fn decode(packet: &[u8]) -> Option<Self> {
+        let body = packet.strip_prefix(MAGIC)?;
+        let body = std::str::from_utf8(body).ok()?;
+        ron::from_str(body).ok()
+    }
+}
+
+/// Receives packets until none are left, skipping the ones that are not discovery messages.
+fn receive_all(socket: &UdpSocket) -> io::Result<Vec<(SocketAddr, DiscoveryMessage)>> {
+    let mut buffer = [0; MAX_PACKET_SIZE];
+    let mut messages = Vec::new();
+    loop {
+        match socket.recv_from(&mut buffer) {
+            Ok((len, from)) => {
+                if let Some(message) = DiscoveryMessage::decode(&buffer[..len]) {
+                    messages.push((from, message));
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(messages),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Server side, answers queries on the discovery port.
+pub struct DiscoveryResponder {
+    socket: UdpSocket,
+}
+
+impl DiscoveryResponder {
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port))?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self { socket })
+    }
+
+    pub fn local_port(&self) -> io::Result<u16> {
+        Ok(self.socket.local_addr()?.port())
+    }
+
+    /// Answers every query received since the last call, returns how many were answered.
+    pub fn respond(&self, announcement: &ServerAnnouncement) -> io::Result<usize> {
+        let queries = receive_all(&self.socket)?
+            .into_iter()
+            .filter(|(_, message)| *message == DiscoveryMessage::Query)
+            .map(|(from, _)| from)
+            .collect::<Vec<_>>();
+        if queries.is_empty() {
+            return Ok(0);
+        }
+
+        let packet = DiscoveryMessage::Announce(announcement.clone()).encode();
+        let mut answered = 0;
+        for from in queries.iter() {
+            match self.socket.send_to(&packet, from) {
+                Ok(_) => answered += 1,
+                Err(err) => warn!("Cannot answer discovery query from {}: {}", from, err),
+            }
+        }
+
+        Ok(answered)
+    }
+}
+
+/// Client side, asks the LAN for servers and collects their answers.
+pub struct DiscoveryClient {
+    socket: UdpSocket,
+}
+
+impl DiscoveryClient {
+    pub fn bind() -> io::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self { socket })
+    }
+
+    /// Asks every server on the local network listening on `port`.
+    pub fn broadcast(&self, port: u16) -> io::Result<()> {
+        self.query(SocketAddr::from((Ipv4Addr::BROADCAST, port)))
+    }
+
+    pub fn query(&self, address: SocketAddr) -> io::Result<()> {
+        self.socket
+            .send_to(&DiscoveryMessage::Query.encode(), address)
+            .map(|_| ())
+    }
+
+    /// Answers received since the last call, with the game address of each server.
+    pub fn receive(&self) -> io::Result<Vec<(SocketAddr, ServerAnnouncement)>> {
+        let announcements = receive_all(&self.socket)?
+            .into_iter()
+            .filter_map(|(from, message)| match message {
+                DiscoveryMessage::Announce(announcement) => {
+                    Some((SocketAddr::new(from.ip(), announcement.port), announcement))
+                }
+                DiscoveryMessage::Query => None,
+            })
+            .collect();
+
+        Ok(announcements)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{thread, time::Duration};
+
+    fn announcement() -> ServerAnnouncement {
+        ServerAnnouncement {
+            name: "Tower".to_owned(),
+            port: 9001,
+            players: 2,
+            max_players: 8,
+            state: ServerState::Lobby,
+            version: GameVersion {
+                protocol: 1,
+                content_hash: 0,
+            },
+        }
+    }
+
+    #[test]
+    fn foreign_packets_are_ignored() {
+        let message = DiscoveryMessage::Announce(announcement());
+        assert_eq!(DiscoveryMessage::decode(&message.encode()), Some(message));
+        assert_eq!(DiscoveryMessage::decode(b"Query"), None);
+        assert_eq!(DiscoveryMessage::decode(b"WIZARDWARS garbage"), None);
+    }
+
+    #[test]
+    fn discover_server_on_loopback() {
+        let responder = DiscoveryResponder::bind(0).unwrap();
+        let port = responder.local_port().unwrap();
+        let client = DiscoveryClient::bind().unwrap();
+        client
+            .query(SocketAddr::from((Ipv4Addr::LOCALHOST, port)))
+            .unwrap();
+
+        let mut found = Vec::new();
+        for _ in 0..100 {
+            responder.respond(&announcement()).unwrap();
+            found.extend(client.receive().unwrap());
+            if !found.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(
+            found,
+            vec![("127.0.0.1:9001".parse().unwrap(), announcement())]
+        );
+    }
+}
@@ -1,6 +1,26 @@
+mod discovery;
+mod replication;
+mod snapshot;
+
 use crate::components::Client;
 use serde::{Deserialize, Serialize};
 
+pub use discovery::{DiscoveryClient, DiscoveryResponder, ServerAnnouncement, DISCOVERY_PORT};
+pub use replication::{
+    ComponentUpdate, NetworkRole, Reliability, Replicate, ReplicationLog, ReplicationRegistry,
+    ReplicationSettings, ResyncClient, FIRST_REPLICATION_CHANNEL,
+};
+pub use snapshot::{
+    EntityDelta, EntitySnapshot, FieldDelta, SnapshotAck, SnapshotAssembler, SnapshotDelta,
+    SnapshotHistory, WorldSnapshot, MAX_SNAPSHOT_MESSAGE_SIZE,
+};
+
+/// Number of the server update a replicated value was sampled at.
+#[derive(
+    Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub struct Tick(pub u32);
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Dest {
     Single(Client),
@@ -0,0 +1,412 @@
+use super::Dest;
+use crate::components::{Client, Uuid};
+use bevy::{ecs::component::Component, prelude::*};
+use bevy_networking_turbulence::{
+    ConnectionChannelsBuilder, MessageChannelMode, MessageChannelSettings, NetworkResource,
+    ReliableChannelSettings,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::HashMap, marker::PhantomData, sync::Arc, time::Duration};
+
+/// Channels below this one are reserved for the fixed protocol messages.
+pub const FIRST_REPLICATION_CHANNEL: u8 = 8;
+
+/// Updates for entities that are still not spawned after this many frames are dropped, the
+/// entity was despawned already or never will be.
+const MAX_PENDING_FRAMES: u32 = 10;
+
+/// Which side of the connection the app is, decides whether replicated components are sent or
+/// received.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NetworkRole {
+    Server,
+    Client,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Reliability {
+    Reliable,
+    Unreliable,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ReplicationSettings {
+    pub reliability: Reliability,
+    /// Only send the component to the client owning the entity.
+    pub owner_only: bool,
+}
+
+impl Default for ReplicationSettings {
+    fn default() -> Self {
+        Self {
+            reliability: Reliability::Reliable,
+            owner_only: false,
+        }
+    }
+}
+
+impl ReplicationSettings {
+    pub fn unreliable() -> Self {
+        Self {
+            reliability: Reliability::Unreliable,
+            ..Default::default()
+        }
+    }
+
+    pub fn owner_only(self) -> Self {
+        Self {
+            owner_only: true,
+            ..self
+        }
+    }
+
+    fn channel_settings(&self, channel: u8) -> MessageChannelSettings {
+        match self.reliability {
+            Reliability::Reliable => MessageChannelSettings {
+                channel,
+                channel_mode: MessageChannelMode::Reliable {
+                    reliability_settings: ReliableChannelSettings {
+                        bandwidth: 4096,
+                        recv_window_size: 1024,
+                        send_window_size: 1024,
+                        burst_bandwidth: 1024,
+                        init_send: 512,
+                        wakeup_time: Duration::from_millis(100),
+                        initial_rtt: Duration::from_millis(200),
+                        max_rtt: Duration::from_secs(2),
+                        rtt_update_factor: 0.1,
+                        rtt_resend_factor: 1.5,
+                    },
+                    max_message_len: 1024,
+                },
+                message_buffer_size: 64,
+                packet_buffer_size: 64,
+            },
+            Reliability::Unreliable => MessageChannelSettings {
+                channel,
+                channel_mode: MessageChannelMode::Unreliable,
+                message_buffer_size: 128,
+                packet_buffer_size: 128,
+            },
+        }
+    }
+}
+
+type ChannelRegistration = dyn Fn(&mut ConnectionChannelsBuilder) + Send + Sync;
+
+/// Channels of all replicated components, in the order they were registered. Both sides have to
+/// register the same components in the same order.
+#[derive(Clone, Default)]
+pub struct ReplicationRegistry {
+    registrations: Vec<Arc<ChannelRegistration>>,
+}
+
+impl ReplicationRegistry {
+    pub fn register_channels(&self, builder: &mut ConnectionChannelsBuilder) {
+        for registration in self.registrations.iter() {
+            registration(builder);
+        }
+    }
+
+    fn next_channel(&self) -> u8 {
+        FIRST_REPLICATION_CHANNEL + self.registrations.len() as u8
+    }
+}
+
+/// Sends the current value of every replicated component to a client that just joined or
+/// reconnected.
+pub struct ResyncClient(pub Client);
+
+/// A component value for the entity with the given id, `None` when it was removed.
+type ReplicationMessage<C> = (Uuid, Option<C>);
+
+/// A replication message encoded for the channel of its component.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ComponentUpdate {
+    pub channel: u8,
+    pub dest: Dest,
+    pub data: Vec<u8>,
+}
+
+/// Component updates passed on outside of the network. While the resource exists the server
+/// logs every update it sends, on the client the updates in it are applied like received ones.
+#[derive(Debug, Default)]
+pub struct ReplicationLog {
+    updates: Vec<ComponentUpdate>,
+}
+
+impl ReplicationLog {
+    pub fn push(&mut self, update: ComponentUpdate) {
+        self.updates.push(update);
+    }
+
+    pub fn drain(&mut self) -> Vec<ComponentUpdate> {
+        std::mem::take(&mut self.updates)
+    }
+
+    fn take(&mut self, channel: u8) -> Vec<ComponentUpdate> {
+        let (taken, rest) = self
+            .drain()
+            .into_iter()
+            .partition(|update| update.channel == channel);
+        self.updates = rest;
+
+        taken
+    }
+}
+
+struct ComponentReplication<C> {
+    settings: ReplicationSettings,
+    channel: u8,
+    marker: PhantomData<C>,
+}
+
+pub trait Replicate {
+    fn replicate<C>(&mut self) -> &mut Self
+    where
+        C: Component + Clone + Serialize + DeserializeOwned;
+
+    fn replicate_with<C>(&mut self, settings: ReplicationSettings) -> &mut Self
+    where
+        C: Component + Clone + Serialize + DeserializeOwned;
+}
+
+impl Replicate for AppBuilder {
+    fn replicate<C>(&mut self) -> &mut Self
+    where
+        C: Component + Clone + Serialize + DeserializeOwned,
+    {
+        self.replicate_with::<C>(ReplicationSettings::default())
+    }
+
+    fn replicate_with<C>(&mut self, settings: ReplicationSettings) -> &mut Self
+    where
+        C: Component + Clone + Serialize + DeserializeOwned,
+    {
+        let mut registry = self
+            .world_mut()
+            .get_resource_or_insert_with(ReplicationRegistry::default);
+        let channel = registry.next_channel();
+        let channel_settings = settings.channel_settings(channel);
+        registry.registrations.push(Arc::new(move |builder| {
+            builder
+                .register::<ReplicationMessage<C>>(channel_settings.clone())
+                .unwrap();
+        }));
+
+        if !self.world().contains_resource::<Events<ResyncClient>>() {
+            self.add_event::<ResyncClient>();
+        }
+        self.insert_resource(ComponentReplication::<C> {
+            settings,
+            channel,
+            marker: PhantomData,
+        })
+        .add_system_to_stage(CoreStage::PreUpdate, receive_component_system::<C>.system())
+        .add_system_to_stage(CoreStage::Last, send_component_system::<C>.system())
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn send_component_system<C>(
+    role: Res<NetworkRole>,
+    replication: Res<ComponentReplication<C>>,
+    mut net: ResMut<NetworkResource>,
+    mut log: Option<ResMut<ReplicationLog>>,
+    mut resyncs: EventReader<ResyncClient>,
+    changed: Query<(&Uuid, &C, Option<&Client>), Changed<C>>,
+    removed: RemovedComponents<C>,
+    entities: Query<(&Uuid, Option<&Client>)>,
+    all: Query<(&Uuid, &C, Option<&Client>)>,
+) where
+    C: Component + Clone + Serialize + DeserializeOwned,
+{
+    if *role != NetworkRole::Server {
+        return;
+    }
+
+    for ResyncClient(client) in resyncs.iter() {
+        for (id, component, owner) in all.iter() {
+            if !replication.settings.owner_only || owner == Some(client) {
+                let _ = net.send_message(client.0, (*id, Some(component.clone())));
+            }
+        }
+    }
+
+    let mut send = |message: ReplicationMessage<C>, owner: Option<&Client>| {
+        let dest = if !replication.settings.owner_only {
+            Dest::All
+        } else if let Some(&owner) = owner {
+            Dest::Single(owner)
+        } else {
+            return;
+        };
+        if let Some(log) = log.as_mut() {
+            log.push(ComponentUpdate {
+                channel: replication.channel,
+                dest: dest.clone(),
+                data: bincode::serialize(&message)
+                    .expect("Replicated components are always serializable"),
+            });
+        }
+
+        match dest {
+            Dest::Single(owner) => {
+                let _ = net.send_message(owner.0, message);
+            }
+            _ => net.broadcast_message(message),
+        }
+    };
+    for (id, component, owner) in changed.iter() {
+        send((*id, Some(component.clone())), owner);
+    }
+    // Despawned entities are not found anymore, the client despawns them on its own.
+    for entity in removed.iter() {
+        if let Ok((id, owner)) = entities.get(entity) {
+            send((*id, None), owner);
+        }
+    }
+}
+
+/// Received values waiting for their entity, with the number of frames they have waited.
+struct PendingUpdates<C> {
+    updates: HashMap<Uuid, (Option<C>, u32)>,
+}
+
+impl<C> Default for PendingUpdates<C> {
+    fn default() -> Self {
+        Self {
+            updates: HashMap::new(),
+        }
+    }
+}
+
+impl<C> PendingUpdates<C> {
+    fn insert(&mut self, id: Uuid, component: Option<C>) {
+        self.updates.insert(id, (component, 0));
+    }
+
+    fn take(&mut self, id: &Uuid) -> Option<Option<C>> {
+        self.updates.remove(id).map(|(component, _)| component)
+    }
+
+    /// Ages the updates that are left and drops the ones that waited too long.
+    fn age(&mut self) {
+        self.updates.retain(|_, (_, frames)| {
+            *frames += 1;
+            *frames <= MAX_PENDING_FRAMES
+        });
+    }
+
+    fn is_empty(&self) -> bool {
+        self.updates.is_empty()
+    }
+}
+
+/// Applies received values to the entity with the matching id. Values for entities that were
+/// not spawned yet are kept for a few frames in case they are.
+fn receive_component_system<C>(
+    mut cmd: Commands,
+    role: Res<NetworkRole>,
+    replication: Res<ComponentReplication<C>>,
+    mut net: ResMut<NetworkResource>,
+    mut log: Option<ResMut<ReplicationLog>>,
+    mut pending: Local<PendingUpdates<C>>,
+    query: Query<(Entity, &Uuid)>,
+) where
+    C: Component + Clone + Serialize + DeserializeOwned,
+{
+    if *role != NetworkRole::Client {
+        return;
+    }
+
+    for (_, connection) in net.connections.iter_mut() {
+        let channels = connection.channels().unwrap();
+        while let Some((id, component)) = channels.recv::<ReplicationMessage<C>>() {
+            pending.insert(id, component);
+        }
+    }
+    if let Some(log) = log.as_mut() {
+        for update in log.take(replication.channel) {
+            match bincode::deserialize::<ReplicationMessage<C>>(&update.data) {
+                Ok((id, component)) => {
+                    pending.insert(id, component);
+                }
+                Err(err) => warn!("Cannot decode component update: {}", err),
+            }
+        }
+    }
+    if pending.is_empty() {
+        return;
+    }
+
+    for (entity, id) in query.iter() {
+        match pending.take(id) {
+            Some(Some(component)) => {
+                cmd.entity(entity).insert(component);
+            }
+            Some(None) => {
+                cmd.entity(entity).remove::<C>();
+            }
+            None => {}
+        }
+    }
+    pending.age();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{Dead, Health};
+
+    #[test]
+    fn channels_are_assigned_in_order() {
+        let mut app = App::build();
+        app.replicate::<Health>()
+            .replicate_with::<Dead>(ReplicationSettings::unreliable().owner_only());
+
+        let world = app.world();
+        let registry = world.get_resource::<ReplicationRegistry>().unwrap();
+        assert_eq!(registry.registrations.len(), 2);
+        assert_eq!(registry.next_channel(), FIRST_REPLICATION_CHANNEL + 2);
+
+        let dead = world.get_resource::<ComponentReplication<Dead>>().unwrap();
+        assert_eq!(dead.settings.reliability, Reliability::Unreliable);
+        assert!(dead.settings.owner_only);
+        assert_eq!(dead.channel, FIRST_REPLICATION_CHANNEL + 1);
+    }
+
+    #[test]
+    fn pending_updates_expire() {
+        let mut pending = PendingUpdates::<Dead>::default();
+        pending.insert(Uuid(1), Some(Dead));
+        for _ in 0..MAX_PENDING_FRAMES {
+            pending.age();
+        }
+        pending.insert(Uuid(2), None);
+        pending.age();
+
+        assert!(pending.take(&Uuid(1)).is_none());
+        assert!(matches!(pending.take(&Uuid(2)), Some(None)));
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn log_hands_out_updates_by_channel() {
+        let update = |channel| ComponentUpdate {
+            channel,
+            dest: Dest::All,
+            data: vec![channel],
+        };
+        let mut log = ReplicationLog::default();
+        log.push(update(8));
+        log.push(update(9));
+        log.push(update(8));
+
+        let taken = log.take(8);
+        assert_eq!(taken.len(), 2);
+        assert!(taken.iter().all(|update| update.channel == 8));
+        assert_eq!(log.drain().len(), 1);
+        assert!(log.drain().is_empty());
+    }
+}
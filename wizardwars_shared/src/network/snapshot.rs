@@ -0,0 +1,406 @@
+use super::Tick;
+use crate::components::{Cooldowns, Mana, Position, Uuid};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+/// Largest serialized snapshot message, bigger deltas are split so every part fits into a
+/// single datagram.
+pub const MAX_SNAPSHOT_MESSAGE_SIZE: u64 = 1024;
+
+/// Incomplete split deltas are dropped once parts of this many ticks are waiting.
+const MAX_PENDING_TICKS: usize = 8;
+
+fn serialized_size<T: Serialize>(value: &T) -> u64 {
+    bincode::serialized_size(value).expect("Snapshots are always serializable")
+}
+
+/// Frequently changing components of a single entity at one tick, everything else is
+/// replicated through its own channel.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct EntitySnapshot {
+    pub position: Option<Position>,
+    pub mana: Option<Mana>,
+    /// Part of the snapshot, which is resent until acked, so the final empty cooldowns cannot
+    /// get lost.
+    pub cooldowns: Option<Cooldowns>,
+}
+
+/// State of every replicated entity at one server tick.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct WorldSnapshot {
+    pub tick: Tick,
+    pub entities: BTreeMap<Uuid, EntitySnapshot>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum FieldDelta<T> {
+    Unchanged,
+    Changed(T),
+    Removed,
+}
+
+impl<T: Clone + PartialEq> FieldDelta<T> {
+    fn between(baseline: Option<&T>, current: Option<&T>) -> Self {
+        match (baseline, current) {
+            (Some(old), Some(new)) if old == new => FieldDelta::Unchanged,
+            (None, None) => FieldDelta::Unchanged,
+            (_, Some(new)) => FieldDelta::Changed(new.clone()),
+            (Some(_), None) => FieldDelta::Removed,
+        }
+    }
+
+    fn apply(self, field: &mut Option<T>) {
+        match self {
+            FieldDelta::Unchanged => {}
+            FieldDelta::Changed(value) => *field = Some(value),
+            FieldDelta::Removed => *field = None,
+        }
+    }
+
+    fn is_unchanged(&self) -> bool {
+        matches!(self, FieldDelta::Unchanged)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EntityDelta {
+    pub position: FieldDelta<Position>,
+    pub mana: FieldDelta<Mana>,
+    pub cooldowns: FieldDelta<Cooldowns>,
+}
+
+impl EntityDelta {
+    fn between(baseline: Option<&EntitySnapshot>, current: &EntitySnapshot) -> Self {
+        let old = baseline.cloned().unwrap_or_default();
+        Self {
+            position: FieldDelta::between(old.position.as_ref(), current.position.as_ref()),
+            mana: FieldDelta::between(old.mana.as_ref(), current.mana.as_ref()),
+            cooldowns: FieldDelta::between(old.cooldowns.as_ref(), current.cooldowns.as_ref()),
+        }
+    }
+
+    fn apply(self, entity: &mut EntitySnapshot) {
+        self.position.apply(&mut entity.position);
+        self.mana.apply(&mut entity.mana);
+        self.cooldowns.apply(&mut entity.cooldowns);
+    }
+
+    fn is_unchanged(&self) -> bool {
+        self.position.is_unchanged() && self.mana.is_unchanged() && self.cooldowns.is_unchanged()
+    }
+}
+
+/// Difference between a snapshot and the one the client acknowledged last. Without a
+/// baseline it contains the full world.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SnapshotDelta {
+    pub tick: Tick,
+    pub baseline: Option<Tick>,
+    /// Index of this part when the delta was split to fit into datagrams.
+    pub part: u16,
+    pub parts: u16,
+    pub changed: Vec<(Uuid, EntityDelta)>,
+    pub removed: Vec<Uuid>,
+}
+
+impl SnapshotDelta {
+    /// Splits the delta into parts that serialize to at most `max_size` bytes. A part only
+    /// goes over it when a single entity does.
+    pub fn split(self, max_size: u64) -> Vec<SnapshotDelta> {
+        let header = SnapshotDelta {
+            tick: self.tick,
+            baseline: self.baseline,
+            part: 0,
+            parts: 1,
+            changed: Vec::new(),
+            removed: Vec::new(),
+        };
+        let header_size = serialized_size(&header);
+
+        let mut parts = Vec::new();
+        let mut current = header.clone();
+        let mut size = header_size;
+        for id in self.removed {
+            let item_size = serialized_size(&id);
+            if size + item_size > max_size && !current.is_empty() {
+                parts.push(std::mem::replace(&mut current, header.clone()));
+                size = header_size;
+            }
+            size += item_size;
+            current.removed.push(id);
+        }
+        for change in self.changed {
+            let item_size = serialized_size(&change);
+            if size + item_size > max_size && !current.is_empty() {
+                parts.push(std::mem::replace(&mut current, header.clone()));
+                size = header_size;
+            }
+            size += item_size;
+            current.changed.push(change);
+        }
+        parts.push(current);
+
+        let count = parts.len() as u16;
+        for (index, part) in parts.iter_mut().enumerate() {
+            part.part = index as u16;
+            part.parts = count;
+        }
+
+        parts
+    }
+
+    fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.removed.is_empty()
+    }
+
+    /// Rebuilds the full snapshot, `None` if `baseline` is not the one the delta was made against.
+    pub fn apply(self, baseline: Option<&WorldSnapshot>) -> Option<WorldSnapshot> {
+        let mut entities = match (self.baseline, baseline) {
+            (None, _) => BTreeMap::new(),
+            (Some(tick), Some(baseline)) if baseline.tick == tick => baseline.entities.clone(),
+            _ => return None,
+        };
+
+        for id in self.removed {
+            entities.remove(&id);
+        }
+        for (id, delta) in self.changed {
+            delta.apply(entities.entry(id).or_default());
+        }
+
+        Some(WorldSnapshot {
+            tick: self.tick,
+            entities,
+        })
+    }
+}
+
+impl WorldSnapshot {
+    pub fn new(tick: Tick) -> Self {
+        Self {
+            tick,
+            entities: BTreeMap::new(),
+        }
+    }
+
+    pub fn delta_from(&self, baseline: Option<&WorldSnapshot>) -> SnapshotDelta {
+        let empty = BTreeMap::new();
+        let old_entities = baseline.map_or(&empty, |baseline| &baseline.entities);
+
+        let changed = self
+            .entities
+            .iter()
+            .map(|(id, entity)| (*id, EntityDelta::between(old_entities.get(id), entity)))
+            .filter(|(id, delta)| !old_entities.contains_key(id) || !delta.is_unchanged())
+            .collect();
+        let removed = old_entities
+            .keys()
+            .filter(|id| !self.entities.contains_key(id))
+            .copied()
+            .collect();
+
+        SnapshotDelta {
+            tick: self.tick,
+            baseline: baseline.map(|baseline| baseline.tick),
+            part: 0,
+            parts: 1,
+            changed,
+            removed,
+        }
+    }
+}
+
+/// Puts split deltas back together.
+#[derive(Debug, Default)]
+pub struct SnapshotAssembler {
+    pending: BTreeMap<Tick, Vec<SnapshotDelta>>,
+}
+
+impl SnapshotAssembler {
+    /// Returns the whole delta once all of its parts arrived. Unfinished older deltas are
+    /// dropped then, they would be out of date anyway.
+    pub fn push(&mut self, delta: SnapshotDelta) -> Option<SnapshotDelta> {
+        let tick = delta.tick;
+        if delta.parts > 1 {
+            let parts = self.pending.entry(tick).or_default();
+            if parts.iter().all(|part| part.part != delta.part) {
+                parts.push(delta);
+            }
+            if parts.len() < parts[0].parts as usize {
+                while self.pending.len() > MAX_PENDING_TICKS {
+                    let oldest = *self.pending.keys().next()?;
+                    self.pending.remove(&oldest);
+                }
+                return None;
+            }
+        } else {
+            self.pending.insert(tick, vec![delta]);
+        }
+
+        let mut parts = self.pending.remove(&tick)?;
+        self.pending = self.pending.split_off(&tick);
+        parts.sort_by_key(|part| part.part);
+
+        let mut parts = parts.into_iter();
+        let mut whole = parts.next()?;
+        for part in parts {
+            whole.changed.extend(part.changed);
+            whole.removed.extend(part.removed);
+        }
+        whole.part = 0;
+        whole.parts = 1;
+
+        Some(whole)
+    }
+}
+
+/// Acknowledges the newest snapshot a client has rebuilt, the next delta is made against it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotAck(pub Tick);
+
+/// The most recent snapshots, oldest first.
+#[derive(Debug)]
+pub struct SnapshotHistory {
+    snapshots: VecDeque<WorldSnapshot>,
+    capacity: usize,
+}
+
+impl Default for SnapshotHistory {
+    fn default() -> Self {
+        Self::with_capacity(64)
+    }
+}
+
+impl SnapshotHistory {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            snapshots: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, snapshot: WorldSnapshot) {
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    pub fn get(&self, tick: Tick) -> Option<&WorldSnapshot> {
+        self.snapshots
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.tick == tick)
+    }
+
+    pub fn latest(&self) -> Option<&WorldSnapshot> {
+        self.snapshots.back()
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::math::Vec3;
+
+    fn snapshot(tick: u32, entities: Vec<(u32, Vec3)>) -> WorldSnapshot {
+        WorldSnapshot {
+            tick: Tick(tick),
+            entities: entities
+                .into_iter()
+                .map(|(id, position)| {
+                    let entity = EntitySnapshot {
+                        position: Some(Position(position)),
+                        ..Default::default()
+                    };
+                    (Uuid(id), entity)
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn full_snapshot_without_baseline() {
+        let current = snapshot(1, vec![(0, Vec3::ZERO), (1, Vec3::ONE)]);
+        let delta = current.delta_from(None);
+
+        assert_eq!(delta.changed.len(), 2);
+        assert_eq!(delta.apply(None), Some(current));
+    }
+
+    #[test]
+    fn delta_contains_only_changes() {
+        let baseline = snapshot(1, vec![(0, Vec3::ZERO), (1, Vec3::ONE), (2, Vec3::ONE)]);
+        let current = snapshot(2, vec![(0, Vec3::ZERO), (1, Vec3::X), (3, Vec3::Y)]);
+        let delta = current.delta_from(Some(&baseline));
+
+        assert_eq!(delta.baseline, Some(Tick(1)));
+        assert_eq!(delta.removed, vec![Uuid(2)]);
+        let changed = delta.changed.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        assert_eq!(changed, vec![Uuid(1), Uuid(3)]);
+
+        assert_eq!(delta.apply(Some(&baseline)), Some(current));
+    }
+
+    #[test]
+    fn removed_components_are_encoded() {
+        let baseline = snapshot(1, vec![(0, Vec3::ZERO)]);
+        let mut current = WorldSnapshot::new(Tick(2));
+        current.entities.insert(Uuid(0), EntitySnapshot::default());
+        let delta = current.delta_from(Some(&baseline));
+
+        assert_eq!(delta.changed[0].1.position, FieldDelta::Removed);
+        assert_eq!(delta.apply(Some(&baseline)), Some(current));
+    }
+
+    #[test]
+    fn reject_wrong_baseline() {
+        let baseline = snapshot(1, vec![(0, Vec3::ZERO)]);
+        let other = snapshot(5, vec![(0, Vec3::ZERO)]);
+        let delta = snapshot(6, vec![(0, Vec3::ONE)]).delta_from(Some(&baseline));
+
+        assert_eq!(delta.clone().apply(Some(&other)), None);
+        assert_eq!(delta.apply(None), None);
+    }
+
+    #[test]
+    fn split_deltas_fit_and_reassemble() {
+        let baseline = snapshot(1, (0..40).map(|id| (id, Vec3::ZERO)).collect());
+        let current = snapshot(2, (20..100).map(|id| (id, Vec3::ONE)).collect());
+        let delta = current.delta_from(Some(&baseline));
+
+        let parts = delta.clone().split(256);
+        assert!(parts.len() > 1);
+        assert!(parts.iter().all(|part| serialized_size(part) <= 256));
+
+        let mut assembler = SnapshotAssembler::default();
+        let mut assembled = None;
+        for part in parts.into_iter().rev() {
+            assert!(assembled.is_none());
+            assembled = assembler.push(part);
+        }
+        assert_eq!(
+            assembled.and_then(|delta| delta.apply(Some(&baseline))),
+            Some(current)
+        );
+    }
+
+    #[test]
+    fn history_keeps_latest_snapshots() {
+        let mut history = SnapshotHistory::with_capacity(2);
+        history.push(WorldSnapshot::new(Tick(1)));
+        history.push(WorldSnapshot::new(Tick(2)));
+        history.push(WorldSnapshot::new(Tick(3)));
+
+        assert!(history.get(Tick(1)).is_none());
+        assert!(history.get(Tick(2)).is_some());
+        assert_eq!(
+            history.latest().map(|snapshot| snapshot.tick),
+            Some(Tick(3))
+        );
+    }
+}
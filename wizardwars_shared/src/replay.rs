@@ -0,0 +1,202 @@
+use crate::{
+    messages::server_messages::ServerMessage,
+    network::{ComponentUpdate, Pack, SnapshotDelta, Tick},
+    version::GameVersion,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Formatter,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+pub const REPLAY_EXTENSION: &str = "replay";
+/// Every replay file starts with this.
+const MAGIC: &[u8] = b"WWREPLAY";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplayHeader {
+    pub version: GameVersion,
+    pub tick_rate: u32,
+}
+
+/// Something the server sent, as it left the server.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ReplayRecord {
+    Packet(Pack<ServerMessage>),
+    /// Delta against the previous recorded snapshot, the first one has no baseline.
+    Snapshot(SnapshotDelta),
+    Component(ComponentUpdate),
+}
+
+/// Everything sent during one server tick.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReplayFrame {
+    pub tick: Tick,
+    pub records: Vec<ReplayRecord>,
+}
+
+/// Writes frames to a replay file as the match goes on.
+pub struct ReplayWriter<W: Write> {
+    writer: W,
+}
+
+impl ReplayWriter<BufWriter<File>> {
+    pub fn create(path: &Path, header: &ReplayHeader) -> Result<Self, ReplayError> {
+        let file = File::create(path).map_err(|err| ReplayError::Open(path.to_owned(), err))?;
+        Self::new(BufWriter::new(file), header)
+    }
+}
+
+impl<W: Write> ReplayWriter<W> {
+    pub fn new(mut writer: W, header: &ReplayHeader) -> Result<Self, ReplayError> {
+        writer.write_all(MAGIC).map_err(ReplayError::Io)?;
+        bincode::serialize_into(&mut writer, header)
+            .map_err(|err| ReplayError::Encoding(err.to_string()))?;
+
+        Ok(Self { writer })
+    }
+
+    pub fn write(&mut self, frame: &ReplayFrame) -> Result<(), ReplayError> {
+        bincode::serialize_into(&mut self.writer, frame)
+            .map_err(|err| ReplayError::Encoding(err.to_string()))
+    }
+
+    pub fn flush(&mut self) -> Result<(), ReplayError> {
+        self.writer.flush().map_err(ReplayError::Io)
+    }
+}
+
+/// A recorded match.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pub header: ReplayHeader,
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    pub fn from_file(path: &Path) -> Result<Self, ReplayError> {
+        let file = File::open(path).map_err(|err| ReplayError::Open(path.to_owned(), err))?;
+        Self::from_reader(file)
+    }
+
+    pub fn from_reader(reader: impl Read) -> Result<Self, ReplayError> {
+        let mut reader = BufReader::new(reader);
+        let mut magic = [0; MAGIC.len()];
+        reader
+            .read_exact(&mut magic)
+            .map_err(|_| ReplayError::NotAReplay)?;
+        if magic != MAGIC {
+            return Err(ReplayError::NotAReplay);
+        }
+
+        let decode = |err: bincode::Error| ReplayError::Encoding(err.to_string());
+        let header = bincode::deserialize_from(&mut reader).map_err(decode)?;
+        let mut frames = Vec::new();
+        while !at_end(&mut reader).map_err(ReplayError::Io)? {
+            frames.push(bincode::deserialize_from(&mut reader).map_err(decode)?);
+        }
+
+        Ok(Self { header, frames })
+    }
+
+    pub fn first_tick(&self) -> Tick {
+        self.frames
+            .first()
+            .map_or_else(Tick::default, |frame| frame.tick)
+    }
+
+    /// Ticks between the first and the last frame.
+    pub fn duration(&self) -> u32 {
+        self.frames
+            .last()
+            .map_or(0, |frame| frame.tick.0.wrapping_sub(self.first_tick().0))
+    }
+}
+
+fn at_end(reader: &mut impl BufRead) -> io::Result<bool> {
+    Ok(reader.fill_buf()?.is_empty())
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Open(PathBuf, io::Error),
+    Io(io::Error),
+    NotAReplay,
+    Encoding(String),
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::Open(path, err) => write!(f, "Cannot open {}: {}", path.display(), err),
+            ReplayError::Io(err) => write!(f, "{}", err),
+            ReplayError::NotAReplay => write!(f, "Not a replay file"),
+            ReplayError::Encoding(err) => write!(f, "Broken replay: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{components::Uuid, network::WorldSnapshot};
+
+    fn header() -> ReplayHeader {
+        ReplayHeader {
+            version: GameVersion {
+                protocol: 1,
+                content_hash: 2,
+            },
+            tick_rate: 60,
+        }
+    }
+
+    #[test]
+    fn frames_survive_the_round_trip() {
+        let frames = vec![
+            ReplayFrame {
+                tick: Tick(10),
+                records: vec![ReplayRecord::Snapshot(
+                    WorldSnapshot::new(Tick(10)).delta_from(None),
+                )],
+            },
+            ReplayFrame {
+                tick: Tick(25),
+                records: vec![ReplayRecord::Packet(Pack::all(ServerMessage::Despawn(
+                    Uuid(3),
+                )))],
+            },
+        ];
+        let mut bytes = Vec::new();
+        let mut writer = ReplayWriter::new(&mut bytes, &header()).unwrap();
+        for frame in frames.iter() {
+            writer.write(frame).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let replay = Replay::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(replay.header, header());
+        assert_eq!(replay.frames.len(), 2);
+        assert_eq!(replay.first_tick(), Tick(10));
+        assert_eq!(replay.duration(), 15);
+        assert!(matches!(
+            replay.frames[1].records[0],
+            ReplayRecord::Packet(Pack {
+                msg: ServerMessage::Despawn(Uuid(3)),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn reject_other_files() {
+        assert!(matches!(
+            Replay::from_reader(&b"(spells: [])"[..]),
+            Err(ReplayError::NotAReplay)
+        ));
+    }
+}
@@ -0,0 +1,322 @@
+use crate::{assets::resolve_path, components::StatusEffect, validation::non_negative};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt::Formatter,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Looked up with [`resolve_path`], so it also works next to the installed executable.
+pub const DEFAULT_SPELLS_PATH: &str = "assets/spells.ron";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+pub struct SpellId(pub String);
+
+impl SpellId {
+    pub fn new(id: &str) -> Self {
+        Self(id.to_owned())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EffectTarget {
+    /// Whoever the projectile hits.
+    Hit,
+    /// The caster, as soon as the spell is released.
+    Caster,
+}
+
+impl Default for EffectTarget {
+    fn default() -> Self {
+        EffectTarget::Hit
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SpellEffect {
+    pub effect: StatusEffect,
+    /// Seconds the effect lasts.
+    pub duration: f32,
+    #[serde(default)]
+    pub target: EffectTarget,
+}
+
+impl SpellEffect {
+    fn validate(&self) -> Result<(), String> {
+        if !self.duration.is_finite() || self.duration <= 0.0 {
+            return Err(format!(
+                "effect duration must be greater than 0, got {}",
+                self.duration
+            ));
+        }
+        match self.effect {
+            StatusEffect::Slow(amount) if !(0.0..=1.0).contains(&amount) => {
+                Err(format!("slow must be between 0 and 1, got {}", amount))
+            }
+            StatusEffect::Burn(0) | StatusEffect::Shield(0) => {
+                Err(format!("{:?} does nothing", self.effect))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Explosion {
+    pub radius: f32,
+    /// Damage at the centre, it falls off linearly towards the edge.
+    pub damage: u32,
+    /// Impulse at the centre, it falls off like the damage.
+    pub knockback: f32,
+}
+
+/// How the projectile of a spell moves and what happens when it hits something. Without any
+/// behaviour it flies straight and is destroyed by the first thing it touches.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectileBehaviour {
+    /// Radians per second the projectile turns towards the nearest enemy.
+    pub homing: f32,
+    /// Targets the projectile passes through before it is destroyed.
+    pub pierce: u32,
+    /// Explosion when the projectile is destroyed or runs out of time.
+    pub explosion: Option<Explosion>,
+}
+
+impl ProjectileBehaviour {
+    fn validate(&self) -> Result<(), String> {
+        non_negative("homing", self.homing)?;
+        if let Some(explosion) = &self.explosion {
+            if !explosion.radius.is_finite() || explosion.radius <= 0.0 {
+                return Err(format!(
+                    "explosion radius must be greater than 0, got {}",
+                    explosion.radius
+                ));
+            }
+            non_negative("explosion knockback", explosion.knockback)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SpellDefinition {
+    pub id: SpellId,
+    pub name: String,
+    pub damage: u32,
+    /// Impulse given to an unhurt target.
+    pub knockback: f32,
+    pub speed: f32,
+    pub radius: f32,
+    pub lifetime: f32,
+    pub cast_time: f32,
+    pub cooldown: f32,
+    #[serde(default)]
+    pub mana_cost: f32,
+    /// Shop price, spells that cost nothing are known by every player.
+    #[serde(default)]
+    pub price: u32,
+    /// Status effects applied by the spell.
+    #[serde(default)]
+    pub effects: Vec<SpellEffect>,
+    #[serde(default)]
+    pub projectile: ProjectileBehaviour,
+}
+
+impl SpellDefinition {
+    fn validate(&self) -> Result<(), SpellsError> {
+        let fields = [
+            ("knockback", self.knockback),
+            ("speed", self.speed),
+            ("radius", self.radius),
+            ("lifetime", self.lifetime),
+            ("cast_time", self.cast_time),
+            ("cooldown", self.cooldown),
+            ("mana_cost", self.mana_cost),
+        ];
+        for &(field, value) in fields.iter() {
+            non_negative(field, value)
+                .map_err(|reason| SpellsError::Invalid(format!("{}: {}", self.id.0, reason)))?;
+        }
+        if self.radius <= 0.0 || self.lifetime <= 0.0 {
+            return Err(SpellsError::Invalid(format!(
+                "{}: radius and lifetime must be greater than 0",
+                self.id.0
+            )));
+        }
+        for effect in self.effects.iter() {
+            effect
+                .validate()
+                .map_err(|reason| SpellsError::Invalid(format!("{}: {}", self.id.0, reason)))?;
+        }
+        self.projectile
+            .validate()
+            .map_err(|reason| SpellsError::Invalid(format!("{}: {}", self.id.0, reason)))?;
+
+        Ok(())
+    }
+}
+
+/// Registry of all spells available in a match, in the order they are
+/// defined in the spells file.
+#[derive(Debug, Clone, Default)]
+pub struct Spells {
+    definitions: Vec<SpellDefinition>,
+    indices: HashMap<SpellId, usize>,
+}
+
+impl Spells {
+    pub fn from_definitions(definitions: Vec<SpellDefinition>) -> Result<Self, SpellsError> {
+        let mut indices = HashMap::new();
+        for (index, definition) in definitions.iter().enumerate() {
+            definition.validate()?;
+            if indices.insert(definition.id.clone(), index).is_some() {
+                return Err(SpellsError::Invalid(format!(
+                    "duplicate spell id: {}",
+                    definition.id.0
+                )));
+            }
+        }
+
+        Ok(Self {
+            definitions,
+            indices,
+        })
+    }
+
+    pub fn from_ron_str(source: &str) -> Result<Self, SpellsError> {
+        let definitions =
+            ron::from_str(source).map_err(|err| SpellsError::Parse(err.to_string()))?;
+        Self::from_definitions(definitions)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, SpellsError> {
+        let source = fs::read_to_string(resolve_path(path))
+            .map_err(|err| SpellsError::Io(path.to_owned(), err))?;
+        Self::from_ron_str(&source)
+    }
+
+    pub fn get(&self, id: &SpellId) -> Option<&SpellDefinition> {
+        self.indices.get(id).map(|&index| &self.definitions[index])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SpellDefinition> {
+        self.definitions.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.definitions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.definitions.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub enum SpellsError {
+    Io(PathBuf, std::io::Error),
+    Parse(String),
+    Invalid(String),
+}
+
+impl std::fmt::Display for SpellsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpellsError::Io(path, err) => write!(f, "Cannot read {}: {}", path.display(), err),
+            SpellsError::Parse(err) => write!(f, "Cannot parse spells: {}", err),
+            SpellsError::Invalid(reason) => write!(f, "Invalid spell: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for SpellsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fireball() -> SpellDefinition {
+        SpellDefinition {
+            id: SpellId::new("fireball"),
+            name: "Fire Ball".to_owned(),
+            damage: 10,
+            knockback: 100.0,
+            speed: 5.0,
+            radius: 0.1,
+            lifetime: 5.0,
+            cast_time: 0.0,
+            cooldown: 1.0,
+            mana_cost: 10.0,
+            price: 0,
+            effects: Vec::new(),
+            projectile: ProjectileBehaviour::default(),
+        }
+    }
+
+    #[test]
+    fn default_spells_file_is_valid() {
+        let spells = Spells::from_file(Path::new(DEFAULT_SPELLS_PATH)).unwrap();
+
+        assert!(!spells.is_empty());
+        assert!(spells.get(&SpellId::new("fireball")).is_some());
+    }
+
+    #[test]
+    fn lookup_by_id() {
+        let spells = Spells::from_definitions(vec![fireball()]).unwrap();
+
+        assert_eq!(spells.len(), 1);
+        assert_eq!(spells.get(&SpellId::new("fireball")), Some(&fireball()));
+        assert_eq!(spells.get(&SpellId::new("frostbolt")), None);
+    }
+
+    #[test]
+    fn reject_duplicate_ids() {
+        let result = Spells::from_definitions(vec![fireball(), fireball()]);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn reject_invalid_values() {
+        let result = Spells::from_definitions(vec![SpellDefinition {
+            speed: -1.0,
+            ..fireball()
+        }]);
+        assert!(result.is_err());
+
+        let result = Spells::from_definitions(vec![SpellDefinition {
+            radius: 0.0,
+            ..fireball()
+        }]);
+        assert!(result.is_err());
+
+        let result = Spells::from_definitions(vec![SpellDefinition {
+            effects: vec![SpellEffect {
+                effect: StatusEffect::Slow(1.5),
+                duration: 1.0,
+                target: EffectTarget::Hit,
+            }],
+            ..fireball()
+        }]);
+        assert!(result.is_err());
+
+        let result = Spells::from_definitions(vec![SpellDefinition {
+            projectile: ProjectileBehaviour {
+                explosion: Some(Explosion {
+                    radius: 0.0,
+                    damage: 5,
+                    knockback: 1.0,
+                }),
+                ..Default::default()
+            },
+            ..fireball()
+        }]);
+        assert!(result.is_err());
+    }
+}
@@ -1,9 +1,107 @@
-use crate::components::{damage::Damage, Health};
+use crate::{
+    components::{
+        damage::{DamageKind, Resistances},
+        Dead, Health, StatusEffects,
+    },
+    events::{DamageApplied, DamageEvent, Killed},
+};
 use bevy::prelude::*;
 
-pub fn apply_damage_system(mut cmd: Commands, mut query: Query<(Entity, &mut Health, &Damage)>) {
-    for (entity, mut health, damage) in query.iter_mut() {
-        health.change_by(-(damage.amount() as i32));
-        cmd.entity(entity).remove::<Damage>();
+/// Health the target loses to the damage after its resistances and shields.
+fn damage_taken(
+    event: &DamageEvent,
+    health: &Health,
+    resistances: Option<&Resistances>,
+    effects: Option<&mut StatusEffects>,
+) -> u32 {
+    let mut amount = resistances.map_or(event.amount, |resistances| {
+        resistances.reduce(event.amount, event.kind)
+    });
+    if event.kind != DamageKind::Environment {
+        if let Some(effects) = effects {
+            amount = effects.absorb(amount);
+        }
+    }
+
+    amount.min(health.current())
+}
+
+#[allow(clippy::type_complexity)]
+pub fn apply_damage_system(
+    mut events: EventReader<DamageEvent>,
+    mut applied: EventWriter<DamageApplied>,
+    mut killed: EventWriter<Killed>,
+    mut targets: Query<
+        (
+            &mut Health,
+            Option<&Resistances>,
+            Option<&mut StatusEffects>,
+        ),
+        Without<Dead>,
+    >,
+) {
+    for event in events.iter() {
+        let (mut health, resistances, mut effects) = match targets.get_mut(event.target) {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+        if event.kind == DamageKind::Healing {
+            health.change_by(event.amount as i32);
+            continue;
+        }
+
+        let amount = damage_taken(event, &health, resistances, effects.as_deref_mut());
+        if amount == 0 {
+            continue;
+        }
+        health.change_by(-(amount as i32));
+        applied.send(DamageApplied {
+            target: event.target,
+            source: event.source,
+            amount,
+            kind: event.kind,
+        });
+        if health.should_die() {
+            killed.send(Killed {
+                target: event.target,
+                source: event.source,
+                kind: event.kind,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::StatusEffect;
+
+    #[test]
+    fn resistances_apply_before_shields() {
+        let target = Entity::new(0);
+        let resistances = Resistances {
+            armor: 0.5,
+            ..Default::default()
+        };
+        let mut effects = StatusEffects::default();
+        effects.add(StatusEffect::Shield(3), 5.0, None);
+
+        let hit = DamageEvent::new(target, 10, DamageKind::Melee);
+        let taken = damage_taken(
+            &hit,
+            &Health::new(20),
+            Some(&resistances),
+            Some(&mut effects),
+        );
+        assert_eq!(taken, 2);
+
+        let fall = DamageEvent::new(target, 30, DamageKind::Environment);
+        let taken = damage_taken(
+            &fall,
+            &Health::new(20),
+            Some(&resistances),
+            Some(&mut effects),
+        );
+        assert_eq!(taken, 20);
     }
 }
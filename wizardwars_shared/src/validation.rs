@@ -0,0 +1,28 @@
+/// Checks a config or asset value that has to be a finite number of at least 0.
+pub fn non_negative(field: &str, value: f32) -> Result<(), String> {
+    if !value.is_finite() || value < 0.0 {
+        return Err(format!(
+            "{} must be a non-negative number, got {}",
+            field, value
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reject_negative_and_non_finite_values() {
+        assert!(non_negative("speed", 0.0).is_ok());
+        assert!(non_negative("speed", 2.5).is_ok());
+        assert_eq!(
+            non_negative("speed", -1.0),
+            Err("speed must be a non-negative number, got -1".to_owned())
+        );
+        assert!(non_negative("speed", f32::NAN).is_err());
+        assert!(non_negative("speed", f32::INFINITY).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{items::Items, spells::Spells};
+use serde::{Deserialize, Serialize};
+use std::fmt::Formatter;
+
+/// Bump whenever messages, channels or replicated components change.
+pub const PROTOCOL_VERSION: u32 = 12;
+
+/// What a client and a server have to agree on to play together. Sent in the `Handshake`, so
+/// its layout must never change.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameVersion {
+    pub protocol: u32,
+    /// Hash of the spell and item definitions.
+    pub content_hash: u64,
+}
+
+impl GameVersion {
+    pub fn new(spells: &Spells, items: &Items) -> Self {
+        Self {
+            protocol: PROTOCOL_VERSION,
+            content_hash: content_hash(spells, items),
+        }
+    }
+}
+
+impl std::fmt::Display for GameVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "protocol {}, content {:016x}",
+            self.protocol, self.content_hash
+        )
+    }
+}
+
+/// FNV-1a over the serialized definitions, stable across builds and platforms unlike the
+/// std hasher.
+fn content_hash(spells: &Spells, items: &Items) -> u64 {
+    let spells = spells.iter().map(ron::to_string);
+    let items = items.iter().map(ron::to_string);
+
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for definition in spells.chain(items) {
+        let definition = definition.expect("Definitions are always serializable");
+        for byte in definition.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spells::{SpellDefinition, SpellId};
+
+    fn spells(damage: u32) -> Spells {
+        let fireball = SpellDefinition {
+            id: SpellId::new("fireball"),
+            name: "Fire Ball".to_owned(),
+            damage,
+            knockback: 100.0,
+            speed: 5.0,
+            radius: 0.1,
+            lifetime: 5.0,
+            cast_time: 0.0,
+            cooldown: 1.0,
+            mana_cost: 10.0,
+            price: 0,
+            effects: Vec::new(),
+            projectile: Default::default(),
+        };
+        Spells::from_definitions(vec![fireball]).unwrap()
+    }
+
+    #[test]
+    fn content_changes_the_version() {
+        let items = Items::default();
+        let version = GameVersion::new(&spells(10), &items);
+
+        assert_eq!(version, GameVersion::new(&spells(10), &items));
+        assert_ne!(version, GameVersion::new(&spells(11), &items));
+    }
+}